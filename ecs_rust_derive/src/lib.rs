@@ -0,0 +1,285 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+// #[derive(Component)] implements Component and ComponentRegistration.
+//
+// #[component(storage = "marker", name = "Paddle", clone, serialize, reflect, construct, debug)]
+//
+// storage: "dense" (default), "sparse_set", "hash_map", "btree_map" or "marker"
+//   (zero-sized, needs Default)
+// name: Stable name used for serialization and reflection, the struct name by default
+// clone: Captured by World::snapshot(), needs Clone
+// serialize: Saved and loaded by World::save()/load(), needs the serde
+//   feature of ecs_rust and Serialize + Deserialize
+// reflect: Fields accessible with World::get_field()/set_field(), needs Reflect
+// construct: Reflectable and creatable by name, e.g. by scripts, needs Reflect + Default
+// debug: Printed with Debug by World::dump(), needs Debug
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	match impl_component(&input) {
+		Ok(tokens) => tokens.into(),
+		Err(error) => error.to_compile_error().into()
+	}
+}
+
+// #[derive(Bundle)] implements Bundle for a struct whose fields are components
+// so that they can be added to an entity at once.
+#[proc_macro_derive(Bundle)]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	match impl_bundle(&input) {
+		Ok(tokens) => tokens.into(),
+		Err(error) => error.to_compile_error().into()
+	}
+}
+
+// #[derive(Reflect)] implements Reflect for a struct with named fields.
+// Fields must implement ReflectValue unless marked with #[reflect(skip)].
+#[proc_macro_derive(Reflect, attributes(reflect))]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	match impl_reflect(&input) {
+		Ok(tokens) => tokens.into(),
+		Err(error) => error.to_compile_error().into()
+	}
+}
+
+struct ComponentOptions {
+	storage: String,
+	name: Option<String>,
+	clone: bool,
+	serialize: bool,
+	reflect: bool,
+	construct: bool,
+	debug: bool
+}
+
+fn parse_component_options(input: &DeriveInput) -> syn::Result<ComponentOptions> {
+	let mut options = ComponentOptions {
+		storage: "dense".to_string(),
+		name: None,
+		clone: false,
+		serialize: false,
+		reflect: false,
+		construct: false,
+		debug: false
+	};
+
+	for attr in input.attrs.iter() {
+		if !attr.path().is_ident("component") {
+			continue;
+		}
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("storage") {
+				let value: LitStr = meta.value()?.parse()?;
+				options.storage = value.value();
+				Ok(())
+			} else if meta.path.is_ident("name") {
+				let value: LitStr = meta.value()?.parse()?;
+				options.name = Some(value.value());
+				Ok(())
+			} else if meta.path.is_ident("clone") {
+				options.clone = true;
+				Ok(())
+			} else if meta.path.is_ident("serialize") {
+				options.serialize = true;
+				Ok(())
+			} else if meta.path.is_ident("reflect") {
+				options.reflect = true;
+				Ok(())
+			} else if meta.path.is_ident("construct") {
+				options.construct = true;
+				Ok(())
+			} else if meta.path.is_ident("debug") {
+				options.debug = true;
+				Ok(())
+			} else {
+				Err(meta.error("unknown component attribute"))
+			}
+		})?;
+	}
+
+	Ok(options)
+}
+
+fn impl_component(input: &DeriveInput) -> syn::Result<TokenStream2> {
+	let options = parse_component_options(input)?;
+	let ident = &input.ident;
+	let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+	let storage = match options.storage.as_str() {
+		"dense" => quote! { ::ecs_rust::storage::DenseStorage<Self> },
+		"sparse_set" => quote! { ::ecs_rust::storage::SparseSetStorage<Self> },
+		"hash_map" => quote! { ::ecs_rust::storage::HashMapStorage<Self> },
+		"btree_map" => quote! { ::ecs_rust::storage::BTreeMapStorage<Self> },
+		"marker" => quote! { ::ecs_rust::storage::MarkerStorage<Self> },
+		_ => return Err(syn::Error::new_spanned(ident, format!("unknown storage \"{}\"", options.storage)))
+	};
+
+	let name = options.name.unwrap_or_else(|| ident.to_string());
+
+	let register_clone = match options.clone {
+		true => quote! { world.register_cloneable_component::<Self>(); },
+		false => quote! {}
+	};
+
+	let register_serialize = match options.serialize {
+		true => quote! { world.register_serializable_component::<Self>(Self::NAME); },
+		false => quote! {}
+	};
+
+	let register_reflect = match (options.reflect, options.construct) {
+		(_, true) => quote! { world.register_constructible_component::<Self>(Self::NAME); },
+		(true, false) => quote! { world.register_reflectable_component::<Self>(Self::NAME); },
+		(false, false) => quote! {}
+	};
+
+	let register_debug = match options.debug {
+		true => quote! { world.register_debuggable_component::<Self>(); },
+		false => quote! {}
+	};
+
+	Ok(quote! {
+		impl #impl_generics ::ecs_rust::component::Component for #ident #type_generics #where_clause {
+			type Storage = #storage;
+		}
+
+		impl #impl_generics ::ecs_rust::component::ComponentRegistration for #ident #type_generics #where_clause {
+			const NAME: &'static str = #name;
+
+			fn register_to(world: &mut ::ecs_rust::world::World) {
+				world.register_named_component::<Self>(Self::NAME);
+				#register_clone
+				#register_serialize
+				#register_reflect
+				#register_debug
+			}
+		}
+	})
+}
+
+fn impl_bundle(input: &DeriveInput) -> syn::Result<TokenStream2> {
+	let ident = &input.ident;
+	let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+	let fields = match &input.data {
+		Data::Struct(data) => &data.fields,
+		_ => return Err(syn::Error::new_spanned(ident, "Bundle can only be derived for structs"))
+	};
+
+	let members: Vec<syn::Member> = match fields {
+		Fields::Named(fields) => fields.named.iter()
+			.map(|field| syn::Member::Named(field.ident.clone().unwrap()))
+			.collect(),
+		Fields::Unnamed(fields) => (0..fields.unnamed.len())
+			.map(|i| syn::Member::Unnamed(i.into()))
+			.collect(),
+		Fields::Unit => Vec::new()
+	};
+
+	Ok(quote! {
+		impl #impl_generics ::ecs_rust::bundle::Bundle for #ident #type_generics #where_clause {
+			fn add_to_entity(self, manager: &mut ::ecs_rust::entity_manager::EntityManager, entity_id: usize) {
+				#(manager.add_component_to_entity(entity_id, self.#members);)*
+			}
+		}
+	})
+}
+
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+	let mut skip = false;
+	for attr in field.attrs.iter() {
+		if !attr.path().is_ident("reflect") {
+			continue;
+		}
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("skip") {
+				skip = true;
+				Ok(())
+			} else {
+				Err(meta.error("unknown reflect attribute"))
+			}
+		})?;
+	}
+	Ok(skip)
+}
+
+fn impl_reflect(input: &DeriveInput) -> syn::Result<TokenStream2> {
+	let ident = &input.ident;
+	let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+	let fields: Vec<&syn::Field> = match &input.data {
+		Data::Struct(data) => match &data.fields {
+			Fields::Named(fields) => fields.named.iter().collect(),
+			Fields::Unit => Vec::new(),
+			_ => return Err(syn::Error::new_spanned(ident, "Reflect needs named fields"))
+		},
+		_ => return Err(syn::Error::new_spanned(ident, "Reflect can only be derived for structs"))
+	};
+
+	let mut idents = Vec::new();
+	let mut names = Vec::new();
+	let mut types = Vec::new();
+	for field in fields {
+		if is_skipped(field)? {
+			continue;
+		}
+		let field_ident = field.ident.clone().unwrap();
+		names.push(field_ident.to_string());
+		idents.push(field_ident);
+		let ty = &field.ty;
+		types.push(quote! { #ty }.to_string().replace(' ', ""));
+	}
+
+	if idents.is_empty() {
+		return Ok(quote! {
+			impl #impl_generics ::ecs_rust::reflect::Reflect for #ident #type_generics #where_clause {
+				fn get_field_infos() -> Vec<::ecs_rust::reflect::FieldInfo> {
+					Vec::new()
+				}
+
+				fn get_field(&self, _name: &str) -> Option<::ecs_rust::reflect::Value> {
+					None
+				}
+
+				fn set_field(&mut self, _name: &str, _value: &::ecs_rust::reflect::Value) -> bool {
+					false
+				}
+			}
+		});
+	}
+
+	Ok(quote! {
+		impl #impl_generics ::ecs_rust::reflect::Reflect for #ident #type_generics #where_clause {
+			fn get_field_infos() -> Vec<::ecs_rust::reflect::FieldInfo> {
+				vec![#(::ecs_rust::reflect::FieldInfo {
+					name: #names,
+					type_name: #types
+				}),*]
+			}
+
+			fn get_field(&self, name: &str) -> Option<::ecs_rust::reflect::Value> {
+				match name {
+					#(#names => Some(::ecs_rust::reflect::ReflectValue::to_value(&self.#idents)),)*
+					_ => None
+				}
+			}
+
+			fn set_field(&mut self, name: &str, value: &::ecs_rust::reflect::Value) -> bool {
+				match name {
+					#(#names => match ::ecs_rust::reflect::ReflectValue::from_value(value) {
+						Some(value) => {
+							self.#idents = value;
+							true
+						},
+						None => false
+					},)*
+					_ => false
+				}
+			}
+		}
+	})
+}
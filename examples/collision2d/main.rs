@@ -0,0 +1,132 @@
+// Balls rolling into a wall, a rotated box and a triangle. Collision
+// events are printed, then a ray and a circle are cast from the left.
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
+use ecs_rust::spatial::SpatialPosition;
+use ecs_rust::collision2d::{
+	ALL_LAYERS,
+	Collider,
+	CollisionEnded,
+	CollisionIndex,
+	CollisionStarted,
+	Shape
+};
+use ecs_rust::system::System;
+
+const BALL_LAYER: u32 = 1;
+const OBSTACLE_LAYER: u32 = 2;
+
+struct Position {
+	x: f32,
+	y: f32
+}
+
+impl Component for Position {
+	type Storage = DenseStorage<Self>;
+}
+
+impl SpatialPosition for Position {
+	fn get_position(&self) -> [f32; 2] {
+		[self.x, self.y]
+	}
+}
+
+struct Velocity {
+	x: f32,
+	y: f32
+}
+
+impl Component for Velocity {
+	type Storage = DenseStorage<Self>;
+}
+
+struct Name {
+	name: &'static str
+}
+
+impl Component for Name {
+	type Storage = DenseStorage<Self>;
+}
+
+struct MoveSystem;
+
+impl System for MoveSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		manager.for_each_pair_mut::<Position, Velocity, _>(|_id, position, velocity| {
+			position.x += velocity.x;
+			position.y += velocity.y;
+		});
+	}
+}
+
+struct CollisionLogSystem;
+
+impl System for CollisionLogSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		let name = |entity_id: usize| manager.borrow_component::<Name>(entity_id).unwrap().name;
+		for event in manager.borrow_events::<CollisionStarted>().unwrap().borrow_events().iter() {
+			println!("Frame {}: {} hits {}", manager.get_frame(), name(event.entity_id1), name(event.entity_id2));
+		}
+		for event in manager.borrow_events::<CollisionEnded>().unwrap().borrow_events().iter() {
+			println!("Frame {}: {} leaves {}", manager.get_frame(), name(event.entity_id1), name(event.entity_id2));
+		}
+	}
+}
+
+fn main() {
+	let mut world = World::new();
+
+	world
+		.register_component::<Position>()
+		.register_component::<Velocity>()
+		.register_component::<Name>()
+		.add_system(MoveSystem {})
+		.add_collision2d(CollisionIndex::<Position>::with_grid(4.0))
+		.add_system(CollisionLogSystem {});
+
+	let obstacles = [
+		("Wall", [10.0, 0.0], Shape::Aabb { half_size: [1.0, 4.0] }),
+		("Box", [6.0, 10.0], Shape::OrientedBox { half_size: [1.0, 1.0], angle: std::f32::consts::FRAC_PI_4 }),
+		("Triangle", [0.0, -10.0], Shape::Polygon { vertices: vec![[-2.0, 0.0], [2.0, 0.0], [0.0, 3.0]] })
+	];
+	for (name, [x, y], shape) in obstacles {
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Name { name })
+			.add_component_to_entity(entity_id, Position { x, y })
+			.add_component_to_entity(entity_id, Collider::new(shape).with_layers(OBSTACLE_LAYER, BALL_LAYER));
+	}
+
+	// Balls don't collide with each other
+	let balls = [
+		("Ball A", [0.0, 0.0], [2.0, 0.0]),
+		("Ball B", [6.0, 4.0], [0.0, 1.0]),
+		("Ball C", [0.0, -4.0], [0.0, -1.0])
+	];
+	for (name, [x, y], [vx, vy]) in balls {
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Name { name })
+			.add_component_to_entity(entity_id, Position { x, y })
+			.add_component_to_entity(entity_id, Velocity { x: vx, y: vy })
+			.add_component_to_entity(entity_id, Collider::new(Shape::Circle { radius: 1.0 }).with_layers(BALL_LAYER, OBSTACLE_LAYER));
+	}
+
+	for _ in 0..8 {
+		world.update();
+	}
+
+	let index = world.borrow_resource::<CollisionIndex<Position>>().unwrap();
+	if let Some(hit) = index.raycast([-10.0, 0.5], [1.0, 0.0], 100.0, ALL_LAYERS) {
+		let name = world.borrow_component::<Name>(hit.entity_id).unwrap().name;
+		println!("Ray hits {} at {:?}, normal {:?}", name, hit.point, hit.normal);
+	}
+	if let Some(hit) = index.shape_cast(&Shape::Circle { radius: 0.5 }, [-10.0, -9.0], [1.0, 0.0], 100.0, OBSTACLE_LAYER) {
+		let name = world.borrow_component::<Name>(hit.entity_id).unwrap().name;
+		println!("Circle hits {} at {:?}, normal {:?}", name, hit.position, hit.normal);
+	}
+	println!("Overlapping the wall: {:?}", index.overlap(&Shape::Aabb { half_size: [0.5, 0.5] }, [10.0, 3.5], ALL_LAYERS));
+}
@@ -0,0 +1,124 @@
+// $ cargo run --example derive --features derive
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::bundle::Bundle;
+use ecs_rust::reflect::{Reflect, Value};
+use ecs_rust::system::System;
+
+#[derive(Clone, Component)]
+#[component(clone)]
+struct Namable {
+	name: &'static str
+}
+
+#[derive(Clone, Component, Reflect)]
+#[component(clone, reflect)]
+struct Position {
+	x: f32,
+	y: f32
+}
+
+#[derive(Clone, Debug, Component)]
+#[component(storage = "sparse_set", clone, debug)]
+struct Velocity {
+	x: f32,
+	y: f32
+}
+
+#[derive(Clone, Default, Component)]
+#[component(storage = "marker", clone)]
+struct Player {
+}
+
+#[derive(Bundle)]
+struct PlayerBundle {
+	namable: Namable,
+	position: Position,
+	velocity: Velocity,
+	player: Player
+}
+
+struct MoveSystem;
+struct PrintPlayerSystem;
+
+impl System for MoveSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		manager.for_each_pair_mut::<Velocity, Position, _>(|_id, velocity, position| {
+			position.x += velocity.x;
+			position.y += velocity.y;
+		});
+	}
+}
+
+impl System for PrintPlayerSystem {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		for id in accessor.borrow_ids_for_pair::<Player, Position>(manager).unwrap().iter() {
+			let name = manager.borrow_component::<Namable>(*id).unwrap();
+			let position = manager.borrow_component::<Position>(*id).unwrap();
+			println!("{} is at ({}, {})", name.name, position.x, position.y);
+		}
+	}
+}
+
+fn main() {
+	let mut world = World::new();
+
+	world
+		.register::<Namable>()
+		.register::<Position>()
+		.register::<Velocity>()
+		.register::<Player>();
+
+	{
+		let entity_id = world.create_entity();
+		world.add_bundle_to_entity(entity_id, PlayerBundle {
+			namable: Namable {name: "Alice"},
+			position: Position {x: 0.0, y: 0.0},
+			velocity: Velocity {x: 1.0, y: 2.0},
+			player: Player {}
+		});
+	}
+
+	{
+		// Tuples of components are bundles too
+		let entity_id = world.create_entity();
+		world.add_bundle_to_entity(entity_id, (
+			Namable {name: "Rock"},
+			Position {x: 5.0, y: 5.0}
+		));
+	}
+
+	world
+		.add_system(MoveSystem {})
+		.add_system(PrintPlayerSystem {});
+
+	let snapshot = world.snapshot().unwrap();
+
+	for _i in 0..3 {
+		world.update();
+	}
+
+	println!("Restore");
+	world.restore(&snapshot);
+	world.update();
+
+	// Components can be inspected and edited without knowing their types
+	for type_info in world.borrow_type_registry().borrow_type_infos().iter() {
+		println!("{}: {} bytes", type_info.get_name(), type_info.get_size());
+		if let Some(fields) = type_info.borrow_fields() {
+			for field in fields.iter() {
+				println!("  {}: {}", field.name, field.type_name);
+			}
+		}
+	}
+
+	world.set_field(0, "Position.x", &Value::Float(10.0));
+	println!("Position.x = {}", world.get_field(0, "Position.x").unwrap());
+	world.update();
+
+	// Stable listing of the whole world, e.g. for golden file tests
+	world.remove_entity(1);
+	print!("{}", world.dump());
+}
@@ -0,0 +1,85 @@
+// Components defined at runtime, e.g. loaded from a mod file
+
+use std::convert::TryInto;
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::dynamic::{ComponentId, ComponentLayout};
+use ecs_rust::storage::DenseStorage;
+use ecs_rust::system::System;
+
+struct Namable {
+	name: &'static str
+}
+
+// Damages entities having Health while they are Poisoned
+struct PoisonSystem {
+	health_id: ComponentId,
+	poisoned_id: ComponentId
+}
+
+struct PrintHealthSystem {
+	namable_id: ComponentId,
+	health_id: ComponentId
+}
+
+impl Component for Namable {
+	type Storage = DenseStorage<Self>;
+}
+
+impl System for PoisonSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		for id in manager.get_entity_ids_for(&[self.health_id, self.poisoned_id]) {
+			let bytes = manager.borrow_dynamic_component_mut(id, self.health_id).unwrap();
+			let health = u32::from_ne_bytes((&*bytes).try_into().unwrap());
+			bytes.copy_from_slice(&health.saturating_sub(10).to_ne_bytes());
+		}
+	}
+}
+
+impl System for PrintHealthSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		// Static and dynamic components can be mixed in a query
+		for id in manager.get_entity_ids_for(&[self.namable_id, self.health_id]) {
+			let name = manager.borrow_component::<Namable>(id).unwrap();
+			let bytes = manager.borrow_dynamic_component(id, self.health_id).unwrap();
+			println!("{} health: {}", name.name, u32::from_ne_bytes(bytes.try_into().unwrap()));
+		}
+	}
+}
+
+fn main() {
+	let mut world = World::new();
+
+	world.register_component::<Namable>();
+
+	// Health is a u32 and Poisoned is a tag
+	let health_id = world.register_dynamic_component(ComponentLayout::new("Health", 4, 4)).unwrap();
+	let poisoned_id = world.register_dynamic_component(ComponentLayout::new("Poisoned", 0, 1)).unwrap();
+
+	{
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Namable {name: "Alice"})
+			.add_dynamic_component_to_entity(entity_id, health_id, &100u32.to_ne_bytes())
+			.add_dynamic_component_to_entity(entity_id, poisoned_id, &[]);
+	}
+
+	{
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Namable {name: "Bob"})
+			.add_dynamic_component_to_entity(entity_id, health_id, &80u32.to_ne_bytes());
+	}
+
+	let namable_id = world.get_component_id::<Namable>();
+
+	world
+		.add_system(PoisonSystem {health_id, poisoned_id})
+		.add_system(PrintHealthSystem {namable_id, health_id});
+
+	for _i in 0..3 {
+		world.update();
+	}
+}
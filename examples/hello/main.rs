@@ -1,6 +1,7 @@
 use ecs_rust::world::World;
 use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
 use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
 use ecs_rust::system::System;
 
 struct Person {
@@ -10,6 +11,7 @@ struct Person {
 struct HelloSystem;
 
 impl Component for Person {
+	type Storage = DenseStorage<Self>;
 }
 
 impl System for HelloSystem {
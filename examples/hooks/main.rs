@@ -0,0 +1,109 @@
+// Component lifecycle hooks keeping a resource in sync with the
+// components and despawning the hit marker of a removed target.
+
+use std::collections::HashSet;
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
+use ecs_rust::system::System;
+
+struct Target {
+	hit_marker_id: usize
+}
+
+impl Component for Target {
+	type Storage = DenseStorage<Self>;
+}
+
+struct Health {
+	value: i32
+}
+
+impl Component for Health {
+	type Storage = DenseStorage<Self>;
+}
+
+struct Name {
+	name: &'static str
+}
+
+impl Component for Name {
+	type Storage = DenseStorage<Self>;
+}
+
+// Maintained by the Target hooks
+struct TargetIds {
+	ids: HashSet<usize>
+}
+
+struct DamageSystem;
+
+impl System for DamageSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		let mut dead_ids = Vec::new();
+		manager.for_each_mut::<Health, _>(|id, health| {
+			health.value -= 40;
+			if health.value <= 0 {
+				dead_ids.push(id);
+			}
+		});
+		for id in dead_ids {
+			manager.borrow_commands_mut().despawn(id);
+		}
+	}
+}
+
+fn main() {
+	let mut world = World::new();
+
+	world
+		.register_component::<Target>()
+		.register_component::<Health>()
+		.register_component::<Name>()
+		.insert_resource(TargetIds { ids: HashSet::new() })
+		.add_system(DamageSystem {});
+
+	world
+		.on_add::<Target, _>(|manager, entity_id| {
+			manager.borrow_resource_mut::<TargetIds>().unwrap().ids.insert(entity_id);
+		})
+		.on_remove::<Target, _>(|manager, entity_id| {
+			manager.borrow_resource_mut::<TargetIds>().unwrap().ids.remove(&entity_id);
+			// Removing entities in the middle of a removal is deferred
+			let hit_marker_id = manager.borrow_component::<Target>(entity_id).unwrap().hit_marker_id;
+			manager.borrow_commands_mut().despawn(hit_marker_id);
+		})
+		.on_replace::<Health, _>(|manager, entity_id| {
+			let health = manager.borrow_component::<Health>(entity_id).unwrap();
+			println!("Entity {} health was {}", entity_id, health.value);
+		})
+		.on_insert::<Health, _>(|manager, entity_id| {
+			let health = manager.borrow_component::<Health>(entity_id).unwrap();
+			println!("Entity {} health is {}", entity_id, health.value);
+		});
+
+	for (name, value) in [("Slime", 50), ("Dragon", 150)] {
+		let hit_marker_id = world.create_entity();
+		world.add_component_to_entity(hit_marker_id, Name { name: "Hit marker" });
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Name { name })
+			.add_component_to_entity(entity_id, Health { value })
+			.add_component_to_entity(entity_id, Target { hit_marker_id });
+	}
+
+	// Replaces the Slime health
+	world.insert_component_to_entity(1, Health { value: 70 });
+
+	for _ in 0..3 {
+		world.update();
+		let mut ids = world.borrow_resource::<TargetIds>().unwrap().ids.iter().copied().collect::<Vec<usize>>();
+		ids.sort();
+		let names = world.get_entity_ids().into_iter()
+			.map(|id| world.borrow_component::<Name>(id).unwrap().name)
+			.collect::<Vec<&str>>();
+		println!("Targets: {:?}, entities: {:?}", ids, names);
+	}
+}
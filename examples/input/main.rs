@@ -0,0 +1,84 @@
+// A player jumping and walking with scripted input, the way a headless
+// test would drive it. A platform's event loop pushes to the queue instead.
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
+use ecs_rust::input::{Binding, Input, InputEvent, MouseButton, ScriptedInput};
+use ecs_rust::system::System;
+
+struct Player {
+	x: f32,
+	jumps: u32
+}
+
+impl Component for Player {
+	type Storage = DenseStorage<Self>;
+}
+
+struct PlayerControlSystem;
+
+impl System for PlayerControlSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		let input = manager.borrow_resource::<Input>().unwrap();
+		let right = input.is_action_pressed("right");
+		let jump = input.is_action_just_pressed("jump");
+		let pointer_position = match input.is_pointer_moved() {
+			true => input.get_pointer_position(),
+			false => None
+		};
+		manager.for_each_mut::<Player, _>(|id, player| {
+			if right {
+				player.x += 1.0;
+			}
+			if jump {
+				player.jumps += 1;
+			}
+			if let Some([x, _y]) = pointer_position {
+				player.x = x;
+			}
+			println!("Player {} x: {}, jumps: {}", id, player.x, player.jumps);
+		});
+	}
+}
+
+fn main() {
+	let key = |key: &str| key.to_string();
+
+	let mut input = Input::new();
+	input
+		.bind("right", Binding::Key(key("ArrowRight")))
+		.bind("right", Binding::Key(key("KeyD")))
+		.bind("jump", Binding::Key(key("Space")))
+		.bind("jump", Binding::Mouse(MouseButton::Left))
+		.add_source(ScriptedInput::new()
+			.step(vec![InputEvent::KeyPressed(key("ArrowRight"))])
+			// Holding the second binding doesn't press the action again
+			.step(vec![InputEvent::KeyPressed(key("KeyD")), InputEvent::KeyPressed(key("Space"))])
+			.step(vec![InputEvent::KeyReleased(key("ArrowRight")), InputEvent::KeyReleased(key("Space"))])
+			.step(vec![InputEvent::KeyReleased(key("KeyD"))])
+			// A click within an update still counts as a jump
+			.step(vec![InputEvent::ButtonPressed(MouseButton::Left), InputEvent::ButtonReleased(MouseButton::Left)])
+			.step(vec![InputEvent::PointerMoved { x: 10.0, y: 5.0 }]));
+
+	let queue = input.get_queue();
+
+	let mut world = World::new();
+
+	world
+		.register_component::<Player>()
+		.add_input(input)
+		.add_system(PlayerControlSystem {});
+
+	let entity_id = world.create_entity();
+	world.add_component_to_entity(entity_id, Player { x: 0.0, jumps: 0 });
+
+	for _ in 0..6 {
+		world.update();
+	}
+
+	// Events from the platform are applied in the next update
+	queue.push(InputEvent::KeyPressed(key("Space")));
+	world.update();
+}
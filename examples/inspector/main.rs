@@ -0,0 +1,101 @@
+// Headless simulation which can be inspected while running
+//
+// $ cargo run --example inspector --features inspector
+// $ cargo run --bin ecs_rust_inspect --features inspector -- entities
+// $ cargo run --bin ecs_rust_inspect --features inspector -- set 0 Position.x 10
+
+use std::thread;
+use std::time::Duration;
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::inspector::DEFAULT_PORT;
+use ecs_rust::reflect::{FieldInfo, Reflect, Value};
+use ecs_rust::storage::DenseStorage;
+use ecs_rust::system::System;
+
+struct Position {
+	x: f32,
+	y: f32
+}
+
+impl Component for Position {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Reflect for Position {
+	fn get_field_infos() -> Vec<FieldInfo> {
+		vec![
+			FieldInfo {name: "x", type_name: "f32"},
+			FieldInfo {name: "y", type_name: "f32"}
+		]
+	}
+
+	fn get_field(&self, name: &str) -> Option<Value> {
+		match name {
+			"x" => Some(Value::Float(self.x as f64)),
+			"y" => Some(Value::Float(self.y as f64)),
+			_ => None
+		}
+	}
+
+	fn set_field(&mut self, name: &str, value: &Value) -> bool {
+		let value = match value {
+			Value::Float(value) => *value as f32,
+			Value::Int(value) => *value as f32,
+			_ => return false
+		};
+		match name {
+			"x" => self.x = value,
+			"y" => self.y = value,
+			_ => return false
+		};
+		true
+	}
+}
+
+struct Velocity {
+	x: f32,
+	y: f32
+}
+
+impl Component for Velocity {
+	type Storage = DenseStorage<Self>;
+}
+
+struct MoveSystem;
+
+impl System for MoveSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		manager.for_each_pair_mut::<Velocity, Position, _>(|_id, velocity, position| {
+			position.x += velocity.x;
+			position.y += velocity.y;
+		});
+	}
+}
+
+fn main() {
+	let mut world = World::new();
+
+	world
+		.register_reflectable_component::<Position>("Position")
+		.register_component::<Velocity>();
+
+	for i in 0..3 {
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Position {x: 0.0, y: 0.0})
+			.add_component_to_entity(entity_id, Velocity {x: i as f32, y: 1.0});
+	}
+
+	world.add_system(MoveSystem {});
+
+	let address = world.start_inspector(DEFAULT_PORT).unwrap();
+	println!("Inspector listening on {}", address);
+
+	loop {
+		world.update();
+		thread::sleep(Duration::from_millis(100));
+	}
+}
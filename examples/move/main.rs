@@ -1,6 +1,7 @@
 use ecs_rust::world::World;
 use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
 use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
 use ecs_rust::system::System;
 
 struct Namable {
@@ -26,15 +27,19 @@ struct MoveSystem;
 struct PrintPositionSystem;
 
 impl Component for Namable {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Position {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Velocity {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Step {
+	type Storage = DenseStorage<Self>;
 }
 
 impl System for PrintStepSystem {
@@ -51,7 +56,7 @@ impl System for MoveSystem {
 	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
 		let entity_ids = accessor.borrow_ids_for_pair::<Velocity, Position>(manager).unwrap();
 		for id in entity_ids.iter() {
-			let (velocity, mut position) = manager.borrow_component_pair_mut::<Velocity, Position>(*id).unwrap();
+			let (velocity, position) = manager.borrow_component_pair_mut::<Velocity, Position>(*id).unwrap();
 			position.x += velocity.x;
 			position.y += velocity.y;
 		}
@@ -0,0 +1,118 @@
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
+use ecs_rust::system::System;
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Namable {
+	name: String
+}
+
+#[derive(Serialize, Deserialize)]
+struct Position {
+	x: f32,
+	y: f32
+}
+
+#[derive(Serialize, Deserialize)]
+struct Velocity {
+	x: f32,
+	y: f32
+}
+
+struct MoveSystem;
+
+impl Component for Namable {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Component for Position {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Component for Velocity {
+	type Storage = DenseStorage<Self>;
+}
+
+impl System for MoveSystem {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		let entity_ids = accessor.borrow_ids_for_pair::<Velocity, Position>(manager).unwrap();
+		for id in entity_ids.iter() {
+			let (velocity, position) = manager.borrow_component_pair_mut::<Velocity, Position>(*id).unwrap();
+			position.x += velocity.x;
+			position.y += velocity.y;
+		}
+	}
+}
+
+fn create_world() -> World {
+	let mut world = World::new();
+
+	world
+		.register_serializable_component::<Namable>("Namable")
+		.register_serializable_component::<Position>("Position")
+		.register_serializable_component::<Velocity>("Velocity")
+		.add_system(MoveSystem {});
+
+	world
+}
+
+fn print_world(world: &World) {
+	println!("{}", serde_json::to_string(&SaveData(world)).unwrap());
+}
+
+// Adapter to pass World to the serde_json/ron helper functions
+struct SaveData<'a>(&'a World);
+
+impl<'a> Serialize for SaveData<'a> {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		self.0.save(serializer)
+	}
+}
+
+fn main() {
+	let mut world = create_world();
+
+	{
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Namable {name: "Alice".to_string()})
+			.add_component_to_entity(entity_id, Position {x: 0.0, y: 0.0})
+			.add_component_to_entity(entity_id, Velocity {x: 1.0, y: 2.0});
+	}
+
+	{
+		// Unmovable
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Namable {name: "Rock".to_string()})
+			.add_component_to_entity(entity_id, Position {x: 5.0, y: 5.0});
+	}
+
+	world.update();
+	print_world(&world);
+
+	// JSON
+	let json = serde_json::to_string(&SaveData(&world)).unwrap();
+	let mut loaded = create_world();
+	loaded.load(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+	loaded.update();
+	print_world(&loaded);
+
+	// RON
+	let text = ron::to_string(&SaveData(&world)).unwrap();
+	let mut loaded = create_world();
+	loaded.load(&mut ron::Deserializer::from_str(&text).unwrap()).unwrap();
+	loaded.update();
+	print_world(&loaded);
+
+	// bincode
+	let bytes = bincode::options().serialize(&SaveData(&world)).unwrap();
+	let mut loaded = create_world();
+	loaded.load(&mut bincode::Deserializer::from_slice(&bytes, bincode::options())).unwrap();
+	loaded.update();
+	print_world(&loaded);
+}
@@ -0,0 +1,81 @@
+// $ cargo run --example scenes --features serde
+//
+// A room scene is saved once and loaded several times into a live world
+
+use std::collections::HashMap;
+
+use ecs_rust::world::World;
+use ecs_rust::component::Component;
+use ecs_rust::serialization::MapEntities;
+use ecs_rust::storage::DenseStorage;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Name {
+	name: String
+}
+
+impl Component for Name {
+	type Storage = DenseStorage<Self>;
+}
+
+// Refers to another entity
+#[derive(Debug, Serialize, Deserialize)]
+struct Guards {
+	entity_id: usize
+}
+
+impl Component for Guards {
+	type Storage = DenseStorage<Self>;
+}
+
+impl MapEntities for Guards {
+	fn map_entities(&mut self, entity_map: &HashMap<usize, usize>) {
+		if let Some(entity_id) = entity_map.get(&self.entity_id) {
+			self.entity_id = *entity_id;
+		}
+	}
+}
+
+fn create_world() -> World {
+	let mut world = World::new();
+	world
+		.register_serializable_component::<Name>("Name")
+		.register_serializable_component::<Guards>("Guards")
+		.register_debuggable_component::<Name>()
+		.register_debuggable_component::<Guards>()
+		.register_map_entities_component::<Guards>();
+	world
+}
+
+fn main() {
+	// Author the room in its own world
+	let room = {
+		let mut world = create_world();
+		let door = world.create_entity();
+		world.add_component_to_entity(door, Name {name: "Door".to_string()});
+		let guard = world.create_entity();
+		world
+			.add_component_to_entity(guard, Name {name: "Guard".to_string()})
+			.add_component_to_entity(guard, Guards {entity_id: door});
+		let mut room = Vec::new();
+		world.save_scene(&[door, guard], &mut ron::Serializer::new(&mut room, None).unwrap()).unwrap();
+		String::from_utf8(room).unwrap()
+	};
+	println!("Room scene: {}", room);
+
+	let mut world = create_world();
+	{
+		let entity_id = world.create_entity();
+		world.add_component_to_entity(entity_id, Name {name: "Player".to_string()});
+	}
+
+	let first_room = world.load_scene(&mut ron::Deserializer::from_str(&room).unwrap()).unwrap();
+	let second_room = world.load_scene(&mut ron::Deserializer::from_str(&room).unwrap()).unwrap();
+	println!("Guard of the second room is {}", world.borrow_scene_entity_map(second_room).unwrap()[&1]);
+	print!("{}", world.dump());
+
+	world.unload_scene(first_room);
+	println!("First room unloaded");
+	print!("{}", world.dump());
+}
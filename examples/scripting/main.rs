@@ -0,0 +1,72 @@
+// $ cargo run --example scripting --features scripting,derive
+//
+// move.rhai is reloaded when it's modified while running
+
+use std::path::Path;
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::reflect::Reflect;
+use ecs_rust::scripting::{ScriptErrors, ScriptResources, ScriptSystem};
+use ecs_rust::system::System;
+
+#[derive(Clone, Default, Component, Reflect)]
+#[component(construct)]
+struct Position {
+	x: f32,
+	y: f32
+}
+
+#[derive(Clone, Default, Component, Reflect)]
+#[component(construct)]
+struct Velocity {
+	x: f32,
+	y: f32
+}
+
+struct PrintSystem;
+
+impl System for PrintSystem {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		for id in accessor.borrow_ids::<Position>(manager).unwrap().iter() {
+			let position = manager.borrow_component::<Position>(*id).unwrap();
+			println!("Entity {} is at ({}, {})", id, position.x, position.y);
+		}
+	}
+}
+
+fn main() {
+	let mut world = World::new();
+
+	world
+		.register::<Position>()
+		.register::<Velocity>();
+
+	{
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Position {x: 0.0, y: 0.0})
+			.add_component_to_entity(entity_id, Velocity {x: 1.0, y: 0.5});
+	}
+
+	let mut resources = ScriptResources::new();
+	resources.set("speed", 1.0);
+	world.insert_resource(resources);
+
+	let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/scripting/move.rhai");
+	let move_system = ScriptSystem::from_file("move", path).unwrap();
+	// Errors are reported and the other systems keep running
+	let broken_system = ScriptSystem::new("broken", "set(0, \"Position.z\", 1.0);").unwrap();
+
+	world
+		.add_system(move_system)
+		.add_system(broken_system)
+		.add_system(PrintSystem {});
+
+	for _i in 0..5 {
+		world.update();
+	}
+
+	println!("{} script errors", world.borrow_resource::<ScriptErrors>().unwrap().borrow_errors().len());
+}
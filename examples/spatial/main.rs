@@ -0,0 +1,106 @@
+// Guards perceive the intruders near them with a spatial index instead of
+// checking every pair.
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::storage::{DenseStorage, MarkerStorage};
+use ecs_rust::spatial::{Aabb, SpatialIndex, SpatialPosition};
+use ecs_rust::system::System;
+
+struct Position {
+	x: f32,
+	y: f32
+}
+
+impl Component for Position {
+	type Storage = DenseStorage<Self>;
+}
+
+impl SpatialPosition for Position {
+	fn get_position(&self) -> [f32; 2] {
+		[self.x, self.y]
+	}
+}
+
+struct Velocity {
+	x: f32,
+	y: f32
+}
+
+impl Component for Velocity {
+	type Storage = DenseStorage<Self>;
+}
+
+#[derive(Default)]
+struct Guard {
+}
+
+impl Component for Guard {
+	type Storage = MarkerStorage<Self>;
+}
+
+struct MoveSystem;
+
+impl System for MoveSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		manager.for_each_pair_mut::<Position, Velocity, _>(|_id, position, velocity| {
+			position.x += velocity.x;
+			position.y += velocity.y;
+		});
+	}
+}
+
+struct PerceptionSystem;
+
+impl System for PerceptionSystem {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		let index = manager.borrow_resource::<SpatialIndex<Position>>().unwrap();
+		for guard_id in accessor.borrow_ids_for_pair::<Guard, Position>(manager).unwrap().iter() {
+			let position = manager.borrow_component::<Position>(*guard_id).unwrap();
+			let seen_ids = index.query_radius([position.x, position.y], 3.0).into_iter()
+				.filter(|id| id != guard_id)
+				.collect::<Vec<usize>>();
+			let nearest_ids = index.query_nearest([position.x, position.y], 2);
+			println!("Guard {} sees {:?}, nearest {:?}", guard_id, seen_ids, nearest_ids);
+		}
+	}
+}
+
+fn main() {
+	for index in [
+		SpatialIndex::<Position>::with_grid(4.0),
+		SpatialIndex::<Position>::with_quad_tree(Aabb::new([-16.0, -16.0], [16.0, 16.0]), 2)
+	] {
+		let mut world = World::new();
+
+		world
+			.register_component::<Position>()
+			.register_component::<Velocity>()
+			.register_component::<Guard>()
+			.add_system(MoveSystem {})
+			.add_spatial_index(index)
+			.add_system(PerceptionSystem {});
+
+		for (x, y) in [(0.0, 0.0), (10.0, 10.0)] {
+			let entity_id = world.create_entity();
+			world
+				.add_component_to_entity(entity_id, Position { x, y })
+				.add_component_to_entity(entity_id, Guard {});
+		}
+
+		for (x, y, vx, vy) in [(-4.0, 0.0, 1.0, 0.0), (10.0, 4.0, 0.0, 1.0), (20.0, 20.0, -1.0, -1.0)] {
+			let entity_id = world.create_entity();
+			world
+				.add_component_to_entity(entity_id, Position { x, y })
+				.add_component_to_entity(entity_id, Velocity { x: vx, y: vy });
+		}
+
+		for _ in 0..3 {
+			world.update();
+		}
+
+		let index = world.borrow_resource::<SpatialIndex<Position>>().unwrap();
+		println!("In the top right: {:?}", index.query_aabb(&Aabb::new([5.0, 5.0], [20.0, 20.0])));
+	}
+}
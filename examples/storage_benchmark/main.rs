@@ -0,0 +1,172 @@
+// Compares StorageMode::PerComponent and StorageMode::Archetype.
+// Run with --release to get meaningful numbers.
+//
+// $ cargo run --release --example storage_benchmark
+
+use std::env;
+use std::fs::File;
+use std::time::Instant;
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager, StorageMode};
+use ecs_rust::component::Component;
+use ecs_rust::storage::{DenseStorage, MarkerStorage};
+use ecs_rust::system::System;
+
+struct Position {
+	x: f32,
+	y: f32
+}
+
+struct Velocity {
+	x: f32,
+	y: f32
+}
+
+struct Acceleration {
+	x: f32,
+	y: f32
+}
+
+#[derive(Default)]
+struct Tag {
+}
+
+// Sum of all positions to make sure both modes compute the same result
+struct Sum {
+	value: f64
+}
+
+struct MoveSystem;
+struct SumSystem;
+struct AccelerateSystem;
+struct ChurnSystem {
+	frame: usize
+}
+
+impl Component for Position {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Component for Velocity {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Component for Acceleration {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Component for Tag {
+	type Storage = MarkerStorage<Self>;
+}
+
+impl System for MoveSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		manager.for_each_pair_mut::<Velocity, Position, _>(|_id, velocity, position| {
+			position.x += velocity.x;
+			position.y += velocity.y;
+		});
+	}
+}
+
+impl System for AccelerateSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		manager.for_each_pair_mut::<Acceleration, Velocity, _>(|_id, acceleration, velocity| {
+			velocity.x += acceleration.x;
+			velocity.y += acceleration.y;
+		});
+	}
+}
+
+// Adds and removes a component to see the cost of moving entities
+// between archetypes
+impl System for ChurnSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		self.frame += 1;
+		for entity_id in (self.frame % 100..ENTITY_COUNT).step_by(100) {
+			match manager.has_component::<Tag>(entity_id) {
+				true => manager.remove_component_from_entity::<Tag>(entity_id),
+				false => manager.add_component_to_entity(entity_id, Tag {})
+			};
+		}
+	}
+}
+
+impl System for SumSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		let mut sum = 0.0;
+		manager.for_each_mut::<Position, _>(|_id, position| {
+			sum += (position.x + position.y) as f64;
+		});
+		manager.borrow_resource_mut::<Sum>().unwrap().value = sum;
+	}
+}
+
+const ENTITY_COUNT: usize = 100_000;
+const FRAME_COUNT: usize = 100;
+
+fn run(storage_mode: StorageMode) {
+	let mut world = World::with_storage_mode(storage_mode);
+
+	world
+		.register_component::<Position>()
+		.register_component::<Velocity>()
+		.register_component::<Acceleration>()
+		.register_component::<Tag>();
+
+	for i in 0..ENTITY_COUNT {
+		let entity_id = world.create_entity();
+		world.add_component_to_entity(entity_id, Position {x: 0.0, y: 0.0});
+		if i % 2 == 0 {
+			world.add_component_to_entity(entity_id, Velocity {x: 1.0, y: 0.5});
+		}
+		if i % 4 == 0 {
+			world.add_component_to_entity(entity_id, Acceleration {x: 0.01, y: 0.02});
+		}
+		if i % 3 == 0 {
+			world.add_component_to_entity(entity_id, Tag {});
+		}
+	}
+
+	world
+		.add_system(AccelerateSystem {})
+		.add_system(MoveSystem {});
+
+	let start = Instant::now();
+	for _i in 0..FRAME_COUNT {
+		world.update();
+	}
+	let query_time = start.elapsed();
+
+	world.add_system(ChurnSystem {frame: 0});
+	world.start_profiling(FRAME_COUNT);
+
+	let start = Instant::now();
+	for _i in 0..FRAME_COUNT {
+		world.update();
+	}
+	let churn_time = start.elapsed();
+
+	// Per system breakdown of the churn frames
+	let frame_stats = world.stop_profiling().unwrap();
+	for stats in frame_stats.borrow_system_stats().iter().chain(frame_stats.borrow_command_stats().iter()) {
+		println!("  {}: min {:?}, avg {:?}, max {:?}", stats.get_name(), stats.get_min(), stats.get_avg(), stats.get_max());
+	}
+	let trace_path = env::temp_dir().join(format!("storage_benchmark_{:?}.json", storage_mode));
+	frame_stats.write_chrome_trace(&mut File::create(&trace_path).unwrap()).unwrap();
+	println!("  Chrome trace written to {}", trace_path.display());
+
+	world
+		.insert_resource(Sum {value: 0.0})
+		.add_system(SumSystem {});
+	world.update();
+	let sum = world.borrow_resource::<Sum>().unwrap().value;
+
+	println!("{:?}: queries {:?}, queries with churn {:?}, checksum {}",
+		storage_mode, query_time, churn_time, sum);
+}
+
+fn main() {
+	run(StorageMode::PerComponent);
+	run(StorageMode::Archetype);
+}
@@ -0,0 +1,99 @@
+// A turret firing bullets which despawn after their lifetime, and a
+// repeating timer spawning waves. Time advances 100ms per update.
+
+use std::time::Duration;
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::storage::MarkerStorage;
+use ecs_rust::time::{Cooldown, CooldownReady, Lifetime, Time, Timer, TimerFinished};
+use ecs_rust::system::System;
+
+#[derive(Default)]
+struct Turret {
+}
+
+impl Component for Turret {
+	type Storage = MarkerStorage<Self>;
+}
+
+#[derive(Default)]
+struct Bullet {
+}
+
+impl Component for Bullet {
+	type Storage = MarkerStorage<Self>;
+}
+
+struct FireSystem;
+
+impl System for FireSystem {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		let elapsed = manager.borrow_resource::<Time>().unwrap().get_elapsed();
+		for event in manager.borrow_events::<CooldownReady>().unwrap().borrow_events().iter() {
+			println!("{:?}: Turret {} is ready", elapsed, event.entity_id);
+		}
+
+		let turret_ids = accessor.borrow_ids_for_pair::<Turret, Cooldown>(manager).unwrap().clone();
+		for turret_id in turret_ids {
+			if manager.borrow_component_mut::<Cooldown>(turret_id).unwrap().trigger() {
+				let bullet_id = manager.create_entity();
+				manager
+					.add_component_to_entity(bullet_id, Bullet {})
+					.add_component_to_entity(bullet_id, Lifetime::new(Duration::from_millis(150)));
+				println!("{:?}: Turret {} fires bullet {}", elapsed, turret_id, bullet_id);
+			}
+		}
+	}
+}
+
+struct WaveSystem;
+
+impl System for WaveSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		let elapsed = manager.borrow_resource::<Time>().unwrap().get_elapsed();
+		for event in manager.borrow_events::<TimerFinished>().unwrap().borrow_events().iter() {
+			println!("{:?}: Wave from spawner {}", elapsed, event.entity_id);
+		}
+	}
+}
+
+struct PrintBulletSystem;
+
+impl System for PrintBulletSystem {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		let bullet_ids = accessor.borrow_ids::<Bullet>(manager).unwrap();
+		println!("Bullets: {:?}", bullet_ids);
+	}
+}
+
+fn main() {
+	let mut world = World::new();
+
+	world
+		.register_component::<Turret>()
+		.register_component::<Bullet>()
+		.add_time(Time::with_fixed_delta(Duration::from_millis(100)))
+		.add_system(FireSystem {})
+		.add_system(WaveSystem {})
+		.add_system(PrintBulletSystem {});
+
+	let turret_id = world.create_entity();
+	world
+		.add_component_to_entity(turret_id, Turret {})
+		.add_component_to_entity(turret_id, Cooldown::new(Duration::from_millis(300)));
+
+	let spawner_id = world.create_entity();
+	world.add_component_to_entity(spawner_id, Timer::repeating(Duration::from_millis(400)));
+
+	for _ in 0..8 {
+		world.update();
+	}
+
+	// The paused timer doesn't finish
+	world.borrow_component_mut::<Timer>(spawner_id).unwrap().pause();
+	for _ in 0..4 {
+		world.update();
+	}
+}
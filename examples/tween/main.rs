@@ -0,0 +1,104 @@
+// A sprite sliding right and up in sequence through its reflected fields,
+// and its alpha pulsing back and forth. Time advances 100ms per update.
+
+use std::time::Duration;
+
+use ecs_rust::world::World;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
+use ecs_rust::reflect::{FieldInfo, Reflect, Value};
+use ecs_rust::time::Time;
+use ecs_rust::tween::{Easing, Tween, TweenCompleted, lerp};
+use ecs_rust::system::System;
+
+struct Position {
+	x: f32,
+	y: f32
+}
+
+impl Component for Position {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Reflect for Position {
+	fn get_field_infos() -> Vec<FieldInfo> {
+		vec![
+			FieldInfo {name: "x", type_name: "f32"},
+			FieldInfo {name: "y", type_name: "f32"}
+		]
+	}
+
+	fn get_field(&self, name: &str) -> Option<Value> {
+		match name {
+			"x" => Some(Value::Float(self.x as f64)),
+			"y" => Some(Value::Float(self.y as f64)),
+			_ => None
+		}
+	}
+
+	fn set_field(&mut self, name: &str, value: &Value) -> bool {
+		let value = match value {
+			Value::Float(value) => *value as f32,
+			Value::Int(value) => *value as f32,
+			_ => return false
+		};
+		match name {
+			"x" => self.x = value,
+			"y" => self.y = value,
+			_ => return false
+		};
+		true
+	}
+}
+
+struct Color {
+	rgba: [f32; 4]
+}
+
+impl Component for Color {
+	type Storage = DenseStorage<Self>;
+}
+
+struct PrintSystem;
+
+impl System for PrintSystem {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		let elapsed = manager.borrow_resource::<Time>().unwrap().get_elapsed();
+		for id in accessor.borrow_ids_for_pair::<Position, Color>(manager).unwrap().iter() {
+			let position = manager.borrow_component::<Position>(*id).unwrap();
+			let color = manager.borrow_component::<Color>(*id).unwrap();
+			println!("{:?}: ({:.2}, {:.2}) alpha {:.2}", elapsed, position.x, position.y, color.rgba[3]);
+		}
+		for event in manager.borrow_events::<TweenCompleted>().unwrap().borrow_events().iter() {
+			println!("{:?}: Tween {} of entity {} completed", elapsed, event.tag, event.entity_id);
+		}
+	}
+}
+
+fn main() {
+	let mut world = World::new();
+
+	world
+		.register_reflectable_component::<Position>("Position")
+		.register_component::<Color>()
+		.add_time(Time::with_fixed_delta(Duration::from_millis(100)))
+		.add_tween::<Position>()
+		.add_tween::<Color>()
+		.add_system(PrintSystem {});
+
+	let entity_id = world.create_entity();
+	world
+		.add_component_to_entity(entity_id, Position { x: 0.0, y: 0.0 })
+		.add_component_to_entity(entity_id, Color { rgba: [1.0, 0.5, 0.0, 1.0] })
+		.add_component_to_entity(entity_id, Tween::<Position>::field(Duration::from_millis(400), Easing::QuadOut, "Position.x", 0.0, 10.0)
+			.then_field(Duration::from_millis(400), Easing::BounceOut, "Position.y", 0.0, 5.0)
+			.with_tag(1))
+		.add_component_to_entity(entity_id, Tween::new(Duration::from_millis(300), Easing::SineInOut, |color: &mut Color, ratio| {
+			color.rgba[3] = lerp(1.0, 0.2, ratio);
+		}).with_yoyo(true).with_repeat(2).with_tag(2));
+
+	for _ in 0..10 {
+		world.update();
+	}
+}
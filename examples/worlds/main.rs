@@ -0,0 +1,91 @@
+// A lobby world and match worlds in one process. Players are moved
+// between them with their components.
+
+use ecs_rust::world::World;
+use ecs_rust::entity::Entity;
+use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
+use ecs_rust::component::Component;
+use ecs_rust::storage::{DenseStorage, MarkerStorage};
+use ecs_rust::system::System;
+
+#[derive(Debug)]
+struct Player {
+	name: &'static str,
+	score: u32
+}
+
+impl Component for Player {
+	type Storage = DenseStorage<Self>;
+}
+
+#[derive(Debug, Default)]
+struct Ready {
+}
+
+impl Component for Ready {
+	type Storage = MarkerStorage<Self>;
+}
+
+struct ScoreSystem;
+
+impl System for ScoreSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		manager.for_each_mut::<Player, _>(|_id, player| {
+			player.score += 1;
+		});
+	}
+}
+
+fn create_match_world() -> World {
+	let mut world = World::new();
+	world
+		.register_debuggable_component::<Player>()
+		.add_system(ScoreSystem {});
+	world
+}
+
+fn main() {
+	let mut lobby = World::new();
+	lobby
+		.register_debuggable_component::<Player>()
+		.register_debuggable_component::<Ready>();
+
+	let mut players = Vec::new();
+	for name in ["Alice", "Bob", "Carol", "Dave"].iter() {
+		let entity_id = lobby.create_entity();
+		lobby
+			.add_component_to_entity(entity_id, Player {name, score: 0})
+			.add_component_to_entity(entity_id, Ready {});
+		players.push(lobby.get_entity(entity_id).unwrap());
+	}
+
+	let mut matches = [create_match_world(), create_match_world()];
+
+	// Two players per match. The returned handles belong to the match worlds.
+	// Ready, unknown to the match worlds, is registered there on the move.
+	let mut in_match = players.iter().enumerate().map(|(i, player)| {
+		let world = &mut matches[i / 2];
+		(i / 2, lobby.move_entity_to(world, *player).unwrap())
+	}).collect::<Vec<(usize, Entity)>>();
+
+	for _i in 0..3 {
+		for world in matches.iter_mut() {
+			world.update();
+		}
+	}
+
+	for (i, player) in in_match.iter() {
+		let component = matches[*i].borrow_component_by_handle::<Player>(*player).unwrap();
+		println!("{} scored {} in match {}", component.name, component.score, i);
+	}
+
+	// Back to the lobby
+	for (i, player) in in_match.drain(..) {
+		assert!(matches[i].is_valid_entity(player));
+		assert!(!lobby.is_valid_entity(player));
+		matches[i].move_entity_to(&mut lobby, player).unwrap();
+	}
+
+	println!("Lobby");
+	print!("{}", lobby.dump());
+}
@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::any::{type_name, Any, TypeId};
+
+use super::component::Component;
+
+// Type erased Vec<T> holding one component type of an archetype
+pub trait ColumnTrait {
+	fn as_any(&self) -> &dyn Any;
+	fn as_any_mut(&mut self) -> &mut dyn Any;
+
+	fn swap_remove(&mut self, row: usize);
+	// swap_remove() returning the boxed component
+	fn take_row(&mut self, row: usize) -> Box<dyn Any>;
+	// Moves the component at row to the end of dst with swap_remove
+	fn move_row(&mut self, row: usize, dst: &mut dyn ColumnTrait);
+}
+
+pub struct Column<T: Component> {
+	components: Vec<T>
+}
+
+impl<T: 'static + Component> ColumnTrait for Column<T> {
+	fn as_any(&self) -> &dyn Any {
+		self as &dyn Any
+	}
+
+	fn as_any_mut(&mut self) -> &mut dyn Any {
+		self as &mut dyn Any
+	}
+
+	fn swap_remove(&mut self, row: usize) {
+		self.components.swap_remove(row);
+	}
+
+	fn take_row(&mut self, row: usize) -> Box<dyn Any> {
+		Box::new(self.components.swap_remove(row))
+	}
+
+	fn move_row(&mut self, row: usize, dst: &mut dyn ColumnTrait) {
+		let component = self.components.swap_remove(row);
+		cast_column_mut::<T>(dst).components.push(component);
+	}
+}
+
+pub fn cast_column<T: 'static + Component>(column: &dyn ColumnTrait) -> &Column<T> {
+	column
+		.as_any()
+		.downcast_ref::<Column<T>>()
+		.unwrap()
+}
+
+pub fn cast_column_mut<T: 'static + Component>(column: &mut dyn ColumnTrait) -> &mut Column<T> {
+	column
+		.as_any_mut()
+		.downcast_mut::<Column<T>>()
+		.unwrap()
+}
+
+fn create_column<T: 'static + Component>() -> Box<dyn ColumnTrait> {
+	Box::new(Column::<T> {
+		components: Vec::new()
+	})
+}
+
+fn clone_column<T: 'static + Component + Clone>(column: &dyn ColumnTrait) -> Box<dyn ColumnTrait> {
+	Box::new(Column::<T> {
+		components: cast_column::<T>(column).components.clone()
+	})
+}
+
+type CreateColumnFn = fn() -> Box<dyn ColumnTrait>;
+type CloneColumnFn = fn(&dyn ColumnTrait) -> Box<dyn ColumnTrait>;
+
+// Table of the entities having exactly the same component types.
+// The components of an entity are at the same row in all the columns.
+pub struct Archetype {
+	type_ids: Vec<TypeId>, // Sorted
+	entity_ids: Vec<usize>, // Same order with the column rows
+	columns: HashMap<TypeId, Box<dyn ColumnTrait>>
+}
+
+impl Archetype {
+	pub fn has(&self, type_id: TypeId) -> bool {
+		self.columns.contains_key(&type_id)
+	}
+
+	pub fn has_all(&self, type_ids: &[TypeId]) -> bool {
+		type_ids.iter().all(|type_id| self.has(*type_id))
+	}
+
+	pub fn borrow_type_ids(&self) -> &Vec<TypeId> {
+		&self.type_ids
+	}
+
+	pub fn borrow_entity_ids(&self) -> &Vec<usize> {
+		&self.entity_ids
+	}
+
+	pub fn borrow_components<T: 'static + Component>(&self) -> Option<&Vec<T>> {
+		self.columns.get(&TypeId::of::<T>())
+			.map(|column| &cast_column::<T>(column.as_ref()).components)
+	}
+
+	pub fn borrow_components_mut<T: 'static + Component>(&mut self) -> Option<&mut Vec<T>> {
+		self.columns.get_mut(&TypeId::of::<T>())
+			.map(|column| &mut cast_column_mut::<T>(column.as_mut()).components)
+	}
+
+	// Detaches the returned reference from the archetype borrow so that
+	// multiple columns can be mutably borrowed at the same time.
+	// The caller must not request the same component type twice.
+	pub(crate) fn borrow_components_mut_unsafe<'a, T: 'static + Component>(&mut self) -> &'a mut Vec<T> {
+		let ptr = self.borrow_components_mut::<T>().unwrap() as *mut Vec<T>;
+		unsafe { &mut *ptr }
+	}
+}
+
+#[derive(Clone, Copy)]
+struct Location {
+	archetype_index: usize,
+	row: usize
+}
+
+// Storage grouping entities by their component type sets. Adding or
+// removing a component moves the entity to another archetype, in exchange
+// multi-component iteration walks contiguous columns without looking up
+// each entity.
+pub struct ArchetypeStorage {
+	archetypes: Vec<Archetype>,
+	archetype_map: HashMap<Vec<TypeId>, usize>, // sorted type_ids -> index in archetypes
+	locations: Vec<Option<Location>>, // Indexed by entity_id. None if no components
+	create_fn_map: HashMap<TypeId, CreateColumnFn>, // Registered components
+	clone_fn_map: HashMap<TypeId, CloneColumnFn>, // Only cloneable components
+	type_name_map: HashMap<TypeId, &'static str> // Registered components
+}
+
+impl Default for ArchetypeStorage {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ArchetypeStorage {
+	pub fn new() -> Self {
+		ArchetypeStorage {
+			archetypes: Vec::new(),
+			archetype_map: HashMap::new(),
+			locations: Vec::new(),
+			create_fn_map: HashMap::new(),
+			clone_fn_map: HashMap::new(),
+			type_name_map: HashMap::new()
+		}
+	}
+
+	pub fn register<T: 'static + Component>(&mut self) {
+		self.create_fn_map.insert(TypeId::of::<T>(), create_column::<T>);
+		self.type_name_map.insert(TypeId::of::<T>(), type_name::<T>());
+	}
+
+	pub fn register_cloneable<T: 'static + Component + Clone>(&mut self) {
+		self.register::<T>();
+		self.clone_fn_map.insert(TypeId::of::<T>(), clone_column::<T>);
+	}
+
+	pub fn is_registered(&self, type_id: TypeId) -> bool {
+		self.create_fn_map.contains_key(&type_id)
+	}
+
+	// Type id and std::any::type_name() of the registered components
+	pub fn get_registered_types(&self) -> Vec<(TypeId, &'static str)> {
+		self.type_name_map.iter().map(|(type_id, name)| (*type_id, *name)).collect()
+	}
+
+	pub fn borrow_archetypes(&self) -> &Vec<Archetype> {
+		&self.archetypes
+	}
+
+	pub fn borrow_archetypes_mut(&mut self) -> &mut Vec<Archetype> {
+		&mut self.archetypes
+	}
+
+	fn get_location(&self, entity_id: usize) -> Option<Location> {
+		self.locations.get(entity_id).copied().flatten()
+	}
+
+	pub fn has(&self, entity_id: usize, type_id: TypeId) -> bool {
+		match self.get_location(entity_id) {
+			Some(location) => self.archetypes[location.archetype_index].has(type_id),
+			None => false
+		}
+	}
+
+	pub fn get_archetype_index(&self, entity_id: usize) -> Option<usize> {
+		self.get_location(entity_id).map(|location| location.archetype_index)
+	}
+
+	pub fn add<T: 'static + Component>(&mut self, entity_id: usize, component: T) {
+		let type_id = TypeId::of::<T>();
+		if self.has(entity_id, type_id) {
+			// Nothing to do? Throw error? Update component?
+			return;
+		}
+
+		let mut type_ids = match self.get_location(entity_id) {
+			Some(location) => self.archetypes[location.archetype_index].type_ids.clone(),
+			None => Vec::new()
+		};
+		type_ids.push(type_id);
+		type_ids.sort();
+
+		let dst = self.get_or_create_archetype(type_ids);
+		self.move_entity(entity_id, Some(dst));
+		cast_column_mut::<T>(self.archetypes[dst].columns.get_mut(&type_id).unwrap().as_mut())
+			.components.push(component);
+	}
+
+	pub fn remove(&mut self, entity_id: usize, type_id: TypeId) {
+		if !self.has(entity_id, type_id) {
+			// Nothing to do? Throw error? Update component?
+			return;
+		}
+
+		let location = self.get_location(entity_id).unwrap();
+		let type_ids = self.archetypes[location.archetype_index].type_ids.iter()
+			.filter(|id| **id != type_id)
+			.copied()
+			.collect::<Vec<TypeId>>();
+
+		let dst = match type_ids.is_empty() {
+			true => None,
+			false => Some(self.get_or_create_archetype(type_ids))
+		};
+		self.move_entity(entity_id, dst);
+	}
+
+	pub fn take<T: 'static + Component>(&mut self, entity_id: usize) -> Option<T> {
+		let type_id = TypeId::of::<T>();
+		if !self.has(entity_id, type_id) {
+			return None;
+		}
+
+		let location = self.get_location(entity_id).unwrap();
+		let type_ids = self.archetypes[location.archetype_index].type_ids.iter()
+			.filter(|id| **id != type_id)
+			.copied()
+			.collect::<Vec<TypeId>>();
+
+		let dst = match type_ids.is_empty() {
+			true => None,
+			false => Some(self.get_or_create_archetype(type_ids))
+		};
+		self.move_entity_taking(entity_id, dst, Some(type_id))
+			.map(|component| *component.downcast::<T>().unwrap())
+	}
+
+	// Returns the types of the removed components
+	pub fn remove_entity(&mut self, entity_id: usize) -> Vec<TypeId> {
+		match self.get_location(entity_id) {
+			Some(location) => {
+				let type_ids = self.archetypes[location.archetype_index].type_ids.clone();
+				self.move_entity(entity_id, None);
+				type_ids
+			},
+			None => Vec::new()
+		}
+	}
+
+	pub fn borrow_component<T: 'static + Component>(&self, entity_id: usize) -> Option<&T> {
+		let location = self.get_location(entity_id)?;
+		self.archetypes[location.archetype_index]
+			.borrow_components::<T>()
+			.map(|components| &components[location.row])
+	}
+
+	pub fn borrow_component_mut<T: 'static + Component>(&mut self, entity_id: usize) -> Option<&mut T> {
+		let location = self.get_location(entity_id)?;
+		self.archetypes[location.archetype_index]
+			.borrow_components_mut::<T>()
+			.map(|components| &mut components[location.row])
+	}
+
+	// Pointer to the component made without borrowing the whole column, so
+	// that the pointers to the other components of the column stay valid.
+	// Dereferencing it is unsafe, the caller must not make two references
+	// to the same component.
+	pub(crate) fn get_component_ptr<T: 'static + Component>(&mut self, entity_id: usize) -> Option<*mut T> {
+		let location = self.get_location(entity_id)?;
+		let components = self.archetypes[location.archetype_index].borrow_components_mut::<T>()?;
+		// In bounds since the row is of an entity in the archetype
+		Some(unsafe { components.as_mut_ptr().add(location.row) })
+	}
+
+	// Appends the ids of the entities having all the types to dst
+	pub fn collect_entity_ids(&self, type_ids: &[TypeId], dst: &mut Vec<usize>) {
+		for archetype in self.archetypes.iter() {
+			if archetype.has_all(type_ids) {
+				dst.extend_from_slice(&archetype.entity_ids);
+			}
+		}
+	}
+
+	pub fn count_components(&self, type_id: TypeId) -> usize {
+		self.archetypes.iter()
+			.filter(|archetype| archetype.has(type_id))
+			.map(|archetype| archetype.entity_ids.len())
+			.sum()
+	}
+
+	// Keeps the components registered to src after this storage was cloned
+	pub(crate) fn add_registrations(&mut self, src: &Self) {
+		for (type_id, create_fn) in src.create_fn_map.iter() {
+			self.create_fn_map.entry(*type_id).or_insert(*create_fn);
+		}
+		for (type_id, clone_fn) in src.clone_fn_map.iter() {
+			self.clone_fn_map.entry(*type_id).or_insert(*clone_fn);
+		}
+		for (type_id, name) in src.type_name_map.iter() {
+			self.type_name_map.entry(*type_id).or_insert(*name);
+		}
+	}
+
+	// Returns the type id of a component which is not cloneable on failure
+	pub fn try_clone(&self) -> Result<Self, TypeId> {
+		if let Some(type_id) = self.create_fn_map.keys().find(|type_id| !self.clone_fn_map.contains_key(type_id)) {
+			return Err(*type_id);
+		}
+		let archetypes = self.archetypes.iter().map(|archetype| {
+			Archetype {
+				type_ids: archetype.type_ids.clone(),
+				entity_ids: archetype.entity_ids.clone(),
+				columns: archetype.columns.iter().map(|(type_id, column)| {
+					(*type_id, (self.clone_fn_map.get(type_id).unwrap())(column.as_ref()))
+				}).collect()
+			}
+		}).collect();
+		Ok(ArchetypeStorage {
+			archetypes,
+			archetype_map: self.archetype_map.clone(),
+			locations: self.locations.clone(),
+			create_fn_map: self.create_fn_map.clone(),
+			clone_fn_map: self.clone_fn_map.clone(),
+			type_name_map: self.type_name_map.clone()
+		})
+	}
+
+	fn get_or_create_archetype(&mut self, type_ids: Vec<TypeId>) -> usize {
+		if let Some(index) = self.archetype_map.get(&type_ids) {
+			return *index;
+		}
+		let columns = type_ids.iter()
+			.map(|type_id| (*type_id, (self.create_fn_map.get(type_id).unwrap())()))
+			.collect();
+		self.archetypes.push(Archetype {
+			type_ids: type_ids.clone(),
+			entity_ids: Vec::new(),
+			columns
+		});
+		let index = self.archetypes.len() - 1;
+		self.archetype_map.insert(type_ids, index);
+		index
+	}
+
+	// Moves the entity row from the current archetype to dst. Components
+	// dst doesn't have are dropped. The caller pushes the components
+	// the current archetype doesn't have into dst.
+	fn move_entity(&mut self, entity_id: usize, dst: Option<usize>) {
+		self.move_entity_taking(entity_id, dst, None);
+	}
+
+	// move_entity() returning the component of the taken type instead of
+	// dropping it
+	fn move_entity_taking(&mut self, entity_id: usize, dst: Option<usize>, taken_type_id: Option<TypeId>) -> Option<Box<dyn Any>> {
+		let mut taken = None;
+		if let Some(src) = self.get_location(entity_id) {
+			let row = src.row;
+			let (src_archetype, mut dst_archetype) = match dst {
+				Some(dst) => {
+					let (a, b) = borrow_two_mut(&mut self.archetypes, src.archetype_index, dst);
+					(a, Some(b))
+				},
+				None => (&mut self.archetypes[src.archetype_index], None)
+			};
+
+			for (type_id, column) in src_archetype.columns.iter_mut() {
+				match dst_archetype.as_mut().and_then(|a| a.columns.get_mut(type_id)) {
+					Some(dst_column) => column.move_row(row, dst_column.as_mut()),
+					None if Some(*type_id) == taken_type_id => taken = Some(column.take_row(row)),
+					None => column.swap_remove(row)
+				};
+			}
+
+			src_archetype.entity_ids.swap_remove(row);
+			if row < src_archetype.entity_ids.len() {
+				let moved_entity_id = src_archetype.entity_ids[row];
+				self.locations[moved_entity_id].as_mut().unwrap().row = row;
+			}
+		}
+
+		if self.locations.len() <= entity_id {
+			self.locations.resize(entity_id + 1, None);
+		}
+
+		self.locations[entity_id] = dst.map(|dst| {
+			let archetype = &mut self.archetypes[dst];
+			archetype.entity_ids.push(entity_id);
+			Location {
+				archetype_index: dst,
+				row: archetype.entity_ids.len() - 1
+			}
+		});
+		taken
+	}
+}
+
+fn borrow_two_mut(archetypes: &mut [Archetype], a: usize, b: usize) -> (&mut Archetype, &mut Archetype) {
+	if a < b {
+		let (left, right) = archetypes.split_at_mut(b);
+		(&mut left[a], &mut right[0])
+	} else {
+		let (left, right) = archetypes.split_at_mut(a);
+		(&mut right[0], &mut left[b])
+	}
+}
@@ -0,0 +1,129 @@
+// Client of the inspector of a running World, see src/inspector.rs
+//
+// $ ecs_rust_inspect [--port 7411] [command]
+//
+// Commands:
+//   status
+//   entities
+//   get <entity> <Component.field | Component>
+//   set <entity> <Component.field> <value>
+//   pause
+//   resume
+//   step [frames]
+//   systems
+//   dump
+//   {"cmd": ...}  Raw request line
+//
+// Without a command, commands are read from stdin line by line.
+
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpStream};
+use std::process;
+
+use ecs_rust::inspector::DEFAULT_PORT;
+use serde_json::json;
+
+fn main() {
+	let mut args = env::args().skip(1).collect::<Vec<String>>();
+	let mut port = DEFAULT_PORT;
+	if args.len() >= 2 && args[0] == "--port" {
+		port = match args[1].parse() {
+			Ok(port) => port,
+			Err(_) => exit_with_error(&format!("Invalid port {}", args[1]))
+		};
+		args.drain(0..2);
+	}
+
+	let stream = match TcpStream::connect((Ipv4Addr::LOCALHOST, port)) {
+		Ok(stream) => stream,
+		Err(error) => exit_with_error(&format!("Can not connect to port {}: {}", port, error))
+	};
+	let mut writer = stream.try_clone().unwrap();
+	let mut reader = BufReader::new(stream);
+
+	if !args.is_empty() {
+		run_command(&args.join(" "), &mut writer, &mut reader);
+		return;
+	}
+
+	let stdin = io::stdin();
+	for line in stdin.lock().lines() {
+		let line = line.unwrap();
+		if !line.trim().is_empty() {
+			run_command(&line, &mut writer, &mut reader);
+		}
+	}
+}
+
+fn run_command(command: &str, writer: &mut TcpStream, reader: &mut BufReader<TcpStream>) {
+	let request = match to_request(command) {
+		Some(request) => request,
+		None => {
+			eprintln!("Unknown command: {}", command);
+			return;
+		}
+	};
+	if writeln!(writer, "{}", request).is_err() {
+		exit_with_error("Disconnected");
+	}
+	let mut response = String::new();
+	match reader.read_line(&mut response) {
+		Ok(0) | Err(_) => exit_with_error("Disconnected"),
+		Ok(_) => print_response(&response)
+	};
+}
+
+fn to_request(command: &str) -> Option<String> {
+	let command = command.trim();
+	if command.starts_with('{') {
+		return Some(command.to_string());
+	}
+	let words = command.split_whitespace().collect::<Vec<&str>>();
+	let request = match words.as_slice() {
+		["status"] => json!({"cmd": "status"}),
+		["entities"] => json!({"cmd": "entities"}),
+		["get", entity, path] => json!({"cmd": "get", "entity": entity.parse::<usize>().ok()?, "path": path}),
+		["set", entity, path, value @ ..] if !value.is_empty() => {
+			let value = value.join(" ");
+			json!({
+				"cmd": "set",
+				"entity": entity.parse::<usize>().ok()?,
+				"path": path,
+				// Unquoted strings are accepted too
+				"value": serde_json::from_str::<serde_json::Value>(&value).unwrap_or(json!(value))
+			})
+		},
+		["pause"] => json!({"cmd": "pause"}),
+		["resume"] => json!({"cmd": "resume"}),
+		["step"] => json!({"cmd": "step"}),
+		["step", frames] => json!({"cmd": "step", "frames": frames.parse::<u64>().ok()?}),
+		["systems"] => json!({"cmd": "systems"}),
+		["dump"] => json!({"cmd": "dump"}),
+		_ => return None
+	};
+	Some(request.to_string())
+}
+
+fn print_response(response: &str) {
+	let response = match serde_json::from_str::<serde_json::Value>(response) {
+		Ok(response) => response,
+		Err(_) => {
+			print!("{}", response);
+			return;
+		}
+	};
+	if response["ok"] == json!(false) {
+		eprintln!("Error: {}", response["error"].as_str().unwrap_or(""));
+		return;
+	}
+	match response["dump"].as_str() {
+		Some(dump) => print!("{}", dump),
+		None => println!("{}", serde_json::to_string_pretty(&response).unwrap())
+	};
+}
+
+fn exit_with_error(message: &str) -> ! {
+	eprintln!("{}", message);
+	process::exit(1);
+}
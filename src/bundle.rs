@@ -0,0 +1,50 @@
+use super::component::Component;
+use super::entity_manager::EntityManager;
+
+#[cfg(feature = "derive")]
+pub use ecs_rust_derive::Bundle;
+
+// A set of components added to an entity at once. Implemented for tuples of
+// components and by #[derive(Bundle)] for structs of components.
+pub trait Bundle {
+	fn add_to_entity(self, manager: &mut EntityManager, entity_id: usize);
+}
+
+impl<
+	T1: 'static + Component,
+	T2: 'static + Component
+> Bundle for (T1, T2) {
+	fn add_to_entity(self, manager: &mut EntityManager, entity_id: usize) {
+		manager
+			.add_component_to_entity(entity_id, self.0)
+			.add_component_to_entity(entity_id, self.1);
+	}
+}
+
+impl<
+	T1: 'static + Component,
+	T2: 'static + Component,
+	T3: 'static + Component
+> Bundle for (T1, T2, T3) {
+	fn add_to_entity(self, manager: &mut EntityManager, entity_id: usize) {
+		manager
+			.add_component_to_entity(entity_id, self.0)
+			.add_component_to_entity(entity_id, self.1)
+			.add_component_to_entity(entity_id, self.2);
+	}
+}
+
+impl<
+	T1: 'static + Component,
+	T2: 'static + Component,
+	T3: 'static + Component,
+	T4: 'static + Component
+> Bundle for (T1, T2, T3, T4) {
+	fn add_to_entity(self, manager: &mut EntityManager, entity_id: usize) {
+		manager
+			.add_component_to_entity(entity_id, self.0)
+			.add_component_to_entity(entity_id, self.1)
+			.add_component_to_entity(entity_id, self.2)
+			.add_component_to_entity(entity_id, self.3);
+	}
+}
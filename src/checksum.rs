@@ -0,0 +1,446 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+
+use super::component::Component;
+use super::entity_manager::EntityManager;
+
+// 64-bit FNV-1a. Unlike std's DefaultHasher the result is guaranteed to be
+// the same across platforms and Rust releases.
+pub struct StableHasher {
+	hash: u64
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+impl Default for StableHasher {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl StableHasher {
+	pub fn new() -> Self {
+		StableHasher {
+			hash: FNV_OFFSET_BASIS
+		}
+	}
+
+	pub fn write(&mut self, bytes: &[u8]) {
+		for byte in bytes.iter() {
+			self.hash ^= *byte as u64;
+			self.hash = self.hash.wrapping_mul(FNV_PRIME);
+		}
+	}
+
+	pub fn write_u64(&mut self, value: u64) {
+		self.write(&value.to_le_bytes());
+	}
+
+	pub fn finish(&self) -> u64 {
+		self.hash
+	}
+}
+
+// Implement this for components which should be covered by World::checksum().
+// The hash must depend only on the value, not on addresses or on anything
+// differing between platforms.
+pub trait StableHash {
+	fn stable_hash(&self, hasher: &mut StableHasher);
+}
+
+macro_rules! impl_stable_hash_for_int {
+	($($t:ty),*) => {
+		$(
+			impl StableHash for $t {
+				fn stable_hash(&self, hasher: &mut StableHasher) {
+					hasher.write(&self.to_le_bytes());
+				}
+			}
+		)*
+	};
+}
+
+impl_stable_hash_for_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+// usize and isize are hashed as 64-bit so 32-bit and 64-bit platforms agree
+impl StableHash for usize {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		hasher.write_u64(*self as u64);
+	}
+}
+
+impl StableHash for isize {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		hasher.write_u64(*self as i64 as u64);
+	}
+}
+
+impl StableHash for f32 {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		hasher.write(&self.to_bits().to_le_bytes());
+	}
+}
+
+impl StableHash for f64 {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		hasher.write(&self.to_bits().to_le_bytes());
+	}
+}
+
+impl StableHash for bool {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		hasher.write(&[*self as u8]);
+	}
+}
+
+impl StableHash for str {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		hasher.write_u64(self.len() as u64);
+		hasher.write(self.as_bytes());
+	}
+}
+
+impl StableHash for String {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		self.as_str().stable_hash(hasher);
+	}
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		(**self).stable_hash(hasher);
+	}
+}
+
+impl<T: StableHash> StableHash for [T] {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		hasher.write_u64(self.len() as u64);
+		for value in self.iter() {
+			value.stable_hash(hasher);
+		}
+	}
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		for value in self.iter() {
+			value.stable_hash(hasher);
+		}
+	}
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		self.as_slice().stable_hash(hasher);
+	}
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		match self {
+			Some(value) => {
+				hasher.write(&[1]);
+				value.stable_hash(hasher);
+			},
+			None => hasher.write(&[0])
+		}
+	}
+}
+
+impl<T1: StableHash, T2: StableHash> StableHash for (T1, T2) {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		self.0.stable_hash(hasher);
+		self.1.stable_hash(hasher);
+	}
+}
+
+impl<T1: StableHash, T2: StableHash, T3: StableHash> StableHash for (T1, T2, T3) {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		self.0.stable_hash(hasher);
+		self.1.stable_hash(hasher);
+		self.2.stable_hash(hasher);
+	}
+}
+
+// Returns false if the entity doesn't have the component
+type HashFn = fn(&EntityManager, usize, &mut StableHasher) -> bool;
+
+struct HashableComponent {
+	name: String,
+	hash: HashFn
+}
+
+pub struct ChecksumRegistry {
+	components: Vec<HashableComponent>,
+	type_map: HashMap<TypeId, usize> // TypeId -> index in components
+}
+
+impl Default for ChecksumRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ChecksumRegistry {
+	pub fn new() -> Self {
+		ChecksumRegistry {
+			components: Vec::new(),
+			type_map: HashMap::new()
+		}
+	}
+
+	pub fn register<T: 'static + Component + StableHash>(&mut self, name: &str) -> &mut Self {
+		let type_id = TypeId::of::<T>();
+		if self.type_map.contains_key(&type_id) {
+			// @TODO: Better error handling
+			println!("Hashable component {} is already registered", name);
+			return self;
+		}
+		self.components.push(HashableComponent {
+			name: name.to_string(),
+			hash: hash_component::<T>
+		});
+		self.type_map.insert(type_id, self.components.len() - 1);
+		self
+	}
+
+	pub fn checksum(&self, manager: &EntityManager) -> u64 {
+		let mut hasher = StableHasher::new();
+		hasher.write_u64(hash_entities(manager));
+		let entity_ids = manager.get_entity_ids();
+		for component in self.components.iter() {
+			let mut component_hasher = StableHasher::new();
+			for entity_id in entity_ids.iter() {
+				hash_entity_component(manager, component, *entity_id, &mut component_hasher);
+			}
+			hasher.write_u64(component_hasher.finish());
+		}
+		hasher.finish()
+	}
+
+	pub fn report(&self, manager: &EntityManager) -> ChecksumReport {
+		let entity_ids = manager.get_entity_ids();
+		let components = self.components.iter().map(|component| {
+			let mut hasher = StableHasher::new();
+			let mut entity_checksums = Vec::new();
+			for entity_id in entity_ids.iter() {
+				let mut entity_hasher = StableHasher::new();
+				if (component.hash)(manager, *entity_id, &mut entity_hasher) {
+					let checksum = entity_hasher.finish();
+					entity_checksums.push((*entity_id, checksum));
+					hasher.write_u64(*entity_id as u64);
+					hasher.write_u64(checksum);
+				}
+			}
+			ComponentChecksum {
+				name: component.name.clone(),
+				checksum: hasher.finish(),
+				entity_checksums
+			}
+		}).collect();
+		ChecksumReport {
+			entities: hash_entities(manager),
+			components
+		}
+	}
+}
+
+fn hash_component<T: 'static + Component + StableHash>(manager: &EntityManager, entity_id: usize, hasher: &mut StableHasher) -> bool {
+	match manager.borrow_component::<T>(entity_id) {
+		Some(component) => {
+			component.stable_hash(hasher);
+			true
+		},
+		None => false
+	}
+}
+
+fn hash_entity_component(manager: &EntityManager, component: &HashableComponent, entity_id: usize, hasher: &mut StableHasher) {
+	let mut entity_hasher = StableHasher::new();
+	if (component.hash)(manager, entity_id, &mut entity_hasher) {
+		hasher.write_u64(entity_id as u64);
+		hasher.write_u64(entity_hasher.finish());
+	}
+}
+
+// The free list is covered too because it decides the ids of new entities
+fn hash_entities(manager: &EntityManager) -> u64 {
+	let mut hasher = StableHasher::new();
+	manager.get_entity_ids().stable_hash(&mut hasher);
+	manager.borrow_available_entity_ids().stable_hash(&mut hasher);
+	hasher.finish()
+}
+
+pub struct ComponentChecksum {
+	pub name: String,
+	pub checksum: u64,
+	pub entity_checksums: Vec<(usize, u64)> // (entity_id, checksum), ascending ids
+}
+
+// Detailed checksums to find where two worlds diverge
+pub struct ChecksumReport {
+	pub entities: u64, // Live entities and free list
+	pub components: Vec<ComponentChecksum>
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Divergence {
+	Entities,
+	Component {
+		name: String,
+		entity_id: usize
+	}
+}
+
+impl ChecksumReport {
+	// Reports the first divergence. Both reports must come from worlds
+	// registering the same hashable components in the same order.
+	pub fn find_divergence(&self, other: &ChecksumReport) -> Option<Divergence> {
+		if self.entities != other.entities {
+			return Some(Divergence::Entities);
+		}
+		for (a, b) in self.components.iter().zip(other.components.iter()) {
+			if a.checksum == b.checksum {
+				continue;
+			}
+			// Differs in a component value or in which entities have the component
+			let min_len = a.entity_checksums.len().min(b.entity_checksums.len());
+			let entity_id = a.entity_checksums.iter()
+				.zip(b.entity_checksums.iter())
+				.find(|(a, b)| a != b)
+				.map(|(a, b)| a.0.min(b.0))
+				.unwrap_or_else(|| match a.entity_checksums.len() > min_len {
+					true => a.entity_checksums[min_len].0,
+					false => b.entity_checksums[min_len].0
+				});
+			return Some(Divergence::Component {
+				name: a.name.clone(),
+				entity_id
+			});
+		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::component::Component;
+	use crate::entity_manager::StorageMode;
+	use crate::storage::DenseStorage;
+	use crate::world::World;
+	use super::{Divergence, StableHash, StableHasher};
+
+	struct Position {
+		x: f32,
+		y: f32
+	}
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	impl StableHash for Position {
+		fn stable_hash(&self, hasher: &mut StableHasher) {
+			self.x.stable_hash(hasher);
+			self.y.stable_hash(hasher);
+		}
+	}
+
+	struct Name {
+		name: String
+	}
+
+	impl Component for Name {
+		type Storage = DenseStorage<Self>;
+	}
+
+	impl StableHash for Name {
+		fn stable_hash(&self, hasher: &mut StableHasher) {
+			self.name.stable_hash(hasher);
+		}
+	}
+
+	// Not hashable
+	struct Velocity;
+
+	impl Component for Velocity {
+		type Storage = DenseStorage<Self>;
+	}
+
+	fn create_world(storage_mode: StorageMode) -> World {
+		let mut world = World::with_storage_mode(storage_mode);
+		world
+			.register_hashable_component::<Position>("Position")
+			.register_hashable_component::<Name>("Name")
+			.register_component::<Velocity>();
+		world
+	}
+
+	// Entities 0 and 2 with a free id 1
+	fn add_entities(world: &mut World, reverse: bool) {
+		let ids = [world.create_entity(), world.create_entity(), world.create_entity()];
+		let order = match reverse {
+			true => [2, 0],
+			false => [0, 2]
+		};
+		for id in order.iter() {
+			world
+				.add_component_to_entity(ids[*id], Name { name: format!("entity{}", id) })
+				.add_component_to_entity(ids[*id], Velocity)
+				.add_component_to_entity(ids[*id], Position { x: *id as f32, y: 0.5 });
+		}
+		world.remove_entity(ids[1]);
+	}
+
+	#[test]
+	fn fnv1a() {
+		let hash = |bytes: &[u8]| {
+			let mut hasher = StableHasher::new();
+			hasher.write(bytes);
+			hasher.finish()
+		};
+		assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+		assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+		assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+	}
+
+	#[test]
+	fn same_state_same_checksum() {
+		let mut world = create_world(StorageMode::PerComponent);
+		add_entities(&mut world, false);
+		let mut other = create_world(StorageMode::Archetype);
+		add_entities(&mut other, true);
+		assert_eq!(world.checksum(), other.checksum());
+		assert_eq!(world.checksum_report().find_divergence(&other.checksum_report()), None);
+
+		// Non hashable components are not covered
+		world.remove_component_from_entity::<Velocity>(0);
+		assert_eq!(world.checksum(), other.checksum());
+	}
+
+	// Fails if the hashing changes, which would break saved checksums
+	#[test]
+	fn golden_checksum() {
+		let mut world = create_world(StorageMode::PerComponent);
+		add_entities(&mut world, false);
+		assert_eq!(world.checksum(), 0xa86e_e7ae_e144_9d33);
+	}
+
+	#[test]
+	fn divergence() {
+		let mut world = create_world(StorageMode::PerComponent);
+		add_entities(&mut world, false);
+		let mut other = create_world(StorageMode::PerComponent);
+		add_entities(&mut other, false);
+		other.borrow_component_mut::<Position>(2).unwrap().y = 1.0;
+		assert_ne!(world.checksum(), other.checksum());
+		assert_eq!(world.checksum_report().find_divergence(&other.checksum_report()), Some(Divergence::Component {
+			name: "Position".to_string(),
+			entity_id: 2
+		}));
+
+		other.create_entity();
+		assert_eq!(world.checksum_report().find_divergence(&other.checksum_report()), Some(Divergence::Entities));
+	}
+}
@@ -0,0 +1,741 @@
+use std::collections::{BTreeSet, HashMap};
+use std::marker::PhantomData;
+
+use super::component::Component;
+use super::entity_manager::{EntityIdAccessor, EntityManager};
+use super::spatial::{Aabb, SpatialIndex, SpatialPosition, SpatialStructure};
+use super::storage::DenseStorage;
+use super::system::System;
+
+pub const ALL_LAYERS: u32 = u32::MAX;
+
+// Relative to the position of the entity
+#[derive(Clone, PartialEq, Debug)]
+pub enum Shape {
+	Circle {
+		radius: f32
+	},
+	Aabb {
+		half_size: [f32; 2]
+	},
+	OrientedBox {
+		half_size: [f32; 2],
+		angle: f32 // Radians
+	},
+	// Convex, in either winding order
+	Polygon {
+		vertices: Vec<[f32; 2]>
+	}
+}
+
+// Two colliders collide if each one's layers are in the other's mask
+#[derive(Clone, Debug)]
+pub struct Collider {
+	shape: Shape,
+	layers: u32, // The layers the collider belongs to
+	mask: u32 // The layers the collider collides with
+}
+
+impl Component for Collider {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Collider {
+	// In the first layer, colliding with all the layers
+	pub fn new(shape: Shape) -> Self {
+		Collider {
+			shape,
+			layers: 1,
+			mask: ALL_LAYERS
+		}
+	}
+
+	pub fn with_layers(mut self, layers: u32, mask: u32) -> Self {
+		self.layers = layers;
+		self.mask = mask;
+		self
+	}
+
+	pub fn borrow_shape(&self) -> &Shape {
+		&self.shape
+	}
+
+	pub fn set_shape(&mut self, shape: Shape) {
+		self.shape = shape;
+	}
+
+	pub fn get_layers(&self) -> u32 {
+		self.layers
+	}
+
+	pub fn get_mask(&self) -> u32 {
+		self.mask
+	}
+}
+
+// Sent by CollisionSystem when two colliders start or stop overlapping.
+// entity_id1 < entity_id2. CollisionEnded is also sent when an entity or
+// its collider is removed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CollisionStarted {
+	pub entity_id1: usize,
+	pub entity_id2: usize
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CollisionEnded {
+	pub entity_id1: usize,
+	pub entity_id2: usize
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RaycastHit {
+	pub entity_id: usize,
+	pub distance: f32,
+	pub point: [f32; 2],
+	pub normal: [f32; 2] // Of the hit surface
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShapeCastHit {
+	pub entity_id: usize,
+	pub distance: f32,
+	pub position: [f32; 2], // Of the cast shape at the hit
+	pub normal: [f32; 2] // Of the hit surface
+}
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+	[a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+	[a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f32; 2], s: f32) -> [f32; 2] {
+	[a[0] * s, a[1] * s]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+	a[0] * b[0] + a[1] * b[1]
+}
+
+fn normalize(a: [f32; 2]) -> [f32; 2] {
+	let length = dot(a, a).sqrt();
+	match length > 0.0 {
+		true => scale(a, 1.0 / length),
+		false => [0.0, 0.0]
+	}
+}
+
+// Shapes in world space. Polygons are counter-clockwise (positive area).
+#[derive(Clone, Debug)]
+enum WorldShape {
+	Circle {
+		center: [f32; 2],
+		radius: f32
+	},
+	Polygon {
+		vertices: Vec<[f32; 2]>
+	}
+}
+
+impl WorldShape {
+	fn new(shape: &Shape, position: [f32; 2]) -> Self {
+		let vertices = match shape {
+			Shape::Circle { radius } => return WorldShape::Circle {
+				center: position,
+				radius: *radius
+			},
+			Shape::Aabb { half_size } => get_box_vertices(*half_size, 0.0),
+			Shape::OrientedBox { half_size, angle } => get_box_vertices(*half_size, *angle),
+			Shape::Polygon { vertices } => vertices.clone()
+		};
+		let mut vertices = vertices.into_iter().map(|vertex| add(vertex, position)).collect::<Vec<[f32; 2]>>();
+		if get_signed_area(&vertices) < 0.0 {
+			vertices.reverse();
+		}
+		WorldShape::Polygon {
+			vertices
+		}
+	}
+
+	// Of the aabb centered at the position
+	fn get_half_extents(&self, position: [f32; 2]) -> [f32; 2] {
+		match self {
+			WorldShape::Circle { radius, .. } => [*radius, *radius],
+			WorldShape::Polygon { vertices } => vertices.iter().fold([0.0, 0.0], |extents, vertex| [
+				extents[0].max((vertex[0] - position[0]).abs()),
+				extents[1].max((vertex[1] - position[1]).abs())
+			])
+		}
+	}
+}
+
+fn get_box_vertices(half_size: [f32; 2], angle: f32) -> Vec<[f32; 2]> {
+	let (sin, cos) = angle.sin_cos();
+	[[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].iter().map(|corner| {
+		let x = corner[0] * half_size[0];
+		let y = corner[1] * half_size[1];
+		[x * cos - y * sin, x * sin + y * cos]
+	}).collect()
+}
+
+fn get_signed_area(vertices: &[[f32; 2]]) -> f32 {
+	let mut area = 0.0;
+	for (i, a) in vertices.iter().enumerate() {
+		let b = vertices[(i + 1) % vertices.len()];
+		area += a[0] * b[1] - b[0] * a[1];
+	}
+	area * 0.5
+}
+
+// (start vertex, outward unit normal) of each edge
+fn get_edges(vertices: &[[f32; 2]]) -> Vec<([f32; 2], [f32; 2])> {
+	(0..vertices.len()).map(|i| {
+		let a = vertices[i];
+		let edge = sub(vertices[(i + 1) % vertices.len()], a);
+		(a, normalize([edge[1], -edge[0]]))
+	}).collect()
+}
+
+fn project(vertices: &[[f32; 2]], axis: [f32; 2]) -> (f32, f32) {
+	vertices.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), vertex| {
+		let d = dot(*vertex, axis);
+		(min.min(d), max.max(d))
+	})
+}
+
+fn is_inside_polygon(point: [f32; 2], vertices: &[[f32; 2]]) -> bool {
+	get_edges(vertices).iter().all(|(a, normal)| dot(sub(point, *a), *normal) <= 0.0)
+}
+
+fn get_distance_to_polygon_edges(point: [f32; 2], vertices: &[[f32; 2]]) -> f32 {
+	let mut distance = f32::INFINITY;
+	for (i, a) in vertices.iter().enumerate() {
+		let edge = sub(vertices[(i + 1) % vertices.len()], *a);
+		let length_squared = dot(edge, edge);
+		let s = match length_squared > 0.0 {
+			true => (dot(sub(point, *a), edge) / length_squared).clamp(0.0, 1.0),
+			false => 0.0
+		};
+		let d = sub(point, add(*a, scale(edge, s)));
+		distance = distance.min(dot(d, d).sqrt());
+	}
+	distance
+}
+
+// Touching shapes don't overlap
+fn overlaps(a: &WorldShape, b: &WorldShape) -> bool {
+	match (a, b) {
+		(WorldShape::Circle { center: center1, radius: radius1 }, WorldShape::Circle { center: center2, radius: radius2 }) => {
+			let d = sub(*center1, *center2);
+			dot(d, d) < (radius1 + radius2) * (radius1 + radius2)
+		},
+		(WorldShape::Circle { center, radius }, WorldShape::Polygon { vertices }) |
+		(WorldShape::Polygon { vertices }, WorldShape::Circle { center, radius }) => {
+			is_inside_polygon(*center, vertices) || get_distance_to_polygon_edges(*center, vertices) < *radius
+		},
+		(WorldShape::Polygon { vertices: vertices1 }, WorldShape::Polygon { vertices: vertices2 }) => {
+			// Separating axis test with the edge normals of both
+			get_edges(vertices1).iter().chain(get_edges(vertices2).iter()).all(|(_, axis)| {
+				let (min1, max1) = project(vertices1, *axis);
+				let (min2, max2) = project(vertices2, *axis);
+				max1 > min2 && max2 > min1
+			})
+		}
+	}
+}
+
+// Distance and normal of the first hit of the ray. direction is a unit
+// vector. A ray starting inside hits at 0 with the normal against it.
+fn raycast_circle(origin: [f32; 2], direction: [f32; 2], center: [f32; 2], radius: f32) -> Option<(f32, [f32; 2])> {
+	let m = sub(origin, center);
+	let b = dot(m, direction);
+	let c = dot(m, m) - radius * radius;
+	if c <= 0.0 {
+		return Some((0.0, scale(direction, -1.0)));
+	}
+	if b > 0.0 {
+		return None;
+	}
+	let discriminant = b * b - c;
+	if discriminant < 0.0 {
+		return None;
+	}
+	let t = -b - discriminant.sqrt();
+	Some((t, normalize(sub(add(origin, scale(direction, t)), center))))
+}
+
+fn raycast_polygon(origin: [f32; 2], direction: [f32; 2], vertices: &[[f32; 2]]) -> Option<(f32, [f32; 2])> {
+	let mut t_enter = f32::NEG_INFINITY;
+	let mut t_exit = f32::INFINITY;
+	let mut normal = scale(direction, -1.0);
+	for (a, edge_normal) in get_edges(vertices) {
+		let denominator = dot(edge_normal, direction);
+		let distance = dot(edge_normal, sub(origin, a)); // Positive outside the edge
+		if denominator == 0.0 {
+			if distance > 0.0 {
+				return None;
+			}
+			continue;
+		}
+		let t = -distance / denominator;
+		if denominator < 0.0 {
+			if t > t_enter {
+				t_enter = t;
+				normal = edge_normal;
+			}
+		} else {
+			t_exit = t_exit.min(t);
+		}
+		if t_enter > t_exit {
+			return None;
+		}
+	}
+	if t_exit < 0.0 {
+		return None;
+	}
+	match t_enter > 0.0 {
+		true => Some((t_enter, normal)),
+		false => Some((0.0, scale(direction, -1.0)))
+	}
+}
+
+// Against the polygon grown by the radius, i.e. the shapes a circle of the
+// radius touches when its center is on them
+fn raycast_rounded_polygon(origin: [f32; 2], direction: [f32; 2], vertices: &[[f32; 2]], radius: f32) -> Option<(f32, [f32; 2])> {
+	if radius <= 0.0 {
+		return raycast_polygon(origin, direction, vertices);
+	}
+	if is_inside_polygon(origin, vertices) || get_distance_to_polygon_edges(origin, vertices) <= radius {
+		return Some((0.0, scale(direction, -1.0)));
+	}
+	let mut hit: Option<(f32, [f32; 2])> = None;
+	let mut update_hit = |t: f32, normal: [f32; 2]| {
+		if hit.map(|(hit_t, _)| t < hit_t).unwrap_or(true) {
+			hit = Some((t, normal));
+		}
+	};
+	for (i, (a, normal)) in get_edges(vertices).into_iter().enumerate() {
+		let denominator = dot(normal, direction);
+		if denominator >= 0.0 {
+			continue;
+		}
+		let a = add(a, scale(normal, radius));
+		let edge = sub(vertices[(i + 1) % vertices.len()], vertices[i]);
+		let t = dot(normal, sub(a, origin)) / denominator;
+		let s = dot(sub(add(origin, scale(direction, t)), a), edge) / dot(edge, edge);
+		if t >= 0.0 && (0.0..=1.0).contains(&s) {
+			update_hit(t, normal);
+		}
+	}
+	for vertex in vertices.iter() {
+		if let Some((t, normal)) = raycast_circle(origin, direction, *vertex, radius) {
+			update_hit(t, normal);
+		}
+	}
+	hit
+}
+
+fn raycast(origin: [f32; 2], direction: [f32; 2], shape: &WorldShape) -> Option<(f32, [f32; 2])> {
+	match shape {
+		WorldShape::Circle { center, radius } => raycast_circle(origin, direction, *center, *radius),
+		WorldShape::Polygon { vertices } => raycast_polygon(origin, direction, vertices)
+	}
+}
+
+// Moves the polygon 1 along the direction. Separating axis test over time
+// with the edge normals of both, exact for translations.
+fn cast_polygon(vertices1: &[[f32; 2]], direction: [f32; 2], vertices2: &[[f32; 2]]) -> Option<(f32, [f32; 2])> {
+	let mut t_enter = f32::NEG_INFINITY;
+	let mut t_exit = f32::INFINITY;
+	let mut normal = scale(direction, -1.0);
+	for (_, axis) in get_edges(vertices1).iter().chain(get_edges(vertices2).iter()) {
+		let (min1, max1) = project(vertices1, *axis);
+		let (min2, max2) = project(vertices2, *axis);
+		let speed = dot(direction, *axis);
+		if speed == 0.0 {
+			if max1 <= min2 || max2 <= min1 {
+				return None;
+			}
+			continue;
+		}
+		let (t0, t1) = match speed > 0.0 {
+			true => ((min2 - max1) / speed, (max2 - min1) / speed),
+			false => ((max2 - min1) / speed, (min2 - max1) / speed)
+		};
+		if t0 > t_enter {
+			t_enter = t0;
+			// Against the moving polygon
+			normal = match speed > 0.0 {
+				true => scale(*axis, -1.0),
+				false => *axis
+			};
+		}
+		t_exit = t_exit.min(t1);
+		if t_enter > t_exit {
+			return None;
+		}
+	}
+	if t_exit < 0.0 {
+		return None;
+	}
+	match t_enter > 0.0 {
+		true => Some((t_enter, normal)),
+		false => Some((0.0, scale(direction, -1.0)))
+	}
+}
+
+// Distance and normal of the hit surface when moving shape1 along the
+// direction
+fn cast(shape1: &WorldShape, direction: [f32; 2], shape2: &WorldShape) -> Option<(f32, [f32; 2])> {
+	match (shape1, shape2) {
+		(WorldShape::Circle { center: center1, radius: radius1 }, WorldShape::Circle { center: center2, radius: radius2 }) => {
+			raycast_circle(*center1, direction, *center2, radius1 + radius2)
+		},
+		(WorldShape::Circle { center, radius }, WorldShape::Polygon { vertices }) => {
+			raycast_rounded_polygon(*center, direction, vertices, *radius)
+		},
+		(WorldShape::Polygon { vertices }, WorldShape::Circle { center, radius }) => {
+			// The circle moving the other way hits the polygon
+			raycast_rounded_polygon(*center, scale(direction, -1.0), vertices, *radius)
+				.map(|(t, normal)| (t, scale(normal, -1.0)))
+		},
+		(WorldShape::Polygon { vertices: vertices1 }, WorldShape::Polygon { vertices: vertices2 }) => {
+			cast_polygon(vertices1, direction, vertices2)
+		}
+	}
+}
+
+// Entity id pairs, the smaller id first
+type Pairs = Vec<(usize, usize)>;
+
+struct ColliderEntry {
+	shape: WorldShape,
+	half_extents: [f32; 2],
+	layers: u32,
+	mask: u32
+}
+
+impl ColliderEntry {
+	fn new(collider: &Collider, position: [f32; 2]) -> Self {
+		let shape = WorldShape::new(&collider.shape, position);
+		ColliderEntry {
+			half_extents: shape.get_half_extents(position),
+			shape,
+			layers: collider.layers,
+			mask: collider.mask
+		}
+	}
+
+	fn can_collide(&self, other: &ColliderEntry) -> bool {
+		self.layers & other.mask != 0 && other.layers & self.mask != 0
+	}
+}
+
+// Resource holding the colliders of the entities having T and Collider,
+// as of the last CollisionSystem<T> run. The broadphase uses a
+// SpatialIndex of the positions. See World::add_collision2d().
+pub struct CollisionIndex<T: SpatialPosition> {
+	spatial_index: SpatialIndex<T>,
+	entries: HashMap<usize, ColliderEntry>,
+	max_half_extents: [f32; 2], // Of all the colliders
+	contacts: BTreeSet<(usize, usize)> // The smaller entity id first
+}
+
+impl<T: SpatialPosition> CollisionIndex<T> {
+	pub fn new(structure: Box<dyn SpatialStructure>) -> Self {
+		CollisionIndex {
+			spatial_index: SpatialIndex::new(structure),
+			entries: HashMap::new(),
+			max_half_extents: [0.0, 0.0],
+			contacts: BTreeSet::new()
+		}
+	}
+
+	pub fn with_grid(cell_size: f32) -> Self {
+		Self::new(Box::new(super::spatial::UniformGrid::new(cell_size)))
+	}
+
+	pub fn with_quad_tree(bounds: Aabb, capacity: usize) -> Self {
+		Self::new(Box::new(super::spatial::QuadTree::new(bounds, capacity)))
+	}
+
+	// The overlapping pairs, the smaller entity id first
+	pub fn borrow_contacts(&self) -> &BTreeSet<(usize, usize)> {
+		&self.contacts
+	}
+
+	pub fn is_colliding(&self, entity_id1: usize, entity_id2: usize) -> bool {
+		self.contacts.contains(&(entity_id1.min(entity_id2), entity_id1.max(entity_id2)))
+	}
+
+	// Entities whose colliders may overlap the aabb
+	fn get_candidates(&self, aabb: &Aabb) -> Vec<usize> {
+		self.spatial_index.query_aabb(&Aabb::new(
+			sub(aabb.min, self.max_half_extents),
+			add(aabb.max, self.max_half_extents)
+		))
+	}
+
+	// Entities in the mask layers overlapping the shape at the position
+	pub fn overlap(&self, shape: &Shape, position: [f32; 2], mask: u32) -> Vec<usize> {
+		let shape = WorldShape::new(shape, position);
+		let half_extents = shape.get_half_extents(position);
+		self.get_candidates(&Aabb::from_center(position, half_extents)).into_iter()
+			.filter(|entity_id| {
+				let entry = &self.entries[entity_id];
+				entry.layers & mask != 0 && overlaps(&shape, &entry.shape)
+			})
+			.collect()
+	}
+
+	// The first collider in the mask layers hit by the ray
+	pub fn raycast(&self, origin: [f32; 2], direction: [f32; 2], max_distance: f32, mask: u32) -> Option<RaycastHit> {
+		let direction = normalize(direction);
+		let end = add(origin, scale(direction, max_distance));
+		let aabb = Aabb::new(
+			[origin[0].min(end[0]), origin[1].min(end[1])],
+			[origin[0].max(end[0]), origin[1].max(end[1])]
+		);
+		let mut hit: Option<RaycastHit> = None;
+		// Candidates are in ascending id order, so the smaller id wins a tie
+		for entity_id in self.get_candidates(&aabb) {
+			let entry = &self.entries[&entity_id];
+			if entry.layers & mask == 0 {
+				continue;
+			}
+			if let Some((distance, normal)) = raycast(origin, direction, &entry.shape) {
+				if distance <= max_distance && hit.map(|hit| distance < hit.distance).unwrap_or(true) {
+					hit = Some(RaycastHit {
+						entity_id,
+						distance,
+						point: add(origin, scale(direction, distance)),
+						normal
+					});
+				}
+			}
+		}
+		hit
+	}
+
+	// The first collider in the mask layers hit by the shape moved from
+	// the origin along the direction
+	pub fn shape_cast(&self, shape: &Shape, origin: [f32; 2], direction: [f32; 2], max_distance: f32, mask: u32) -> Option<ShapeCastHit> {
+		let direction = normalize(direction);
+		let shape = WorldShape::new(shape, origin);
+		let half_extents = shape.get_half_extents(origin);
+		let end = add(origin, scale(direction, max_distance));
+		let aabb = Aabb::new(
+			sub([origin[0].min(end[0]), origin[1].min(end[1])], half_extents),
+			add([origin[0].max(end[0]), origin[1].max(end[1])], half_extents)
+		);
+		let mut hit: Option<ShapeCastHit> = None;
+		for entity_id in self.get_candidates(&aabb) {
+			let entry = &self.entries[&entity_id];
+			if entry.layers & mask == 0 {
+				continue;
+			}
+			if let Some((distance, normal)) = cast(&shape, direction, &entry.shape) {
+				if distance <= max_distance && hit.map(|hit| distance < hit.distance).unwrap_or(true) {
+					hit = Some(ShapeCastHit {
+						entity_id,
+						distance,
+						position: add(origin, scale(direction, distance)),
+						normal
+					});
+				}
+			}
+		}
+		hit
+	}
+
+	// Replaces the colliders and returns the started and ended contacts.
+	// positions are sorted by entity id.
+	fn update(&mut self, positions: &[(usize, [f32; 2])], entries: Vec<ColliderEntry>) -> (Pairs, Pairs) {
+		self.spatial_index.sync(positions);
+		self.max_half_extents = entries.iter().fold([0.0, 0.0], |extents, entry| [
+			extents[0].max(entry.half_extents[0]),
+			extents[1].max(entry.half_extents[1])
+		]);
+		self.entries = positions.iter().map(|(entity_id, _)| *entity_id).zip(entries).collect();
+
+		let mut contacts = BTreeSet::new();
+		for (entity_id, position) in positions.iter() {
+			let entry = &self.entries[entity_id];
+			for other_id in self.get_candidates(&Aabb::from_center(*position, entry.half_extents)) {
+				if other_id <= *entity_id {
+					continue;
+				}
+				let other = &self.entries[&other_id];
+				if entry.can_collide(other) && overlaps(&entry.shape, &other.shape) {
+					contacts.insert((*entity_id, other_id));
+				}
+			}
+		}
+
+		let started = contacts.difference(&self.contacts).copied().collect();
+		let ended = self.contacts.difference(&contacts).copied().collect();
+		self.contacts = contacts;
+		(started, ended)
+	}
+}
+
+// Updates CollisionIndex<T> and sends CollisionStarted and CollisionEnded
+// events. Add it after the systems moving the entities.
+pub struct CollisionSystem<T: SpatialPosition> {
+	component: PhantomData<fn() -> T>
+}
+
+impl<T: SpatialPosition> Default for CollisionSystem<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T: SpatialPosition> CollisionSystem<T> {
+	pub fn new() -> Self {
+		CollisionSystem {
+			component: PhantomData
+		}
+	}
+}
+
+impl<T: 'static + SpatialPosition> System for CollisionSystem<T> {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		if !manager.has_resource::<CollisionIndex<T>>() {
+			return;
+		}
+		let mut positions = Vec::new();
+		let mut entries = Vec::new();
+		if let Some(entity_ids) = accessor.borrow_ids_for_pair::<T, Collider>(manager) {
+			// The cached ids are not in order
+			let mut entity_ids = entity_ids.clone();
+			entity_ids.sort_unstable();
+			for entity_id in entity_ids.iter() {
+				let position = manager.borrow_component::<T>(*entity_id).unwrap().get_position();
+				let collider = manager.borrow_component::<Collider>(*entity_id).unwrap();
+				positions.push((*entity_id, position));
+				entries.push(ColliderEntry::new(collider, position));
+			}
+		}
+		let (started, ended) = manager.borrow_resource_mut::<CollisionIndex<T>>().unwrap()
+			.update(&positions, entries);
+		for (entity_id1, entity_id2) in ended {
+			manager.send_event(CollisionEnded { entity_id1, entity_id2 });
+		}
+		for (entity_id1, entity_id2) in started {
+			manager.send_event(CollisionStarted { entity_id1, entity_id2 });
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use crate::component::Component;
+	use crate::entity_manager::{EntityIdAccessor, EntityManager};
+	use crate::spatial::SpatialPosition;
+	use crate::storage::DenseStorage;
+	use crate::system::System;
+	use crate::world::World;
+	use super::{Collider, CollisionEnded, CollisionIndex, CollisionStarted, Shape};
+
+	struct Position {
+		position: [f32; 2]
+	}
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	impl SpatialPosition for Position {
+		fn get_position(&self) -> [f32; 2] {
+			self.position
+		}
+	}
+
+	// Collects the started and ended collisions, as (started, entity_id1, entity_id2)
+	struct ContactSystem;
+
+	impl System for ContactSystem {
+		fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+			let mut contacts = manager.borrow_events::<CollisionEnded>().unwrap().borrow_events().iter()
+				.map(|event| (false, event.entity_id1, event.entity_id2))
+				.collect::<Vec<(bool, usize, usize)>>();
+			contacts.extend(manager.borrow_events::<CollisionStarted>().unwrap().borrow_events().iter()
+				.map(|event| (true, event.entity_id1, event.entity_id2)));
+			manager.borrow_resource_mut::<Vec<(bool, usize, usize)>>().unwrap().extend(contacts);
+		}
+	}
+
+	fn create_world() -> World {
+		let mut world = World::new();
+		world
+			.register_component::<Position>()
+			.add_collision2d(CollisionIndex::<Position>::with_grid(10.0))
+			.add_system(ContactSystem);
+		world.insert_resource(Vec::<(bool, usize, usize)>::new());
+		world
+	}
+
+	fn create_collider(world: &mut World, position: [f32; 2]) -> usize {
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Position { position })
+			.add_component_to_entity(entity_id, Collider::new(Shape::Circle { radius: 1.0 }));
+		entity_id
+	}
+
+	fn take_contacts(world: &mut World) -> Vec<(bool, usize, usize)> {
+		std::mem::take(world.borrow_resource_mut::<Vec<(bool, usize, usize)>>().unwrap())
+	}
+
+	#[test]
+	fn started_and_ended() {
+		let mut world = create_world();
+		let entity_id1 = create_collider(&mut world, [0.0, 0.0]);
+		let entity_id2 = create_collider(&mut world, [1.5, 0.0]);
+
+		world.update();
+		assert_eq!(take_contacts(&mut world), vec![(true, entity_id1, entity_id2)]);
+		assert!(world.borrow_resource::<CollisionIndex<Position>>().unwrap().is_colliding(entity_id2, entity_id1));
+
+		// Still overlapping, no new events
+		world.borrow_component_mut::<Position>(entity_id2).unwrap().position = [1.0, 0.5];
+		world.update();
+		assert!(take_contacts(&mut world).is_empty());
+
+		world.borrow_component_mut::<Position>(entity_id2).unwrap().position = [5.0, 0.0];
+		world.update();
+		assert_eq!(take_contacts(&mut world), vec![(false, entity_id1, entity_id2)]);
+		assert!(!world.borrow_resource::<CollisionIndex<Position>>().unwrap().is_colliding(entity_id1, entity_id2));
+
+		world.update();
+		assert!(take_contacts(&mut world).is_empty());
+	}
+
+	#[test]
+	fn ended_on_removal() {
+		let mut world = create_world();
+		let entity_id1 = create_collider(&mut world, [0.0, 0.0]);
+		let entity_id2 = create_collider(&mut world, [0.0, 1.0]);
+		let entity_id3 = create_collider(&mut world, [0.0, -1.0]);
+
+		world.update();
+		assert_eq!(take_contacts(&mut world), vec![
+			(true, entity_id1, entity_id2),
+			(true, entity_id1, entity_id3)
+		]);
+
+		world.remove_component_from_entity::<Collider>(entity_id2);
+		world.remove_entity(entity_id3);
+		world.update();
+		assert_eq!(take_contacts(&mut world), vec![
+			(false, entity_id1, entity_id2),
+			(false, entity_id1, entity_id3)
+		]);
+		assert!(world.borrow_resource::<CollisionIndex<Position>>().unwrap().borrow_contacts().is_empty());
+	}
+}
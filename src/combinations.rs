@@ -0,0 +1,304 @@
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+use super::component::Component;
+use super::entity_manager::{EntityManager, assert_distinct_type_ids};
+
+// Sealed so that fetch_many_mut() can't be called outside of this crate
+mod fetch {
+	use std::any::TypeId;
+
+	use super::super::entity_manager::EntityManager;
+
+	pub trait Fetch {
+		type Item<'a>;
+		type ItemMut<'a>;
+		fn get_type_ids() -> Vec<TypeId>;
+		fn fetch(manager: &EntityManager, entity_id: usize) -> Option<Self::Item<'_>>;
+		// The components of K distinct entities at once. Panics if the entity
+		// ids aren't distinct.
+		fn fetch_many_mut<const K: usize>(manager: &mut EntityManager, entity_ids: [usize; K]) -> Option<[Self::ItemMut<'_>; K]>;
+	}
+}
+
+use fetch::Fetch;
+
+// The components fetched for each entity of a combination. A component
+// type, or a tuple of up to four distinct component types.
+pub trait QueryData: Fetch {}
+
+impl<Q: Fetch> QueryData for Q {}
+
+impl<T: 'static + Component> Fetch for T {
+	type Item<'a> = &'a T;
+	type ItemMut<'a> = &'a mut T;
+
+	fn get_type_ids() -> Vec<TypeId> {
+		vec![TypeId::of::<T>()]
+	}
+
+	fn fetch(manager: &EntityManager, entity_id: usize) -> Option<Self::Item<'_>> {
+		manager.borrow_component::<T>(entity_id)
+	}
+
+	fn fetch_many_mut<const K: usize>(manager: &mut EntityManager, entity_ids: [usize; K]) -> Option<[Self::ItemMut<'_>; K]> {
+		let ptrs = manager.get_component_ptrs::<T, K>(entity_ids)?;
+		// The pointers are to components of distinct entities
+		Some(ptrs.map(|ptr| unsafe { &mut *ptr }))
+	}
+}
+
+macro_rules! impl_fetch_for_tuple {
+	($($t:ident $ptrs:ident),*) => {
+		impl<$($t: 'static + Component),*> Fetch for ($($t,)*) {
+			type Item<'a> = ($(&'a $t,)*);
+			type ItemMut<'a> = ($(&'a mut $t,)*);
+
+			fn get_type_ids() -> Vec<TypeId> {
+				vec![$(TypeId::of::<$t>()),*]
+			}
+
+			fn fetch(manager: &EntityManager, entity_id: usize) -> Option<Self::Item<'_>> {
+				Some(($(manager.borrow_component::<$t>(entity_id)?,)*))
+			}
+
+			fn fetch_many_mut<const K: usize>(manager: &mut EntityManager, entity_ids: [usize; K]) -> Option<[Self::ItemMut<'_>; K]> {
+				// All the pointers are made before any reference so that
+				// making one doesn't invalidate another
+				$(let $ptrs = manager.get_component_ptrs::<$t, K>(entity_ids)?;)*
+				// The entities are distinct and the component types are
+				// distinct, so no component is referenced twice
+				Some(std::array::from_fn(|i| unsafe { ($(&mut *$ptrs[i],)*) }))
+			}
+		}
+	};
+}
+
+impl_fetch_for_tuple!(T1 ptrs1);
+impl_fetch_for_tuple!(T1 ptrs1, T2 ptrs2);
+impl_fetch_for_tuple!(T1 ptrs1, T2 ptrs2, T3 ptrs3);
+impl_fetch_for_tuple!(T1 ptrs1, T2 ptrs2, T3 ptrs3, T4 ptrs4);
+
+// Indices of the current combination in the entity ids, in the
+// lexicographic order
+struct CombinationIndices<const K: usize> {
+	indices: [usize; K],
+	len: usize,
+	started: bool
+}
+
+impl<const K: usize> CombinationIndices<K> {
+	fn new(len: usize) -> Self {
+		CombinationIndices {
+			indices: std::array::from_fn(|i| i),
+			len,
+			started: false
+		}
+	}
+
+	fn advance(&mut self) -> Option<&[usize; K]> {
+		if K == 0 || K > self.len {
+			return None;
+		}
+		if !self.started {
+			self.started = true;
+			return Some(&self.indices);
+		}
+		// The rightmost index which can be incremented
+		let i = (0..K).rev().find(|i| self.indices[*i] < self.len - K + i)?;
+		self.indices[i] += 1;
+		for j in (i + 1)..K {
+			self.indices[j] = self.indices[j - 1] + 1;
+		}
+		Some(&self.indices)
+	}
+}
+
+fn collect_entity_ids<Q: QueryData>(manager: &EntityManager) -> Vec<usize> {
+	let type_ids = Q::get_type_ids();
+	assert_distinct_type_ids(&type_ids);
+	if type_ids.iter().any(|type_id| !manager.is_registered_type_id(*type_id)) {
+		return Vec::new();
+	}
+	let mut entity_ids = Vec::new();
+	manager.collect_entity_ids(&type_ids, &mut entity_ids);
+	// Same order with EntityIdAccessor
+	entity_ids.sort_unstable();
+	entity_ids
+}
+
+// Iterator returned by EntityManager::iter_combinations(). Yields each
+// unordered combination once, the entity ids in ascending order.
+pub struct Combinations<'a, Q: QueryData, const K: usize> {
+	manager: &'a EntityManager,
+	entity_ids: Vec<usize>,
+	indices: CombinationIndices<K>,
+	query: PhantomData<Q>
+}
+
+impl<'a, Q: QueryData, const K: usize> Combinations<'a, Q, K> {
+	pub(crate) fn new(manager: &'a EntityManager) -> Self {
+		let entity_ids = collect_entity_ids::<Q>(manager);
+		Combinations {
+			manager,
+			indices: CombinationIndices::new(entity_ids.len()),
+			entity_ids,
+			query: PhantomData
+		}
+	}
+}
+
+impl<'a, Q: QueryData, const K: usize> Iterator for Combinations<'a, Q, K> {
+	type Item = [(usize, Q::Item<'a>); K];
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let indices = *self.indices.advance()?;
+		let manager = self.manager;
+		let entity_ids = &self.entity_ids;
+		Some(std::array::from_fn(|i| {
+			let entity_id = entity_ids[indices[i]];
+			// The entities were collected with all the components
+			(entity_id, Q::fetch(manager, entity_id).unwrap())
+		}))
+	}
+}
+
+// Returned by EntityManager::iter_combinations_mut()
+//
+// let mut combinations = manager.iter_combinations_mut::<(Position, Collidable), 2>();
+// while let Some([(id1, (position1, collidable1)), (id2, (position2, collidable2))]) = combinations.fetch_next() {
+//     ...
+// }
+pub struct CombinationsMut<'a, Q: QueryData, const K: usize> {
+	manager: &'a mut EntityManager,
+	entity_ids: Vec<usize>,
+	indices: CombinationIndices<K>,
+	query: PhantomData<Q>
+}
+
+impl<'a, Q: QueryData, const K: usize> CombinationsMut<'a, Q, K> {
+	pub(crate) fn new(manager: &'a mut EntityManager) -> Self {
+		let entity_ids = collect_entity_ids::<Q>(manager);
+		CombinationsMut {
+			manager,
+			indices: CombinationIndices::new(entity_ids.len()),
+			entity_ids,
+			query: PhantomData
+		}
+	}
+
+	// Not an Iterator because the components of an entity appear in many
+	// combinations. The returned components borrow self, so the ones of
+	// the previous combination are released before the next one.
+	pub fn fetch_next(&mut self) -> Option<[(usize, Q::ItemMut<'_>); K]> {
+		let indices = *self.indices.advance()?;
+		let entity_ids: [usize; K] = indices.map(|index| self.entity_ids[index]);
+		// The entities in a combination are distinct and were collected
+		// with all the components
+		let items = Q::fetch_many_mut(&mut *self.manager, entity_ids).unwrap();
+		let mut items = IntoIterator::into_iter(items);
+		Some(entity_ids.map(|entity_id| (entity_id, items.next().unwrap())))
+	}
+}
+
+// Run with Miri to check the mutable combinations, e.g.
+// cargo +nightly miri test combinations
+#[cfg(test)]
+mod tests {
+	use crate::component::Component;
+	use crate::entity_manager::{EntityManager, StorageMode};
+	use crate::storage::{BTreeMapStorage, DenseStorage, HashMapStorage, MarkerStorage, SparseSetStorage};
+
+	struct Position(i32);
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	struct Velocity(i32);
+
+	impl Component for Velocity {
+		type Storage = SparseSetStorage<Self>;
+	}
+
+	struct Mass(i32);
+
+	impl Component for Mass {
+		type Storage = HashMapStorage<Self>;
+	}
+
+	struct Charge(i32);
+
+	impl Component for Charge {
+		type Storage = BTreeMapStorage<Self>;
+	}
+
+	#[derive(Default)]
+	struct Collidable;
+
+	impl Component for Collidable {
+		type Storage = MarkerStorage<Self>;
+	}
+
+	fn create_manager(storage_mode: StorageMode) -> EntityManager {
+		let mut manager = EntityManager::with_storage_mode(storage_mode);
+		manager
+			.register::<Position>()
+			.register::<Velocity>()
+			.register::<Mass>()
+			.register::<Charge>()
+			.register::<Collidable>();
+		for i in 0..4 {
+			let entity_id = manager.create_entity();
+			manager
+				.add_component_to_entity(entity_id, Position(i))
+				.add_component_to_entity(entity_id, Velocity(i))
+				.add_component_to_entity(entity_id, Mass(i))
+				.add_component_to_entity(entity_id, Charge(i))
+				.add_component_to_entity(entity_id, Collidable);
+		}
+		// In another archetype in Archetype mode
+		manager.remove_component_from_entity::<Velocity>(3);
+		manager
+	}
+
+	fn pairs_mut(storage_mode: StorageMode) {
+		let mut manager = create_manager(storage_mode);
+		let mut count = 0;
+		let mut combinations = manager.iter_combinations_mut::<(Position, Mass, Charge, Collidable), 2>();
+		while let Some([(id1, (p1, m1, c1, _)), (id2, (p2, m2, c2, _))]) = combinations.fetch_next() {
+			assert!(id1 < id2);
+			// Both entities are written while both are borrowed
+			p1.0 += 1;
+			p2.0 += 1;
+			m1.0 += p2.0;
+			m2.0 += p1.0;
+			c1.0 += 1;
+			c2.0 += 1;
+			count += 1;
+		}
+		assert_eq!(count, 6);
+		for entity_id in 0..4 {
+			assert_eq!(manager.borrow_component::<Position>(entity_id).unwrap().0, entity_id as i32 + 3);
+			assert_eq!(manager.borrow_component::<Charge>(entity_id).unwrap().0, entity_id as i32 + 3);
+		}
+
+		let mut count = 0;
+		let mut combinations = manager.iter_combinations_mut::<Velocity, 2>();
+		while let Some([(_, v1), (_, v2)]) = combinations.fetch_next() {
+			std::mem::swap(&mut v1.0, &mut v2.0);
+			count += 1;
+		}
+		assert_eq!(count, 3);
+	}
+
+	#[test]
+	fn pairs_mut_per_component() {
+		pairs_mut(StorageMode::PerComponent);
+	}
+
+	#[test]
+	fn pairs_mut_archetype() {
+		pairs_mut(StorageMode::Archetype);
+	}
+}
@@ -0,0 +1,68 @@
+use super::bundle::Bundle;
+use super::component::Component;
+use super::entity_manager::EntityManager;
+
+type Command = Box<dyn FnOnce(&mut EntityManager)>;
+
+// Structural changes deferred until the running system returns, e.g. from
+// scripts or while iterating components. World::update() applies them in
+// order after each system.
+pub struct Commands {
+	commands: Vec<Command>
+}
+
+impl Default for Commands {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Commands {
+	pub fn new() -> Self {
+		Commands {
+			commands: Vec::new()
+		}
+	}
+
+	pub fn spawn<T: 'static + Bundle>(&mut self, bundle: T) -> &mut Self {
+		self.push(move |manager| {
+			let entity_id = manager.create_entity();
+			manager.add_bundle_to_entity(entity_id, bundle);
+		})
+	}
+
+	pub fn despawn(&mut self, entity_id: usize) -> &mut Self {
+		self.push(move |manager| {
+			if manager.has_entity(entity_id) {
+				manager.remove_entity(entity_id);
+			}
+		})
+	}
+
+	pub fn add_component<T: 'static + Component>(&mut self, entity_id: usize, component: T) -> &mut Self {
+		self.push(move |manager| {
+			if manager.has_entity(entity_id) {
+				manager.add_component_to_entity(entity_id, component);
+			}
+		})
+	}
+
+	pub fn remove_component<T: 'static + Component>(&mut self, entity_id: usize) -> &mut Self {
+		self.push(move |manager| {
+			manager.remove_component_from_entity::<T>(entity_id);
+		})
+	}
+
+	pub fn push<F: 'static + FnOnce(&mut EntityManager)>(&mut self, command: F) -> &mut Self {
+		self.commands.push(Box::new(command));
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.commands.is_empty()
+	}
+
+	pub(crate) fn take(&mut self) -> Vec<Command> {
+		std::mem::take(&mut self.commands)
+	}
+}
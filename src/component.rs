@@ -1,2 +1,20 @@
+use super::storage::ComponentStorage;
+use super::world::World;
+
+#[cfg(feature = "derive")]
+pub use ecs_rust_derive::Component;
+
 pub trait Component: Sized {
+	// Where the components are kept in PerComponent mode, e.g.
+	// DenseStorage<Self> or MarkerStorage<Self>. See storage.rs.
+	type Storage: ComponentStorage<Component = Self>;
+}
+
+// Implemented by #[derive(Component)]. Registers the component to a world
+// with the options of the #[component(...)] attribute. See World::register().
+pub trait ComponentRegistration: Component {
+	// Stable name used for serialization
+	const NAME: &'static str;
+
+	fn register_to(world: &mut World);
 }
@@ -1,7 +1,7 @@
-use std::collections::HashMap;
-use std::any::{Any, TypeId};
+use std::any::{type_name, Any, TypeId};
 
 use super::component::Component;
+use super::storage::ComponentStorage;
 
 // @TODO: Write comment
 pub trait ComponentManagerTrait {
@@ -13,9 +13,12 @@ pub trait ComponentManagerTrait {
 	fn has(&self, entity_id: usize) -> bool;
 	fn remove(&mut self, entity_id: usize);
 	fn get_type_id(&self) -> TypeId;
+	fn get_type_name(&self) -> &'static str;
+	fn get_component_count(&self) -> usize;
+	fn collect_entity_ids(&self, dst: &mut Vec<usize>);
 }
 
-impl<T: 'static + Component> ComponentManagerTrait for ComponentManager<T> {
+impl<S: ComponentStorage> ComponentManagerTrait for S {
     fn as_any(&self) -> &dyn Any {
         self as &dyn Any
     }
@@ -25,55 +28,121 @@ impl<T: 'static + Component> ComponentManagerTrait for ComponentManager<T> {
     }
 
 	fn has(&self, entity_id: usize) -> bool {
-		let manager = cast_manager::<T>(self);
-		manager.has(entity_id)
+		ComponentStorage::has(self, entity_id)
 	}
 
 	fn remove(&mut self, entity_id: usize) {
-		let manager = cast_manager_mut::<T>(self);
-		manager.remove(entity_id);
+		ComponentStorage::remove(self, entity_id);
 	}
 
 	fn get_type_id(&self) -> TypeId {
-		TypeId::of::<T>()
+		TypeId::of::<S::Component>()
+	}
+
+	fn get_type_name(&self) -> &'static str {
+		type_name::<S::Component>()
+	}
+
+	fn get_component_count(&self) -> usize {
+		ComponentStorage::len(self)
+	}
+
+	fn collect_entity_ids(&self, dst: &mut Vec<usize>) {
+		ComponentStorage::collect_entity_ids(self, dst);
 	}
 }
 
 // @TODO: Write comment
 pub fn cast_manager<T: 'static + Component>
-	(manager: &dyn ComponentManagerTrait) -> &ComponentManager<T> {
+	(manager: &dyn ComponentManagerTrait) -> &T::Storage {
 	manager
 		.as_any()
-		.downcast_ref::<ComponentManager<T>>()
+		.downcast_ref::<T::Storage>()
 		.unwrap()
 }
 
 // @TODO: Write comment
 pub fn cast_manager_mut<T: 'static + Component>
-	(manager: &mut dyn ComponentManagerTrait) -> &mut ComponentManager<T> {
+	(manager: &mut dyn ComponentManagerTrait) -> &mut T::Storage {
 	manager
 		.as_any_mut()
-		.downcast_mut::<ComponentManager<T>>()
+		.downcast_mut::<T::Storage>()
 		.unwrap()
 }
 
-pub struct ComponentManager<T: Component> {
+// Used for world snapshots
+pub fn clone_manager<T: 'static + Component + Clone>
+	(manager: &dyn ComponentManagerTrait) -> Box<dyn ComponentManagerTrait> {
+	Box::new(cast_manager::<T>(manager).clone_storage())
+}
+
+const PAGE_SIZE: usize = 256;
+const INVALID_INDEX: usize = usize::MAX;
+
+// Maps entity ids to indices without hashing. Entity ids are small dense
+// integers so they index an array directly. The array is split into pages
+// allocated on first use so that components only a few entities with large
+// ids have don't need a large array.
+#[derive(Clone)]
+pub(crate) struct SparseIndex {
+	pages: Vec<Option<Box<[usize; PAGE_SIZE]>>>
+}
+
+impl SparseIndex {
+	pub(crate) fn new() -> Self {
+		SparseIndex {
+			pages: Vec::new()
+		}
+	}
+
+	pub(crate) fn get(&self, entity_id: usize) -> Option<usize> {
+		match self.pages.get(entity_id / PAGE_SIZE) {
+			Some(Some(page)) if page[entity_id % PAGE_SIZE] != INVALID_INDEX => Some(page[entity_id % PAGE_SIZE]),
+			_ => None
+		}
+	}
+
+	pub(crate) fn insert(&mut self, entity_id: usize, index: usize) {
+		let page_index = entity_id / PAGE_SIZE;
+		if self.pages.len() <= page_index {
+			self.pages.resize_with(page_index + 1, || None);
+		}
+		let page = self.pages[page_index].get_or_insert_with(|| Box::new([INVALID_INDEX; PAGE_SIZE]));
+		page[entity_id % PAGE_SIZE] = index;
+	}
+
+	pub(crate) fn remove(&mut self, entity_id: usize) {
+		if let Some(Some(page)) = self.pages.get_mut(entity_id / PAGE_SIZE) {
+			page[entity_id % PAGE_SIZE] = INVALID_INDEX;
+		}
+	}
+}
+
+// Dense storage, the default choice. Components are packed in a Vec.
+#[derive(Clone)]
+pub struct ComponentManager<T> {
 	components: Vec<T>, // Component contents
 	entity_ids: Vec<usize>, // Same order with components
-	entity_id_map: HashMap<usize, usize>  // entity_id -> index in components
+	entity_id_map: SparseIndex // entity_id -> index in components
+}
+
+impl<T> Default for ComponentManager<T> {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
-impl<T: Component> ComponentManager<T> {
+impl<T> ComponentManager<T> {
 	pub fn new() -> Self {
 		ComponentManager {
 			components: Vec::new(),
 			entity_ids: Vec::new(),
-			entity_id_map: HashMap::new(),
+			entity_id_map: SparseIndex::new(),
 		}
 	}
 
 	pub fn has(&self, entity_id: usize) -> bool {
-		self.entity_id_map.contains_key(&entity_id)
+		self.entity_id_map.get(entity_id).is_some()
 	}
 
 	pub fn add(&mut self, entity_id: usize, component: T) {
@@ -92,27 +161,33 @@ impl<T: Component> ComponentManager<T> {
 			// Nothing to do? Throw error? Update component?
 			return;
 		}
-		let index = *self.entity_id_map.get(&entity_id).unwrap();
+		let index = self.entity_id_map.get(entity_id).unwrap();
 		self.entity_id_map.insert(*self.entity_ids.last().unwrap(), index);
 		self.components.swap_remove(index);
 		self.entity_ids.swap_remove(index);
-		self.entity_id_map.remove(&entity_id);
+		self.entity_id_map.remove(entity_id);
+	}
+
+	pub fn take(&mut self, entity_id: usize) -> Option<T> {
+		let index = self.entity_id_map.get(entity_id)?;
+		self.entity_id_map.insert(*self.entity_ids.last().unwrap(), index);
+		self.entity_ids.swap_remove(index);
+		self.entity_id_map.remove(entity_id);
+		Some(self.components.swap_remove(index))
 	}
 
 	pub fn borrow_component(&self, entity_id: usize) -> Option<&T> {
-		if !self.has(entity_id) {
-			return None;
+		match self.entity_id_map.get(entity_id) {
+			Some(index) => Some(&self.components[index]),
+			None => None
 		}
-		let index = self.entity_id_map.get(&entity_id).unwrap();
-		Some(&self.components[*index])
 	}
 
 	pub fn borrow_component_mut(&mut self, entity_id: usize) -> Option<&mut T> {
-		if !self.has(entity_id) {
-			return None;
+		match self.entity_id_map.get(entity_id) {
+			Some(index) => Some(&mut self.components[index]),
+			None => None
 		}
-		let index = self.entity_id_map.get(&entity_id).unwrap();
-		Some(&mut self.components[*index])
 	}
 
 	pub fn borrow_entity_ids(&self) -> &Vec<usize> {
@@ -127,3 +202,65 @@ impl<T: Component> ComponentManager<T> {
 		&mut self.components
 	}
 }
+
+impl<T: 'static> ComponentStorage for ComponentManager<T> {
+	type Component = T;
+
+	fn has(&self, entity_id: usize) -> bool {
+		ComponentManager::has(self, entity_id)
+	}
+
+	fn add(&mut self, entity_id: usize, component: T) {
+		ComponentManager::add(self, entity_id, component);
+	}
+
+	fn remove(&mut self, entity_id: usize) {
+		ComponentManager::remove(self, entity_id);
+	}
+
+	fn take(&mut self, entity_id: usize) -> Option<T> {
+		ComponentManager::take(self, entity_id)
+	}
+
+	fn len(&self) -> usize {
+		self.components.len()
+	}
+
+	fn borrow_component(&self, entity_id: usize) -> Option<&T> {
+		ComponentManager::borrow_component(self, entity_id)
+	}
+
+	fn borrow_component_mut(&mut self, entity_id: usize) -> Option<&mut T> {
+		ComponentManager::borrow_component_mut(self, entity_id)
+	}
+
+	fn borrow_components_disjoint_mut<const K: usize>(&mut self, entity_ids: [usize; K]) -> Option<[&mut T; K]> {
+		let mut indices = [0; K];
+		for (index, entity_id) in indices.iter_mut().zip(entity_ids.iter()) {
+			*index = self.entity_id_map.get(*entity_id)?;
+		}
+		Some(self.components.get_disjoint_mut(indices).expect("The entity ids must be distinct"))
+	}
+
+	fn collect_entity_ids(&self, dst: &mut Vec<usize>) {
+		dst.extend_from_slice(&self.entity_ids);
+	}
+
+	fn for_each_mut<F: FnMut(usize, &mut T)>(&mut self, mut f: F) {
+		for (entity_id, component) in self.entity_ids.iter().zip(self.components.iter_mut()) {
+			f(*entity_id, component);
+		}
+	}
+
+	fn clone_storage(&self) -> Self where T: Clone {
+		self.clone()
+	}
+
+	fn borrow_dense_components(&self) -> Option<&Vec<T>> {
+		Some(&self.components)
+	}
+
+	fn borrow_dense_components_mut(&mut self) -> Option<&mut Vec<T>> {
+		Some(&mut self.components)
+	}
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+use std::fmt::Write;
+
+use super::dynamic::ComponentId;
+use super::entity_manager::EntityManager;
+use super::type_registry::get_short_type_name;
+
+// Readable listing of the entities and their components. Components and
+// entities are sorted by name and id, so the same world state always
+// produces the same text, e.g. for golden file tests.
+//
+// storage mode: PerComponent
+// frame: 12
+// entities: 2 live, 1 free [1]
+// component frames:
+//   Position: 3
+// entity 0
+//   Position { x: 1.0, y: 2.0 }
+// entity 2
+//   Health [0a 00 00 00]
+pub(crate) fn dump(manager: &EntityManager) -> String {
+	let mut text = String::new();
+	// Writing to a String never fails
+	write_dump(manager, &mut text).unwrap();
+	text
+}
+
+fn write_dump(manager: &EntityManager, text: &mut String) -> fmt::Result {
+	let entity_ids = manager.get_entity_ids();
+	let available_ids = manager.borrow_available_entity_ids();
+
+	writeln!(text, "storage mode: {:?}", manager.get_storage_mode())?;
+	writeln!(text, "frame: {}", manager.get_frame())?;
+	writeln!(text, "entities: {} live, {} free {:?}", entity_ids.len(), available_ids.len(), available_ids)?;
+
+	// Registered components sorted by name. The ones not in the type
+	// registry are named by their type names. Same names, e.g. of types in
+	// different modules, are ordered by the full type names.
+	let mut types = manager.get_registered_types().into_iter().map(|(type_id, type_name)| {
+		match manager.borrow_type_registry().borrow_type_info_by_type_id(type_id) {
+			Some(type_info) => (type_info.get_name().to_string(), type_name, type_id, Some(type_info)),
+			None => (get_short_type_name(type_name), type_name, type_id, None)
+		}
+	}).collect::<Vec<_>>();
+	types.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+	let mut dynamic_ids = manager.get_dynamic_component_ids();
+	dynamic_ids.sort_by_key(|component_id| get_dynamic_name(manager, *component_id));
+
+	writeln!(text, "component frames:")?;
+	for (name, _, type_id, _) in types.iter() {
+		writeln!(text, "  {}: {}", name, manager.get_updated_frame(*type_id))?;
+	}
+
+	for entity_id in entity_ids.iter() {
+		writeln!(text, "entity {}", entity_id)?;
+		for (name, _, type_id, type_info) in types.iter() {
+			match type_info {
+				Some(type_info) => if let Some(component) = type_info.format_component(manager, *entity_id) {
+					writeln!(text, "  {}", component)?;
+				},
+				None => if manager.has_component_type_id(*entity_id, *type_id) {
+					writeln!(text, "  {}", name)?;
+				}
+			};
+		}
+		for component_id in dynamic_ids.iter() {
+			if let Some(bytes) = manager.borrow_dynamic_component(*entity_id, *component_id) {
+				let bytes = bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<String>>();
+				writeln!(text, "  {} [{}]", get_dynamic_name(manager, *component_id), bytes.join(" "))?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn get_dynamic_name(manager: &EntityManager, component_id: ComponentId) -> &str {
+	manager.borrow_dynamic_component_layout(component_id).unwrap().get_name()
+}
+
+impl fmt::Debug for EntityManager {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&dump(self))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+
+	use crate::component::Component;
+	use crate::storage::{DenseStorage, MarkerStorage};
+	use crate::entity_manager::StorageMode;
+	use crate::world::World;
+
+	struct Position {
+		x: f32,
+		y: f32
+	}
+
+	impl fmt::Debug for Position {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			write!(f, "Position {{ x: {:?}, y: {:?} }}", self.x, self.y)
+		}
+	}
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	// Not in the type registry
+	#[derive(Default)]
+	struct Frozen;
+
+	impl Component for Frozen {
+		type Storage = MarkerStorage<Self>;
+	}
+
+	mod other {
+		use crate::component::Component;
+		use crate::storage::MarkerStorage;
+
+		// Same short name as the outer Position, not in the type registry
+		#[derive(Default)]
+		pub struct Position;
+
+		impl Component for Position {
+			type Storage = MarkerStorage<Self>;
+		}
+	}
+
+	const GOLDEN: &str = "\
+frame: 0
+entities: 2 live, 1 free [1]
+component frames:
+  Frozen: 0
+  Position: 0
+entity 0
+  Position { x: 1.0, y: 2.0 }
+entity 2
+  Frozen
+  Position { x: 3.0, y: 4.0 }
+";
+
+	fn create_world(world: &mut World) {
+		world.register_debuggable_component::<Position>();
+		world.borrow_entity_manager_mut().register::<Frozen>();
+
+		let entity_id = world.create_entity();
+		world.add_component_to_entity(entity_id, Position { x: 1.0, y: 2.0 });
+		let removed_id = world.create_entity();
+		let entity_id = world.create_entity();
+		world.add_component_to_entity(entity_id, Position { x: 3.0, y: 4.0 });
+		world.add_component_to_entity(entity_id, Frozen);
+		world.remove_entity(removed_id);
+	}
+
+	#[test]
+	fn golden_dump() {
+		let mut world = World::new();
+		create_world(&mut world);
+		assert_eq!(world.dump(), format!("storage mode: PerComponent\n{}", GOLDEN));
+	}
+
+	#[test]
+	fn golden_dump_archetype() {
+		let mut world = World::with_storage_mode(StorageMode::Archetype);
+		create_world(&mut world);
+		assert_eq!(world.dump(), format!("storage mode: Archetype\n{}", GOLDEN));
+	}
+
+	#[test]
+	fn same_names_ordered_by_type_name() {
+		let mut world = World::new();
+		world.register_debuggable_component::<Position>();
+		world.borrow_entity_manager_mut().register::<other::Position>();
+		let entity_id = world.create_entity();
+		world.add_component_to_entity(entity_id, other::Position);
+		world.add_component_to_entity(entity_id, Position { x: 1.0, y: 2.0 });
+
+		assert!(world.dump().ends_with("entity 0\n  Position { x: 1.0, y: 2.0 }\n  Position\n"));
+	}
+}
@@ -0,0 +1,205 @@
+use std::alloc::{self, Layout};
+use std::any::TypeId;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+use super::component_manager::SparseIndex;
+
+// Identifies a component type in the type-erased APIs. Static ids are
+// for Rust types implementing Component, dynamic ids are for components
+// registered at runtime from a ComponentLayout.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ComponentId {
+	Static(TypeId),
+	Dynamic(usize)
+}
+
+// Describes a component type unknown at compile time, e.g. defined by
+// a mod or a script. Its values are plain bytes.
+#[derive(Clone)]
+pub struct ComponentLayout {
+	name: String,
+	size: usize,
+	align: usize,
+	drop: Option<unsafe fn(*mut u8)>
+}
+
+impl ComponentLayout {
+	pub fn new(name: &str, size: usize, align: usize) -> Self {
+		ComponentLayout {
+			name: name.to_string(),
+			size,
+			align,
+			drop: None
+		}
+	}
+
+	/// drop is called with a pointer to each component value when it is
+	/// removed or when the world is dropped.
+	///
+	/// # Safety
+	///
+	/// drop must be safe to call with any bytes given to
+	/// add_dynamic_component_to_entity() for this component, aligned to align.
+	pub unsafe fn with_drop(mut self, drop: unsafe fn(*mut u8)) -> Self {
+		self.drop = Some(drop);
+		self
+	}
+
+	pub fn get_name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn get_size(&self) -> usize {
+		self.size
+	}
+
+	pub fn get_align(&self) -> usize {
+		self.align
+	}
+}
+
+// Densely packed byte values of a dynamic component
+pub(crate) struct DynamicStorage {
+	layout: ComponentLayout,
+	item_layout: Layout, // Size padded to the alignment
+	data: NonNull<u8>,
+	capacity: usize,
+	entity_ids: Vec<usize>, // Same order with the values
+	entity_id_map: SparseIndex // entity_id -> index in the values
+}
+
+impl DynamicStorage {
+	// None if the size and alignment are invalid
+	pub(crate) fn new(layout: ComponentLayout) -> Option<Self> {
+		let item_layout = Layout::from_size_align(layout.size, layout.align).ok()?.pad_to_align();
+		Some(DynamicStorage {
+			data: dangling(item_layout.align()),
+			capacity: match item_layout.size() {
+				0 => usize::MAX,
+				_ => 0
+			},
+			layout,
+			item_layout,
+			entity_ids: Vec::new(),
+			entity_id_map: SparseIndex::new()
+		})
+	}
+
+	pub(crate) fn borrow_layout(&self) -> &ComponentLayout {
+		&self.layout
+	}
+
+	pub(crate) fn borrow_entity_ids(&self) -> &Vec<usize> {
+		&self.entity_ids
+	}
+
+	pub(crate) fn has(&self, entity_id: usize) -> bool {
+		self.entity_id_map.get(entity_id).is_some()
+	}
+
+	// Returns false if the entity already has the component or the
+	// bytes length doesn't match the size
+	pub(crate) fn add(&mut self, entity_id: usize, bytes: &[u8]) -> bool {
+		if self.has(entity_id) || bytes.len() != self.layout.size {
+			return false;
+		}
+		let index = self.entity_ids.len();
+		if index == self.capacity {
+			self.grow();
+		}
+		unsafe {
+			ptr::copy_nonoverlapping(bytes.as_ptr(), self.get_ptr(index), bytes.len());
+		}
+		self.entity_ids.push(entity_id);
+		self.entity_id_map.insert(entity_id, index);
+		true
+	}
+
+	pub(crate) fn remove(&mut self, entity_id: usize) {
+		let index = match self.entity_id_map.get(entity_id) {
+			Some(index) => index,
+			None => return
+		};
+		let last_index = self.entity_ids.len() - 1;
+		unsafe {
+			if let Some(drop) = self.layout.drop {
+				drop(self.get_ptr(index));
+			}
+			if index != last_index {
+				ptr::copy_nonoverlapping(self.get_ptr(last_index), self.get_ptr(index), self.item_layout.size());
+			}
+		}
+		self.entity_id_map.insert(self.entity_ids[last_index], index);
+		self.entity_id_map.remove(entity_id);
+		self.entity_ids.swap_remove(index);
+	}
+
+	// Removes the value without dropping it and returns its bytes
+	pub(crate) fn take(&mut self, entity_id: usize) -> Option<Vec<u8>> {
+		let bytes = self.borrow(entity_id)?.to_vec();
+		let drop = self.layout.drop.take();
+		self.remove(entity_id);
+		self.layout.drop = drop;
+		Some(bytes)
+	}
+
+	pub(crate) fn borrow(&self, entity_id: usize) -> Option<&[u8]> {
+		let index = self.entity_id_map.get(entity_id)?;
+		unsafe {
+			Some(slice::from_raw_parts(self.get_ptr(index), self.layout.size))
+		}
+	}
+
+	pub(crate) fn borrow_mut(&mut self, entity_id: usize) -> Option<&mut [u8]> {
+		let index = self.entity_id_map.get(entity_id)?;
+		unsafe {
+			Some(slice::from_raw_parts_mut(self.get_ptr(index), self.layout.size))
+		}
+	}
+
+	fn get_ptr(&self, index: usize) -> *mut u8 {
+		unsafe { self.data.as_ptr().add(index * self.item_layout.size()) }
+	}
+
+	// Not called for zero-sized components whose capacity is usize::MAX
+	fn grow(&mut self) {
+		let new_capacity = match self.capacity {
+			0 => 4,
+			capacity => capacity * 2
+		};
+		let new_layout = array_layout(self.item_layout, new_capacity);
+		let data = unsafe {
+			match self.capacity {
+				0 => alloc::alloc(new_layout),
+				_ => alloc::realloc(self.data.as_ptr(), array_layout(self.item_layout, self.capacity), new_layout.size())
+			}
+		};
+		self.data = match NonNull::new(data) {
+			Some(data) => data,
+			None => alloc::handle_alloc_error(new_layout)
+		};
+		self.capacity = new_capacity;
+	}
+}
+
+impl Drop for DynamicStorage {
+	fn drop(&mut self) {
+		if let Some(drop) = self.layout.drop {
+			for index in 0..self.entity_ids.len() {
+				unsafe { drop(self.get_ptr(index)); }
+			}
+		}
+		if self.item_layout.size() != 0 && self.capacity != 0 {
+			unsafe { alloc::dealloc(self.data.as_ptr(), array_layout(self.item_layout, self.capacity)); }
+		}
+	}
+}
+
+fn array_layout(item_layout: Layout, capacity: usize) -> Layout {
+	Layout::from_size_align(item_layout.size() * capacity, item_layout.align()).unwrap()
+}
+
+fn dangling(align: usize) -> NonNull<u8> {
+	NonNull::new(align as *mut u8).unwrap()
+}
@@ -1,10 +1,59 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Unique in the process, given to each World
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct WorldId(u64);
+
+static NEXT_WORLD_ID: AtomicU64 = AtomicU64::new(0);
+
+impl WorldId {
+	pub(crate) fn new() -> Self {
+		WorldId(NEXT_WORLD_ID.fetch_add(1, Ordering::Relaxed))
+	}
+}
+
+// Entity id tagged with the world it belongs to, so that using it with
+// another world is caught. See World::get_entity(), move_entity_to() and
+// the *_by_handle() accessors.
+// @TODO: No generation yet, a handle of a removed entity refers to the
+// next entity reusing the id.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Entity {
-	alive: bool
+	world_id: WorldId,
+	id: usize
 }
 
 impl Entity {
-	pub fn new() -> Self {
+	pub(crate) fn new(world_id: WorldId, id: usize) -> Self {
 		Entity {
+			world_id,
+			id
+		}
+	}
+
+	pub fn get_world_id(&self) -> WorldId {
+		self.world_id
+	}
+
+	pub fn get_id(&self) -> usize {
+		self.id
+	}
+}
+
+#[derive(Clone)]
+pub(crate) struct EntitySlot {
+	alive: bool
+}
+
+impl Default for EntitySlot {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl EntitySlot {
+	pub fn new() -> Self {
+		EntitySlot {
 			alive: true
 		}
 	}
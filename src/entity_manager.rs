@@ -1,19 +1,38 @@
 use std::collections::HashMap;
 use std::any::TypeId;
-use std::mem::transmute;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
 use std::vec;
 
-use super::entity::Entity;
+use super::entity::EntitySlot;
 use super::component::Component;
+use super::bundle::Bundle;
+use super::commands::Commands;
+use super::hooks::{HookKind, HookRegistry};
+use super::combinations::{Combinations, CombinationsMut, QueryData};
 use super::component_manager::{
-	ComponentManager,
 	ComponentManagerTrait,
 	cast_manager,
-	cast_manager_mut
+	cast_manager_mut,
+	clone_manager,
+	SparseIndex
 };
-
+use super::storage::{ComponentStorage, assert_distinct_entity_ids};
+use super::archetype::ArchetypeStorage;
+use super::dynamic::{ComponentId, ComponentLayout, DynamicStorage};
+use super::reflect::Value;
+use super::type_registry::TypeRegistry;
+use super::dump::dump;
+use super::resource_manager::ResourceManager;
+use super::events::Events;
+use super::hierarchy::{Children, Parent};
+
+type CloneManagerFn = fn(&dyn ComponentManagerTrait) -> Box<dyn ComponentManagerTrait>;
+
+#[derive(Clone)]
 struct Entities {
-	entities: Vec<Entity>,
+	entities: Vec<EntitySlot>,
 	availables: Vec<usize>
 }
 
@@ -30,16 +49,26 @@ impl Entities {
 	}
 
 	fn create(&mut self) -> usize {
-		if self.availables.len() > 0 {
+		if !self.availables.is_empty() {
 			let index = self.availables.remove(0);
 			self.entities[index].reset();
 			return index;
 		}
-		let entity = Entity::new();
+		let entity = EntitySlot::new();
 		self.entities.push(entity);
 		self.entities.len() - 1
 	}
 
+	fn get_ids(&self) -> Vec<usize> {
+		(0..self.entities.len())
+			.filter(|id| self.entities[*id].is_alive())
+			.collect()
+	}
+
+	fn borrow_availables(&self) -> &Vec<usize> {
+		&self.availables
+	}
+
 	fn remove(&mut self, entity_id: usize) {
 		if !self.has(entity_id) {
 			// @TODO: Error handling
@@ -50,81 +79,176 @@ impl Entities {
 	}
 }
 
-// @TODO: Is this name good?
-pub struct EntityIdAccessor {
-	cache_map: HashMap<TypeId, Vec<usize>>,
-	updated_frame_map: HashMap<TypeId, u64> // @TODO: Rename
+// Max changes kept. Beyond this the id caches are rebuilt instead.
+const MAX_COMPONENT_CHANGES: usize = 1 << 16;
+
+// Log of the component additions and removals, read by EntityIdAccessor
+// to update its caches incrementally
+#[derive(Clone)]
+struct ComponentChanges {
+	start: u64, // Sequence number of changes[0]
+	changes: Vec<(usize, Option<TypeId>)> // Entity id and component type, None for entity removal
 }
 
-impl EntityIdAccessor {
-	pub fn new() -> Self {
-		EntityIdAccessor {
-			cache_map: HashMap::new(),
-			updated_frame_map: HashMap::new()
+impl ComponentChanges {
+	fn new() -> Self {
+		ComponentChanges {
+			start: 0,
+			changes: Vec::new()
 		}
 	}
 
-	pub fn borrow_ids<T: 'static + Component>(&mut self, manager: &EntityManager) -> Option<&Vec<usize>> {
-		if !manager.has_component_manager::<T>() {
+	fn push(&mut self, entity_id: usize, type_id: Option<TypeId>) {
+		if self.changes.len() == MAX_COMPONENT_CHANGES {
+			self.clear();
+		}
+		self.changes.push((entity_id, type_id));
+	}
+
+	fn get_end(&self) -> u64 {
+		self.start + self.changes.len() as u64
+	}
+
+	fn borrow_since(&self, sequence: u64) -> Option<&[(usize, Option<TypeId>)]> {
+		if sequence < self.start || sequence > self.get_end() {
 			return None;
 		}
+		Some(&self.changes[(sequence - self.start) as usize..])
+	}
 
-		let type_id = TypeId::of::<T>();
-		let needs_update = if !self.cache_map.contains_key(&type_id) {
-			self.cache_map.insert(type_id, Vec::new());
-			true
-		} else {
-			let updated_frame = *self.updated_frame_map.get(&type_id).unwrap();
-			manager.get_updated_frame::<T>() != updated_frame
-		};
+	// Drops the changes. Readers which haven't seen them rebuild their caches.
+	fn clear(&mut self) {
+		self.start = self.get_end();
+		self.changes.clear();
+	}
 
-		if needs_update {
-			let src = &manager.borrow_entity_ids::<T>().unwrap();
-			let dst = self.cache_map.get_mut(&type_id).unwrap();
-			dst.clear();
-			for id in src.iter() {
-				dst.push(*id);
-			}
-			self.updated_frame_map.insert(type_id, manager.get_frame());
+	// Drops the changes before the sequence number
+	fn clear_before(&mut self, sequence: u64) {
+		if sequence <= self.start || sequence > self.get_end() {
+			return;
 		}
+		self.changes.drain(..(sequence - self.start) as usize);
+		self.start = sequence;
+	}
 
-		self.cache_map.get(&type_id)
+	// Makes all the readers rebuild their caches, e.g. on restore
+	fn invalidate(&mut self) {
+		self.clear();
+		self.start += 1;
 	}
+}
 
-	pub fn borrow_ids_for_pair<
-		T1: 'static + Component,
-		T2: 'static + Component
-	>(&mut self, manager: &EntityManager) -> Option<&Vec<usize>> {
-		if !manager.has_component_manager::<T1>() ||
-			!manager.has_component_manager::<T2>() {
-			return None;
+// Entities whose components of a tracked type may have been mutably
+// borrowed since the last take, see EntityManager::track_mutations()
+#[derive(Default)]
+struct Mutations {
+	entity_ids: Vec<usize>,
+	all: bool // By the accessors borrowing all the components, e.g. for_each_mut()
+}
+
+impl Mutations {
+	fn push(&mut self, entity_id: Option<usize>) {
+		if self.all {
+			return;
 		}
+		match entity_id {
+			Some(entity_id) if self.entity_ids.len() < MAX_COMPONENT_CHANGES => self.entity_ids.push(entity_id),
+			_ => {
+				self.all = true;
+				self.entity_ids.clear();
+			}
+		};
+	}
+}
+
+// Entity ids having all the types of a query. Sorted when built, then
+// updated in O(1) per change, so the order depends on the history of the
+// changes. The same changes give the same order, e.g. on replay.
+struct QueryCache {
+	type_ids: Vec<TypeId>,
+	ids: Vec<usize>,
+	id_map: SparseIndex // entity_id -> index in ids
+}
 
-		let type_id = TypeId::of::<(T1, T2)>();
-		let needs_update = if !self.cache_map.contains_key(&type_id) {
-			self.cache_map.insert(type_id, Vec::new());
-			true
-		} else {
-			let updated_frame = *self.updated_frame_map.get(&type_id).unwrap();
-			manager.get_updated_frame::<T1>() != updated_frame ||
-				manager.get_updated_frame::<T2>() != updated_frame
+impl QueryCache {
+	fn new(manager: &EntityManager, type_ids: &[TypeId]) -> Self {
+		let mut cache = QueryCache {
+			type_ids: type_ids.to_vec(),
+			ids: Vec::new(),
+			id_map: SparseIndex::new()
 		};
+		cache.rebuild(manager);
+		cache
+	}
+
+	fn rebuild(&mut self, manager: &EntityManager) {
+		self.ids.clear();
+		manager.collect_entity_ids(&self.type_ids, &mut self.ids);
+		self.ids.sort_unstable();
+		self.id_map = SparseIndex::new();
+		for (index, entity_id) in self.ids.iter().enumerate() {
+			self.id_map.insert(*entity_id, index);
+		}
+	}
 
-		if needs_update {
-			// @TODO: Can be optimized if iterating a shorter array
-			let src = &manager.borrow_entity_ids::<T1>().unwrap();
-			let manager2 = manager.borrow_component_manager::<T2>();
-			let dst = self.cache_map.get_mut(&type_id).unwrap();
-			dst.clear();
-			for id in src.iter() {
-				if manager2.has(*id) {
-					dst.push(*id);
+	// Checks if the entity still matches after its change
+	fn update(&mut self, manager: &EntityManager, entity_id: usize) {
+		let matches = manager.has_entity(entity_id) && self.type_ids.iter()
+			.all(|type_id| manager.has_component_type_id(entity_id, *type_id));
+		match self.id_map.get(entity_id) {
+			Some(index) => if !matches {
+				self.ids.swap_remove(index);
+				self.id_map.remove(entity_id);
+				if let Some(moved_id) = self.ids.get(index) {
+					self.id_map.insert(*moved_id, index);
 				}
+			},
+			None => if matches {
+				self.id_map.insert(entity_id, self.ids.len());
+				self.ids.push(entity_id);
 			}
-			self.updated_frame_map.insert(type_id, manager.get_frame());
+		};
+	}
+}
+
+// @TODO: Is this name good?
+// The caches are updated incrementally with the component changes
+// recorded by EntityManager since the last access.
+pub struct EntityIdAccessor {
+	cache_map: HashMap<TypeId, QueryCache>,
+	cache_keys_map: HashMap<TypeId, Vec<TypeId>>, // component type -> keys of the caches having it
+	sequence: u64 // EntityManager::get_change_sequence() at the last sync
+}
+
+impl Default for EntityIdAccessor {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl EntityIdAccessor {
+	pub fn new() -> Self {
+		EntityIdAccessor {
+			cache_map: HashMap::new(),
+			cache_keys_map: HashMap::new(),
+			sequence: 0
 		}
+	}
 
-		self.cache_map.get(&type_id)
+	pub fn borrow_ids<T: 'static + Component>(&mut self, manager: &EntityManager) -> Option<&Vec<usize>> {
+		self.borrow_ids_for_types(manager, TypeId::of::<T>(), &[
+			TypeId::of::<T>()
+		])
+	}
+
+	pub fn borrow_ids_for_pair<
+		T1: 'static + Component,
+		T2: 'static + Component
+	>(&mut self, manager: &EntityManager) -> Option<&Vec<usize>> {
+		self.borrow_ids_for_types(manager, TypeId::of::<(T1, T2)>(), &[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>()
+		])
 	}
 
 	pub fn borrow_ids_for_triple<
@@ -132,39 +256,11 @@ impl EntityIdAccessor {
 		T2: 'static + Component,
 		T3: 'static + Component
 	>(&mut self, manager: &EntityManager) -> Option<&Vec<usize>> {
-		if !manager.has_component_manager::<T1>() ||
-			!manager.has_component_manager::<T2>() ||
-			!manager.has_component_manager::<T3>() {
-			return None;
-		}
-
-		let type_id = TypeId::of::<(T1, T2, T3)>();
-		let needs_update = if !self.cache_map.contains_key(&type_id) {
-			self.cache_map.insert(type_id, Vec::new());
-			true
-		} else {
-			let updated_frame = *self.updated_frame_map.get(&type_id).unwrap();
-			manager.get_updated_frame::<T1>() != updated_frame ||
-				manager.get_updated_frame::<T2>() != updated_frame ||
-				manager.get_updated_frame::<T3>() != updated_frame
-		};
-
-		if needs_update {
-			// @TODO: Can be optimized if iterating the shortest array
-			let src = &manager.borrow_entity_ids::<T1>().unwrap();
-			let manager2 = manager.borrow_component_manager::<T2>();
-			let manager3 = manager.borrow_component_manager::<T3>();
-			let dst = self.cache_map.get_mut(&type_id).unwrap();
-			dst.clear();
-			for id in src.iter() {
-				if manager2.has(*id) && manager3.has(*id) {
-					dst.push(*id);
-				}
-			}
-			self.updated_frame_map.insert(type_id, manager.get_frame());
-		}
-
-		self.cache_map.get(&type_id)
+		self.borrow_ids_for_types(manager, TypeId::of::<(T1, T2, T3)>(), &[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>(),
+			TypeId::of::<T3>()
+		])
 	}
 
 	pub fn borrow_ids_for_quad<
@@ -173,59 +269,146 @@ impl EntityIdAccessor {
 		T3: 'static + Component,
 		T4: 'static + Component
 	>(&mut self, manager: &EntityManager) -> Option<&Vec<usize>> {
-		if !manager.has_component_manager::<T1>() ||
-			!manager.has_component_manager::<T2>() ||
-			!manager.has_component_manager::<T3>() ||
-			!manager.has_component_manager::<T4>() {
+		self.borrow_ids_for_types(manager, TypeId::of::<(T1, T2, T3, T4)>(), &[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>(),
+			TypeId::of::<T3>(),
+			TypeId::of::<T4>()
+		])
+	}
+
+	// cache_key is the TypeId of the tuple of the types
+	fn borrow_ids_for_types(&mut self, manager: &EntityManager, cache_key: TypeId, type_ids: &[TypeId]) -> Option<&Vec<usize>> {
+		if type_ids.iter().any(|type_id| !manager.is_registered_type_id(*type_id)) {
 			return None;
 		}
 
-		let type_id = TypeId::of::<(T1, T2, T3, T4)>();
-		let needs_update = if !self.cache_map.contains_key(&type_id) {
-			self.cache_map.insert(type_id, Vec::new());
-			true
-		} else {
-			let updated_frame = *self.updated_frame_map.get(&type_id).unwrap();
-			manager.get_updated_frame::<T1>() != updated_frame ||
-				manager.get_updated_frame::<T2>() != updated_frame ||
-				manager.get_updated_frame::<T3>() != updated_frame ||
-				manager.get_updated_frame::<T4>() != updated_frame
-		};
+		self.sync(manager);
+		let cache_keys_map = &mut self.cache_keys_map;
+		let cache = self.cache_map.entry(cache_key).or_insert_with(|| {
+			for type_id in type_ids.iter() {
+				cache_keys_map.entry(*type_id).or_default().push(cache_key);
+			}
+			QueryCache::new(manager, type_ids)
+		});
+		Some(&cache.ids)
+	}
 
-		if needs_update {
-			// @TODO: Can be optimized if iterating the shortest array
-			let src = &manager.borrow_entity_ids::<T1>().unwrap();
-			let manager2 = manager.borrow_component_manager::<T2>();
-			let manager3 = manager.borrow_component_manager::<T3>();
-			let manager4 = manager.borrow_component_manager::<T4>();
-			let dst = self.cache_map.get_mut(&type_id).unwrap();
-			dst.clear();
-			for id in src.iter() {
-				if manager2.has(*id) && manager3.has(*id) && manager4.has(*id) {
-					dst.push(*id);
+	// Applies the changes since the last sync to the caches. Rebuilds them
+	// if the manager has dropped some of the changes.
+	pub(crate) fn sync(&mut self, manager: &EntityManager) {
+		match manager.borrow_changes_since(self.sequence) {
+			Some(changes) => {
+				for (entity_id, type_id) in changes.iter() {
+					match type_id {
+						Some(type_id) => if let Some(cache_keys) = self.cache_keys_map.get(type_id) {
+							for cache_key in cache_keys.iter() {
+								self.cache_map.get_mut(cache_key).unwrap().update(manager, *entity_id);
+							}
+						},
+						// An entity removal
+						None => for cache in self.cache_map.values_mut() {
+							cache.update(manager, *entity_id);
+						}
+					};
+				}
+			},
+			None => {
+				for cache in self.cache_map.values_mut() {
+					cache.rebuild(manager);
 				}
 			}
-			self.updated_frame_map.insert(type_id, manager.get_frame());
-		}
+		};
+		self.sequence = manager.get_change_sequence();
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StorageMode {
+	// A ComponentManager per component type. Adding and removing components
+	// is cheap, multi-component queries look up each entity in each manager.
+	PerComponent,
+	// Entities having the same component types share a table.
+	// See ArchetypeStorage.
+	Archetype
+}
+
+// Returned by EntityManager::move_entity_to() and World::move_entity_to().
+// Nothing is moved on error.
+#[derive(Debug)]
+pub enum MoveEntityError {
+	UnknownEntity(usize),
+	// Components are moved type-erased through the type registry
+	UnregisteredComponent(&'static str),
+	// The destination has another component type with the same name
+	NameConflict(String),
+	// The destination has a dynamic component of the same name with
+	// a different size or alignment
+	LayoutMismatch(String)
+}
 
-		self.cache_map.get(&type_id)
+impl fmt::Display for MoveEntityError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MoveEntityError::UnknownEntity(entity_id) => write!(f, "Unknown entity {}", entity_id),
+			MoveEntityError::UnregisteredComponent(type_name) => write!(f, "Component {} is not in the type registry", type_name),
+			MoveEntityError::NameConflict(name) => write!(f, "Another component is named {} in the destination", name),
+			MoveEntityError::LayoutMismatch(name) => write!(f, "Dynamic component {} has a different layout in the destination", name)
+		}
 	}
 }
 
+impl Error for MoveEntityError {}
+
 pub struct EntityManager {
 	entities: Entities,
-	manager_map: HashMap<TypeId, Box<dyn ComponentManagerTrait>>,
+	manager_map: HashMap<TypeId, Box<dyn ComponentManagerTrait>>, // Only in PerComponent mode
+	archetype_storage: Option<ArchetypeStorage>, // Only in Archetype mode
 	frame: u64, // Rename
-	updated_frame_map: HashMap<TypeId, u64> // Rename
+	updated_frame_map: HashMap<TypeId, u64>, // Rename
+	component_changes: ComponentChanges,
+	mutation_map: HashMap<TypeId, Mutations>, // Only tracked components
+	clone_fn_map: HashMap<TypeId, CloneManagerFn>, // Only cloneable components
+	dynamic_storages: Vec<DynamicStorage>, // Indexed by ComponentId::Dynamic
+	dynamic_name_map: HashMap<String, usize>, // name -> index in dynamic_storages
+	type_registry: TypeRegistry,
+	commands: Commands,
+	hook_registry: HookRegistry,
+	resource_manager: ResourceManager,
+	event_clear_fns: Vec<fn(&mut EntityManager)>
+}
+
+impl Default for EntityManager {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl EntityManager {
 	pub fn new() -> Self {
+		Self::with_storage_mode(StorageMode::PerComponent)
+	}
+
+	pub fn with_storage_mode(storage_mode: StorageMode) -> Self {
 		EntityManager {
 			entities: Entities::new(),
 			manager_map: HashMap::new(),
+			archetype_storage: match storage_mode {
+				StorageMode::PerComponent => None,
+				StorageMode::Archetype => Some(ArchetypeStorage::new())
+			},
 			frame: 0,
-			updated_frame_map: HashMap::new()
+			updated_frame_map: HashMap::new(),
+			component_changes: ComponentChanges::new(),
+			mutation_map: HashMap::new(),
+			clone_fn_map: HashMap::new(),
+			dynamic_storages: Vec::new(),
+			dynamic_name_map: HashMap::new(),
+			type_registry: TypeRegistry::new(),
+			commands: Commands::new(),
+			hook_registry: HookRegistry::new(),
+			resource_manager: ResourceManager::new(),
+			event_clear_fns: Vec::new()
 		}
 	}
 
@@ -233,63 +416,397 @@ impl EntityManager {
 		self.frame += 1;
 	}
 
-	fn get_frame(&self) -> u64 {
+	// Incremented after each system in World::update()
+	pub fn get_frame(&self) -> u64 {
 		self.frame
 	}
 
-	fn get_updated_frame<T: 'static + Component>(&self) -> u64 {
-		*self.updated_frame_map.get(&TypeId::of::<T>()).unwrap()
+	// The frame the components of the type were last added or removed at
+	pub(crate) fn get_updated_frame(&self, type_id: TypeId) -> u64 {
+		*self.updated_frame_map.get(&type_id).unwrap()
+	}
+
+	// Sequence number of the next component change
+	pub(crate) fn get_change_sequence(&self) -> u64 {
+		self.component_changes.get_end()
+	}
+
+	// None if some of the changes since the sequence number are dropped
+	pub(crate) fn borrow_changes_since(&self, sequence: u64) -> Option<&[(usize, Option<TypeId>)]> {
+		self.component_changes.borrow_since(sequence)
+	}
+
+	// Called when the readers have seen the changes before the sequence
+	// number. The readers which run once per frame, e.g. systems, need the
+	// changes since their last run.
+	pub(crate) fn clear_changes_before(&mut self, sequence: u64) {
+		self.component_changes.clear_before(sequence);
+	}
+
+	// Starts recording the entities whose T components are mutably borrowed,
+	// for the readers which need to know the mutated components, e.g.
+	// SpatialIndexSystem. Does nothing if already tracked.
+	pub fn track_mutations<T: 'static + Component>(&mut self) -> &mut Self {
+		self.mutation_map.entry(TypeId::of::<T>()).or_default();
+		self
+	}
+
+	// The entities whose T components may have been mutated since the last
+	// call, in no particular order. None if not tracked or if all of them
+	// may have been mutated.
+	pub(crate) fn take_mutated_ids<T: 'static + Component>(&mut self) -> Option<Vec<usize>> {
+		let mutations = std::mem::take(self.mutation_map.get_mut(&TypeId::of::<T>())?);
+		match mutations.all {
+			true => None,
+			false => Some(mutations.entity_ids)
+		}
+	}
+
+	// None entity_id is all the entities
+	fn mark_mutated(&mut self, type_id: TypeId, entity_id: Option<usize>) {
+		if let Some(mutations) = self.mutation_map.get_mut(&type_id) {
+			mutations.push(entity_id);
+		}
+	}
+
+	// See World::dump()
+	pub fn dump(&self) -> String {
+		dump(self)
+	}
+
+	pub fn get_storage_mode(&self) -> StorageMode {
+		match self.archetype_storage {
+			Some(_) => StorageMode::Archetype,
+			None => StorageMode::PerComponent
+		}
 	}
 
 	pub fn register<T: 'static + Component>(&mut self) -> &mut Self {
 		// @TODO: Error handling if already registered?
-		if ! self.has_component_manager::<T>() {
+		if ! self.is_registered::<T>() {
 			let type_id = TypeId::of::<T>();
-			self.manager_map.insert(type_id, Box::new(ComponentManager::<T>::new()));
+			match self.archetype_storage.as_mut() {
+				Some(storage) => storage.register::<T>(),
+				None => {
+					self.manager_map.insert(type_id, Box::new(T::Storage::default()));
+				}
+			};
 			self.updated_frame_map.insert(type_id, self.get_frame());
 		}
 		self
 	}
 
+	// Cloneable components are captured by world snapshots
+	pub fn register_cloneable<T: 'static + Component + Clone>(&mut self) -> &mut Self {
+		self.register::<T>();
+		match self.archetype_storage.as_mut() {
+			Some(storage) => storage.register_cloneable::<T>(),
+			None => {
+				self.clone_fn_map.insert(TypeId::of::<T>(), clone_manager::<T>);
+			}
+		};
+		self
+	}
+
+	pub fn is_registered<T: 'static + Component>(&self) -> bool {
+		self.is_registered_type_id(TypeId::of::<T>())
+	}
+
+	pub(crate) fn is_registered_type_id(&self, type_id: TypeId) -> bool {
+		match self.archetype_storage.as_ref() {
+			Some(storage) => storage.is_registered(type_id),
+			None => self.manager_map.contains_key(&type_id)
+		}
+	}
+
 	pub fn create_entity(&mut self) -> usize {
 		self.entities.create()
 	}
 
+	pub fn has_entity(&self, entity_id: usize) -> bool {
+		self.entities.has(entity_id)
+	}
+
+	// Live entity ids in ascending order
+	pub fn get_entity_ids(&self) -> Vec<usize> {
+		self.entities.get_ids()
+	}
+
+	// Removed entity ids to be reused, in the reuse order
+	pub fn borrow_available_entity_ids(&self) -> &Vec<usize> {
+		self.entities.borrow_availables()
+	}
+
 	pub fn remove_entity(&mut self, entity_id: usize) {
+		self.detach_from_hierarchy(entity_id);
+		if !self.hook_registry.is_empty() {
+			for type_id in self.get_component_type_ids(entity_id) {
+				self.run_hooks(type_id, HookKind::OnReplace, entity_id);
+				self.run_hooks(type_id, HookKind::OnRemove, entity_id);
+			}
+		}
 		let frame = self.get_frame();
-		for (_, manager) in self.manager_map.iter_mut() {
-			if manager.has(entity_id) {
-				manager.remove(entity_id);
-				// @TODO: Write comment for +1
-				self.updated_frame_map.insert(manager.get_type_id(), frame + 1);
+		match self.archetype_storage.as_mut() {
+			Some(storage) => {
+				for type_id in storage.remove_entity(entity_id) {
+					self.updated_frame_map.insert(type_id, frame + 1);
+				}
+			},
+			None => {
+				for (_, manager) in self.manager_map.iter_mut() {
+					if manager.has(entity_id) {
+						manager.remove(entity_id);
+						// +1 so that the removal is seen as after the systems run in this frame
+						self.updated_frame_map.insert(manager.get_type_id(), frame + 1);
+					}
+				}
 			}
+		};
+		for storage in self.dynamic_storages.iter_mut() {
+			storage.remove(entity_id);
+		}
+		if self.entities.has(entity_id) {
+			self.component_changes.push(entity_id, None);
 		}
 		self.entities.remove(entity_id);
 	}
 
 	pub fn add_component_to_entity<T: 'static + Component>(&mut self, entity_id: usize, component: T) -> &mut Self {
-		if ! self.has_component_manager::<T>() {
+		if ! self.is_registered::<T>() {
 			// @TODO: Better error handling
 			println!("Unknown component");
 			return self;
 		}
-		self.borrow_component_manager_mut::<T>()
-			.add(entity_id, component);
+		// The storages keep the current component if the entity has one
+		let added = !self.has_component::<T>(entity_id);
+		match self.archetype_storage.as_mut() {
+			Some(storage) => storage.add(entity_id, component),
+			None => self.borrow_component_manager_mut::<T>().add(entity_id, component)
+		};
 		self.updated_frame_map.insert(TypeId::of::<T>(), self.get_frame());
+		if added {
+			self.component_changes.push(entity_id, Some(TypeId::of::<T>()));
+			self.run_hooks(TypeId::of::<T>(), HookKind::OnAdd, entity_id);
+			self.run_hooks(TypeId::of::<T>(), HookKind::OnInsert, entity_id);
+		}
 
 		self
 	}
 
-	fn borrow_entity_ids<T: 'static + Component>(&self) -> Option<&Vec<usize>> {
-		if ! self.has_component_manager::<T>() {
+	// Adds the component, or replaces the value if the entity already has it
+	pub fn insert_component_to_entity<T: 'static + Component>(&mut self, entity_id: usize, component: T) -> &mut Self {
+		if !self.has_component::<T>(entity_id) {
+			return self.add_component_to_entity(entity_id, component);
+		}
+		self.run_hooks(TypeId::of::<T>(), HookKind::OnReplace, entity_id);
+		// The hooks may have removed it
+		if let Some(old_component) = self.borrow_component_mut::<T>(entity_id) {
+			*old_component = component;
+			self.updated_frame_map.insert(TypeId::of::<T>(), self.get_frame());
+			self.run_hooks(TypeId::of::<T>(), HookKind::OnInsert, entity_id);
+		}
+		self
+	}
+
+	pub fn add_bundle_to_entity<T: Bundle>(&mut self, entity_id: usize, bundle: T) -> &mut Self {
+		bundle.add_to_entity(self, entity_id);
+		self
+	}
+
+	pub fn remove_component_from_entity<T: 'static + Component>(&mut self, entity_id: usize) -> &mut Self {
+		if ! self.is_registered::<T>() {
 			// @TODO: Better error handling
 			println!("Unknown component");
+			return self;
+		}
+		if self.has_component::<T>(entity_id) {
+			self.run_hooks(TypeId::of::<T>(), HookKind::OnReplace, entity_id);
+			self.run_hooks(TypeId::of::<T>(), HookKind::OnRemove, entity_id);
+			match self.archetype_storage.as_mut() {
+				Some(storage) => storage.remove(entity_id, TypeId::of::<T>()),
+				None => ComponentStorage::remove(self.borrow_component_manager_mut::<T>(), entity_id)
+			};
+			// See remove_entity() for +1
+			self.updated_frame_map.insert(TypeId::of::<T>(), self.get_frame() + 1);
+			self.component_changes.push(entity_id, Some(TypeId::of::<T>()));
+		}
+		self
+	}
+
+	// Removes the component and returns it instead of dropping it
+	pub fn take_component<T: 'static + Component>(&mut self, entity_id: usize) -> Option<T> {
+		if !self.has_component::<T>(entity_id) {
 			return None;
 		}
-		Some(self.borrow_component_manager::<T>().borrow_entity_ids())
+		self.run_hooks(TypeId::of::<T>(), HookKind::OnReplace, entity_id);
+		self.run_hooks(TypeId::of::<T>(), HookKind::OnRemove, entity_id);
+		let component = match self.archetype_storage.as_mut() {
+			Some(storage) => storage.take::<T>(entity_id),
+			None => ComponentStorage::take(self.borrow_component_manager_mut::<T>(), entity_id)
+		};
+		// See remove_entity() for +1
+		self.updated_frame_map.insert(TypeId::of::<T>(), self.get_frame() + 1);
+		self.component_changes.push(entity_id, Some(TypeId::of::<T>()));
+		component
+	}
+
+	// Moves the entity and its components in the type registry and its
+	// dynamic components to another manager, and returns the new entity id.
+	// Component types unknown to dst are registered with their type infos,
+	// but not as cloneable.
+	// Hierarchy links are not moved, the entity and its children are
+	// detached like on removal.
+	// Fails without moving anything if a component can't be moved.
+	pub fn move_entity_to(&mut self, dst: &mut EntityManager, entity_id: usize) -> Result<usize, MoveEntityError> {
+		self.validate_move(dst, entity_id)?;
+		self.detach_from_hierarchy(entity_id);
+		if self.has_component::<Children>(entity_id) {
+			self.remove_component_from_entity::<Children>(entity_id);
+		}
+
+		let dst_entity_id = dst.create_entity();
+		let mut move_fns = Vec::new();
+		for type_info in self.type_registry.borrow_type_infos().iter() {
+			if type_info.has_component(self, entity_id) {
+				dst.type_registry.register_type_info(type_info);
+				move_fns.push(type_info.get_move_fn());
+			}
+		}
+		for move_component in move_fns {
+			move_component(self, entity_id, dst, dst_entity_id);
+		}
+
+		for storage in self.dynamic_storages.iter_mut() {
+			let bytes = match storage.take(entity_id) {
+				Some(bytes) => bytes,
+				None => continue
+			};
+			if let Some(component_id) = dst.register_dynamic_component(storage.borrow_layout().clone()) {
+				dst.add_dynamic_component_to_entity(dst_entity_id, component_id, &bytes);
+			}
+		}
+
+		self.remove_entity(entity_id);
+		Ok(dst_entity_id)
+	}
+
+	fn validate_move(&self, dst: &EntityManager, entity_id: usize) -> Result<(), MoveEntityError> {
+		if !self.has_entity(entity_id) {
+			return Err(MoveEntityError::UnknownEntity(entity_id));
+		}
+
+		for (type_id, type_name) in self.get_registered_types() {
+			// Detached instead of moved
+			if type_id == TypeId::of::<Parent>() || type_id == TypeId::of::<Children>() ||
+				!self.has_component_type_id(entity_id, type_id) {
+				continue;
+			}
+			let name = match self.type_registry.borrow_type_info_by_type_id(type_id) {
+				Some(type_info) => type_info.get_name(),
+				None => return Err(MoveEntityError::UnregisteredComponent(type_name))
+			};
+			if let Some(type_info) = dst.type_registry.borrow_type_info(name) {
+				if type_info.get_type_id() != type_id {
+					return Err(MoveEntityError::NameConflict(name.to_string()));
+				}
+			}
+		}
+
+		for storage in self.dynamic_storages.iter().filter(|storage| storage.has(entity_id)) {
+			let layout = storage.borrow_layout();
+			let dst_layout = match dst.dynamic_name_map.get(layout.get_name()) {
+				Some(index) => dst.dynamic_storages[*index].borrow_layout(),
+				None => continue
+			};
+			if dst_layout.get_size() != layout.get_size() || dst_layout.get_align() != layout.get_align() {
+				return Err(MoveEntityError::LayoutMismatch(layout.get_name().to_string()));
+			}
+		}
+		Ok(())
+	}
+
+	// Hooks run synchronously when a component of the type is added,
+	// replaced or removed. See hooks.rs.
+	pub fn add_hook<T: 'static + Component, F: 'static + Fn(&mut EntityManager, usize)>(&mut self, kind: HookKind, hook: F) -> &mut Self {
+		self.hook_registry.add(TypeId::of::<T>(), kind, Rc::new(hook));
+		self
+	}
+
+	pub(crate) fn has_hooks(&self) -> bool {
+		!self.hook_registry.is_empty()
+	}
+
+	fn run_hooks(&mut self, type_id: TypeId, kind: HookKind, entity_id: usize) {
+		if !self.hook_registry.has(type_id, kind) {
+			return;
+		}
+		for hook in self.hook_registry.get(type_id, kind) {
+			hook(self, entity_id);
+		}
+	}
+
+	fn get_component_type_ids(&self, entity_id: usize) -> Vec<TypeId> {
+		match self.archetype_storage.as_ref() {
+			Some(storage) => match storage.get_archetype_index(entity_id) {
+				Some(index) => storage.borrow_archetypes()[index].borrow_type_ids().clone(),
+				None => Vec::new()
+			},
+			None => self.manager_map.iter()
+				.filter(|(_, manager)| manager.has(entity_id))
+				.map(|(type_id, _)| *type_id)
+				.collect()
+		}
+	}
+
+	pub fn has_component<T: 'static + Component>(&self, entity_id: usize) -> bool {
+		self.has_component_type_id(entity_id, TypeId::of::<T>())
+	}
+
+	// Type id and std::any::type_name() of the registered components,
+	// including the ones not in the type registry
+	pub(crate) fn get_registered_types(&self) -> Vec<(TypeId, &'static str)> {
+		match self.archetype_storage.as_ref() {
+			Some(storage) => storage.get_registered_types(),
+			None => self.manager_map.iter()
+				.map(|(type_id, manager)| (*type_id, manager.get_type_name()))
+				.collect()
+		}
+	}
+
+	pub(crate) fn has_component_type_id(&self, entity_id: usize, type_id: TypeId) -> bool {
+		match self.archetype_storage.as_ref() {
+			Some(storage) => storage.has(entity_id, type_id),
+			None => match self.manager_map.get(&type_id) {
+				Some(manager) => manager.has(entity_id),
+				None => false
+			}
+		}
+	}
+
+	// Appends the ids of the entities having all the types to dst
+	pub(crate) fn collect_entity_ids(&self, type_ids: &[TypeId], dst: &mut Vec<usize>) {
+		if let Some(storage) = self.archetype_storage.as_ref() {
+			storage.collect_entity_ids(type_ids, dst);
+			return;
+		}
+
+		// Iterates the smallest set
+		let smallest = type_ids.iter()
+			.min_by_key(|type_id| self.manager_map.get(type_id).unwrap().get_component_count())
+			.unwrap();
+		let mut src = Vec::new();
+		self.manager_map.get(smallest).unwrap().collect_entity_ids(&mut src);
+		for id in src {
+			if type_ids.iter().all(|type_id| type_id == smallest || self.manager_map.get(type_id).unwrap().has(id)) {
+				dst.push(id);
+			}
+		}
 	}
 
 	pub fn borrow_component<T: 'static + Component>(&self, entity_id: usize) -> Option<&T> {
+		if let Some(storage) = self.archetype_storage.as_ref() {
+			return storage.borrow_component::<T>(entity_id);
+		}
 		match self.has_component_manager::<T>() {
 			true => self.borrow_component_manager::<T>()
 				.borrow_component(entity_id),
@@ -298,29 +815,67 @@ impl EntityManager {
 	}
 
 	pub fn borrow_component_mut<T: 'static + Component>(&mut self, entity_id: usize) -> Option<&mut T> {
-		match self.has_component_manager::<T>() {
-			true => self.borrow_component_manager_mut::<T>()
-				.borrow_component_mut(entity_id),
-			false => None
+		self.mark_mutated(TypeId::of::<T>(), Some(entity_id));
+		match self.archetype_storage.as_mut() {
+			Some(storage) => storage.borrow_component_mut::<T>(entity_id),
+			None => match self.manager_map.get_mut(&TypeId::of::<T>()) {
+				Some(manager) => cast_manager_mut::<T>(manager.as_mut())
+					.borrow_component_mut(entity_id),
+				None => None
+			}
 		}
 	}
 
+	// Pointers to the T components of K distinct entities, made without
+	// borrowing the other components, so that the pointers of the other
+	// entities and types stay valid. See CombinationsMut::fetch_next().
+	// Panics if the entity ids aren't distinct.
+	pub(crate) fn get_component_ptrs<T: 'static + Component, const K: usize>(&mut self, entity_ids: [usize; K]) -> Option<[*mut T; K]> {
+		for entity_id in entity_ids.iter() {
+			self.mark_mutated(TypeId::of::<T>(), Some(*entity_id));
+		}
+		if let Some(storage) = self.archetype_storage.as_mut() {
+			assert_distinct_entity_ids(&entity_ids);
+			let mut ptrs = [std::ptr::null_mut(); K];
+			for (ptr, entity_id) in ptrs.iter_mut().zip(entity_ids.iter()) {
+				*ptr = storage.get_component_ptr::<T>(*entity_id)?;
+			}
+			return Some(ptrs);
+		}
+		let manager = self.manager_map.get_mut(&TypeId::of::<T>())?;
+		let components = cast_manager_mut::<T>(manager.as_mut()).borrow_components_disjoint_mut(entity_ids)?;
+		Some(components.map(|component| component as *mut T))
+	}
+
+	// Unordered combinations of K distinct entities having all the
+	// components of Q, e.g. iter_combinations::<(Position, Circle), 2>()
+	// for collision checks. See combinations.rs.
+	pub fn iter_combinations<Q: QueryData, const K: usize>(&self) -> Combinations<'_, Q, K> {
+		Combinations::new(self)
+	}
+
+	// Mutable version of iter_combinations(). Use fetch_next() in a while
+	// let loop because the components can't outlive each step.
+	pub fn iter_combinations_mut<Q: QueryData, const K: usize>(&mut self) -> CombinationsMut<'_, Q, K> {
+		CombinationsMut::new(self)
+	}
+
+	// borrow_components*() are available only in PerComponent mode and for
+	// the storages keeping the components in a Vec, e.g. DenseStorage. Use
+	// for_each*() which works in all the cases instead.
 	pub fn borrow_components<T: 'static + Component>(&self) -> Option<&Vec<T>> {
 		match self.has_component_manager::<T>() {
-			true => Some(
-				self.borrow_component_manager::<T>()
-					.borrow_components()
-			),
+			true => self.borrow_component_manager::<T>()
+				.borrow_dense_components(),
 			false => None
 		}
 	}
 
 	pub fn borrow_components_mut<T: 'static + Component>(&mut self) -> Option<&mut Vec<T>> {
+		self.mark_mutated(TypeId::of::<T>(), None);
 		match self.has_component_manager::<T>() {
-			true => Some(
-				self.borrow_component_manager_mut::<T>()
-					.borrow_components_mut()
-			),
+			true => self.borrow_component_manager_mut::<T>()
+				.borrow_dense_components_mut(),
 			false => None
 		}
 	}
@@ -329,6 +884,13 @@ impl EntityManager {
 		T1: 'static + Component,
 		T2: 'static + Component,
 	>(&mut self) -> Option<(&mut Vec<T1>, &mut Vec<T2>)> {
+		assert_distinct_type_ids(&[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>()
+		]);
+		self.mark_mutated(TypeId::of::<T1>(), None);
+		self.mark_mutated(TypeId::of::<T2>(), None);
+
 		if ! self.has_component_manager::<T1>() ||
 			! self.has_component_manager::<T2>() {
 			return None;
@@ -337,12 +899,12 @@ impl EntityManager {
 		let type_id1 = TypeId::of::<T1>();
 		let type_id2 = TypeId::of::<T2>();
 
-		let manager1 = cast_manager_mut_unsafe(self.manager_map.get(&type_id1).unwrap());
-		let manager2 = cast_manager_mut_unsafe(self.manager_map.get(&type_id2).unwrap());
+		let manager1 = cast_manager_mut_unsafe::<T1>(self.manager_map.get_mut(&type_id1).unwrap().as_mut());
+		let manager2 = cast_manager_mut_unsafe::<T2>(self.manager_map.get_mut(&type_id2).unwrap().as_mut());
 
 		Some((
-			manager1.borrow_components_mut(),
-			manager2.borrow_components_mut(),
+			manager1.borrow_dense_components_mut()?,
+			manager2.borrow_dense_components_mut()?,
 		))
 	}
 
@@ -351,6 +913,15 @@ impl EntityManager {
 		T2: 'static + Component,
 		T3: 'static + Component,
 	>(&mut self) -> Option<(&mut Vec<T1>, &mut Vec<T2>, &mut Vec<T3>)> {
+		assert_distinct_type_ids(&[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>(),
+			TypeId::of::<T3>()
+		]);
+		self.mark_mutated(TypeId::of::<T1>(), None);
+		self.mark_mutated(TypeId::of::<T2>(), None);
+		self.mark_mutated(TypeId::of::<T3>(), None);
+
 		if ! self.has_component_manager::<T1>() ||
 			! self.has_component_manager::<T2>() ||
 			! self.has_component_manager::<T3>() {
@@ -361,23 +932,35 @@ impl EntityManager {
 		let type_id2 = TypeId::of::<T2>();
 		let type_id3 = TypeId::of::<T3>();
 
-		let manager1 = cast_manager_mut_unsafe(self.manager_map.get(&type_id1).unwrap());
-		let manager2 = cast_manager_mut_unsafe(self.manager_map.get(&type_id2).unwrap());
-		let manager3 = cast_manager_mut_unsafe(self.manager_map.get(&type_id3).unwrap());
+		let manager1 = cast_manager_mut_unsafe::<T1>(self.manager_map.get_mut(&type_id1).unwrap().as_mut());
+		let manager2 = cast_manager_mut_unsafe::<T2>(self.manager_map.get_mut(&type_id2).unwrap().as_mut());
+		let manager3 = cast_manager_mut_unsafe::<T3>(self.manager_map.get_mut(&type_id3).unwrap().as_mut());
 
 		Some((
-			manager1.borrow_components_mut(),
-			manager2.borrow_components_mut(),
-			manager3.borrow_components_mut(),
+			manager1.borrow_dense_components_mut()?,
+			manager2.borrow_dense_components_mut()?,
+			manager3.borrow_dense_components_mut()?,
 		))
 	}
 
+	#[allow(clippy::type_complexity)]
 	pub fn borrow_components_quad_mut<
 		T1: 'static + Component,
 		T2: 'static + Component,
 		T3: 'static + Component,
 		T4: 'static + Component,
 	>(&mut self) -> Option<(&mut Vec<T1>, &mut Vec<T2>, &mut Vec<T3>, &mut Vec<T4>)> {
+		assert_distinct_type_ids(&[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>(),
+			TypeId::of::<T3>(),
+			TypeId::of::<T4>()
+		]);
+		self.mark_mutated(TypeId::of::<T1>(), None);
+		self.mark_mutated(TypeId::of::<T2>(), None);
+		self.mark_mutated(TypeId::of::<T3>(), None);
+		self.mark_mutated(TypeId::of::<T4>(), None);
+
 		if ! self.has_component_manager::<T1>() ||
 			! self.has_component_manager::<T2>() ||
 			! self.has_component_manager::<T3>() ||
@@ -390,16 +973,16 @@ impl EntityManager {
 		let type_id3 = TypeId::of::<T3>();
 		let type_id4 = TypeId::of::<T4>();
 
-		let manager1 = cast_manager_mut_unsafe(self.manager_map.get(&type_id1).unwrap());
-		let manager2 = cast_manager_mut_unsafe(self.manager_map.get(&type_id2).unwrap());
-		let manager3 = cast_manager_mut_unsafe(self.manager_map.get(&type_id3).unwrap());
-		let manager4 = cast_manager_mut_unsafe(self.manager_map.get(&type_id4).unwrap());
+		let manager1 = cast_manager_mut_unsafe::<T1>(self.manager_map.get_mut(&type_id1).unwrap().as_mut());
+		let manager2 = cast_manager_mut_unsafe::<T2>(self.manager_map.get_mut(&type_id2).unwrap().as_mut());
+		let manager3 = cast_manager_mut_unsafe::<T3>(self.manager_map.get_mut(&type_id3).unwrap().as_mut());
+		let manager4 = cast_manager_mut_unsafe::<T4>(self.manager_map.get_mut(&type_id4).unwrap().as_mut());
 
 		Some((
-			manager1.borrow_components_mut(),
-			manager2.borrow_components_mut(),
-			manager3.borrow_components_mut(),
-			manager4.borrow_components_mut(),
+			manager1.borrow_dense_components_mut()?,
+			manager2.borrow_dense_components_mut()?,
+			manager3.borrow_dense_components_mut()?,
+			manager4.borrow_dense_components_mut()?,
 		))
 	}
 
@@ -407,6 +990,25 @@ impl EntityManager {
 		T1: 'static + Component,
 		T2: 'static + Component
 	>(&mut self, entity_id: usize) -> Option<(&mut T1, &mut T2)> {
+		assert_distinct_type_ids(&[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>()
+		]);
+		self.mark_mutated(TypeId::of::<T1>(), Some(entity_id));
+		self.mark_mutated(TypeId::of::<T2>(), Some(entity_id));
+
+		if let Some(storage) = self.archetype_storage.as_mut() {
+			if !storage.has(entity_id, TypeId::of::<T1>()) ||
+				!storage.has(entity_id, TypeId::of::<T2>()) {
+				return None;
+			}
+			// The component types are distinct, so the components don't overlap
+			return Some(unsafe { (
+				&mut *storage.get_component_ptr::<T1>(entity_id).unwrap(),
+				&mut *storage.get_component_ptr::<T2>(entity_id).unwrap()
+			) });
+		}
+
 		if ! self.has_component_manager::<T1>() ||
 			! self.has_component_manager::<T2>() {
 			return None;
@@ -415,16 +1017,12 @@ impl EntityManager {
 		let type_id1 = TypeId::of::<T1>();
 		let type_id2 = TypeId::of::<T2>();
 
-		let manager1 = cast_manager_mut_unsafe(self.manager_map.get(&type_id1).unwrap());
-		let manager2 = cast_manager_mut_unsafe(self.manager_map.get(&type_id2).unwrap());
-
-		if !manager1.has(entity_id) || !manager2.has(entity_id) {
-			return None;
-		}
+		let manager1 = cast_manager_mut_unsafe::<T1>(self.manager_map.get_mut(&type_id1).unwrap().as_mut());
+		let manager2 = cast_manager_mut_unsafe::<T2>(self.manager_map.get_mut(&type_id2).unwrap().as_mut());
 
 		Some((
-			manager1.borrow_component_mut(entity_id).unwrap(),
-			manager2.borrow_component_mut(entity_id).unwrap()
+			manager1.borrow_component_mut(entity_id)?,
+			manager2.borrow_component_mut(entity_id)?
 		))
 	}
 
@@ -433,6 +1031,29 @@ impl EntityManager {
 		T2: 'static + Component,
 		T3: 'static + Component
 	>(&mut self, entity_id: usize) -> Option<(&mut T1, &mut T2, &mut T3)> {
+		assert_distinct_type_ids(&[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>(),
+			TypeId::of::<T3>()
+		]);
+		self.mark_mutated(TypeId::of::<T1>(), Some(entity_id));
+		self.mark_mutated(TypeId::of::<T2>(), Some(entity_id));
+		self.mark_mutated(TypeId::of::<T3>(), Some(entity_id));
+
+		if let Some(storage) = self.archetype_storage.as_mut() {
+			if !storage.has(entity_id, TypeId::of::<T1>()) ||
+				!storage.has(entity_id, TypeId::of::<T2>()) ||
+				!storage.has(entity_id, TypeId::of::<T3>()) {
+				return None;
+			}
+			// The component types are distinct, so the components don't overlap
+			return Some(unsafe { (
+				&mut *storage.get_component_ptr::<T1>(entity_id).unwrap(),
+				&mut *storage.get_component_ptr::<T2>(entity_id).unwrap(),
+				&mut *storage.get_component_ptr::<T3>(entity_id).unwrap()
+			) });
+		}
+
 		if ! self.has_component_manager::<T1>() ||
 			! self.has_component_manager::<T2>() ||
 			! self.has_component_manager::<T3>() {
@@ -443,18 +1064,14 @@ impl EntityManager {
 		let type_id2 = TypeId::of::<T2>();
 		let type_id3 = TypeId::of::<T3>();
 
-		let manager1 = cast_manager_mut_unsafe(self.manager_map.get(&type_id1).unwrap());
-		let manager2 = cast_manager_mut_unsafe(self.manager_map.get(&type_id2).unwrap());
-		let manager3 = cast_manager_mut_unsafe(self.manager_map.get(&type_id3).unwrap());
-
-		if !manager1.has(entity_id) || !manager2.has(entity_id) || !manager3.has(entity_id) {
-			return None;
-		}
+		let manager1 = cast_manager_mut_unsafe::<T1>(self.manager_map.get_mut(&type_id1).unwrap().as_mut());
+		let manager2 = cast_manager_mut_unsafe::<T2>(self.manager_map.get_mut(&type_id2).unwrap().as_mut());
+		let manager3 = cast_manager_mut_unsafe::<T3>(self.manager_map.get_mut(&type_id3).unwrap().as_mut());
 
 		Some((
-			manager1.borrow_component_mut(entity_id).unwrap(),
-			manager2.borrow_component_mut(entity_id).unwrap(),
-			manager3.borrow_component_mut(entity_id).unwrap()
+			manager1.borrow_component_mut(entity_id)?,
+			manager2.borrow_component_mut(entity_id)?,
+			manager3.borrow_component_mut(entity_id)?
 		))
 	}
 
@@ -464,6 +1081,33 @@ impl EntityManager {
 		T3: 'static + Component,
 		T4: 'static + Component
 	>(&mut self, entity_id: usize) -> Option<(&mut T1, &mut T2, &mut T3, &mut T4)> {
+		assert_distinct_type_ids(&[
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>(),
+			TypeId::of::<T3>(),
+			TypeId::of::<T4>()
+		]);
+		self.mark_mutated(TypeId::of::<T1>(), Some(entity_id));
+		self.mark_mutated(TypeId::of::<T2>(), Some(entity_id));
+		self.mark_mutated(TypeId::of::<T3>(), Some(entity_id));
+		self.mark_mutated(TypeId::of::<T4>(), Some(entity_id));
+
+		if let Some(storage) = self.archetype_storage.as_mut() {
+			if !storage.has(entity_id, TypeId::of::<T1>()) ||
+				!storage.has(entity_id, TypeId::of::<T2>()) ||
+				!storage.has(entity_id, TypeId::of::<T3>()) ||
+				!storage.has(entity_id, TypeId::of::<T4>()) {
+				return None;
+			}
+			// The component types are distinct, so the components don't overlap
+			return Some(unsafe { (
+				&mut *storage.get_component_ptr::<T1>(entity_id).unwrap(),
+				&mut *storage.get_component_ptr::<T2>(entity_id).unwrap(),
+				&mut *storage.get_component_ptr::<T3>(entity_id).unwrap(),
+				&mut *storage.get_component_ptr::<T4>(entity_id).unwrap()
+			) });
+		}
+
 		if ! self.has_component_manager::<T1>() ||
 			! self.has_component_manager::<T2>() ||
 			! self.has_component_manager::<T3>() ||
@@ -476,43 +1120,659 @@ impl EntityManager {
 		let type_id3 = TypeId::of::<T3>();
 		let type_id4 = TypeId::of::<T4>();
 
-		let manager1 = cast_manager_mut_unsafe(self.manager_map.get(&type_id1).unwrap());
-		let manager2 = cast_manager_mut_unsafe(self.manager_map.get(&type_id2).unwrap());
-		let manager3 = cast_manager_mut_unsafe(self.manager_map.get(&type_id3).unwrap());
-		let manager4 = cast_manager_mut_unsafe(self.manager_map.get(&type_id4).unwrap());
-
-		if !manager1.has(entity_id) || !manager2.has(entity_id) || !manager3.has(entity_id) || !manager4.has(entity_id) {
-			return None;
-		}
+		let manager1 = cast_manager_mut_unsafe::<T1>(self.manager_map.get_mut(&type_id1).unwrap().as_mut());
+		let manager2 = cast_manager_mut_unsafe::<T2>(self.manager_map.get_mut(&type_id2).unwrap().as_mut());
+		let manager3 = cast_manager_mut_unsafe::<T3>(self.manager_map.get_mut(&type_id3).unwrap().as_mut());
+		let manager4 = cast_manager_mut_unsafe::<T4>(self.manager_map.get_mut(&type_id4).unwrap().as_mut());
 
 		Some((
-			manager1.borrow_component_mut(entity_id).unwrap(),
-			manager2.borrow_component_mut(entity_id).unwrap(),
-			manager3.borrow_component_mut(entity_id).unwrap(),
-			manager4.borrow_component_mut(entity_id).unwrap()
+			manager1.borrow_component_mut(entity_id)?,
+			manager2.borrow_component_mut(entity_id)?,
+			manager3.borrow_component_mut(entity_id)?,
+			manager4.borrow_component_mut(entity_id)?
 		))
 	}
 
+	// Calls f with each entity having the component
+	pub fn for_each_mut<
+		T: 'static + Component,
+		F: FnMut(usize, &mut T)
+	>(&mut self, mut f: F) {
+		if ! self.is_registered::<T>() {
+			return;
+		}
+		self.mark_mutated(TypeId::of::<T>(), None);
+
+		if let Some(storage) = self.archetype_storage.as_mut() {
+			let type_id = TypeId::of::<T>();
+			for archetype in storage.borrow_archetypes_mut().iter_mut() {
+				if !archetype.has(type_id) {
+					continue;
+				}
+				let components = archetype.borrow_components_mut_unsafe::<T>();
+				for (entity_id, component) in archetype.borrow_entity_ids().iter().zip(components.iter_mut()) {
+					f(*entity_id, component);
+				}
+			}
+			return;
+		}
+
+		self.borrow_component_manager_mut::<T>().for_each_mut(f);
+	}
+
+	// Calls f with each entity having all the components. In Archetype mode
+	// this walks the matching archetypes' columns without any lookups.
+	pub fn for_each_pair_mut<
+		T1: 'static + Component,
+		T2: 'static + Component,
+		F: FnMut(usize, &mut T1, &mut T2)
+	>(&mut self, mut f: F) {
+		if ! self.is_registered::<T1>() ||
+			! self.is_registered::<T2>() {
+			return;
+		}
+
+		let type_ids = [
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>()
+		];
+		assert_distinct_type_ids(&type_ids);
+		for type_id in type_ids.iter() {
+			self.mark_mutated(*type_id, None);
+		}
+
+		if let Some(storage) = self.archetype_storage.as_mut() {
+			for archetype in storage.borrow_archetypes_mut().iter_mut() {
+				if !archetype.has_all(&type_ids) {
+					continue;
+				}
+				let components1 = archetype.borrow_components_mut_unsafe::<T1>();
+				let components2 = archetype.borrow_components_mut_unsafe::<T2>();
+				for (row, entity_id) in archetype.borrow_entity_ids().iter().enumerate() {
+					f(*entity_id, &mut components1[row], &mut components2[row]);
+				}
+			}
+			return;
+		}
+
+		for entity_id in self.get_shortest_entity_ids(&type_ids) {
+			if let Some((c1, c2)) = self.borrow_component_pair_mut::<T1, T2>(entity_id) {
+				f(entity_id, c1, c2);
+			}
+		}
+	}
+
+	// Calls f with each entity having all the components. In Archetype mode
+	// this walks the matching archetypes' columns without any lookups.
+	pub fn for_each_triple_mut<
+		T1: 'static + Component,
+		T2: 'static + Component,
+		T3: 'static + Component,
+		F: FnMut(usize, &mut T1, &mut T2, &mut T3)
+	>(&mut self, mut f: F) {
+		if ! self.is_registered::<T1>() ||
+			! self.is_registered::<T2>() ||
+			! self.is_registered::<T3>() {
+			return;
+		}
+
+		let type_ids = [
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>(),
+			TypeId::of::<T3>()
+		];
+		assert_distinct_type_ids(&type_ids);
+		for type_id in type_ids.iter() {
+			self.mark_mutated(*type_id, None);
+		}
+
+		if let Some(storage) = self.archetype_storage.as_mut() {
+			for archetype in storage.borrow_archetypes_mut().iter_mut() {
+				if !archetype.has_all(&type_ids) {
+					continue;
+				}
+				let components1 = archetype.borrow_components_mut_unsafe::<T1>();
+				let components2 = archetype.borrow_components_mut_unsafe::<T2>();
+				let components3 = archetype.borrow_components_mut_unsafe::<T3>();
+				for (row, entity_id) in archetype.borrow_entity_ids().iter().enumerate() {
+					f(*entity_id, &mut components1[row], &mut components2[row], &mut components3[row]);
+				}
+			}
+			return;
+		}
+
+		for entity_id in self.get_shortest_entity_ids(&type_ids) {
+			if let Some((c1, c2, c3)) = self.borrow_component_triple_mut::<T1, T2, T3>(entity_id) {
+				f(entity_id, c1, c2, c3);
+			}
+		}
+	}
+
+	// Calls f with each entity having all the components. In Archetype mode
+	// this walks the matching archetypes' columns without any lookups.
+	pub fn for_each_quad_mut<
+		T1: 'static + Component,
+		T2: 'static + Component,
+		T3: 'static + Component,
+		T4: 'static + Component,
+		F: FnMut(usize, &mut T1, &mut T2, &mut T3, &mut T4)
+	>(&mut self, mut f: F) {
+		if ! self.is_registered::<T1>() ||
+			! self.is_registered::<T2>() ||
+			! self.is_registered::<T3>() ||
+			! self.is_registered::<T4>() {
+			return;
+		}
+
+		let type_ids = [
+			TypeId::of::<T1>(),
+			TypeId::of::<T2>(),
+			TypeId::of::<T3>(),
+			TypeId::of::<T4>()
+		];
+		assert_distinct_type_ids(&type_ids);
+		for type_id in type_ids.iter() {
+			self.mark_mutated(*type_id, None);
+		}
+
+		if let Some(storage) = self.archetype_storage.as_mut() {
+			for archetype in storage.borrow_archetypes_mut().iter_mut() {
+				if !archetype.has_all(&type_ids) {
+					continue;
+				}
+				let components1 = archetype.borrow_components_mut_unsafe::<T1>();
+				let components2 = archetype.borrow_components_mut_unsafe::<T2>();
+				let components3 = archetype.borrow_components_mut_unsafe::<T3>();
+				let components4 = archetype.borrow_components_mut_unsafe::<T4>();
+				for (row, entity_id) in archetype.borrow_entity_ids().iter().enumerate() {
+					f(*entity_id, &mut components1[row], &mut components2[row], &mut components3[row], &mut components4[row]);
+				}
+			}
+			return;
+		}
+
+		for entity_id in self.get_shortest_entity_ids(&type_ids) {
+			if let Some((c1, c2, c3, c4)) = self.borrow_component_quad_mut::<T1, T2, T3, T4>(entity_id) {
+				f(entity_id, c1, c2, c3, c4);
+			}
+		}
+	}
+
+	// For PerComponent mode
+	fn get_shortest_entity_ids(&self, type_ids: &[TypeId]) -> Vec<usize> {
+		let mut entity_ids = Vec::new();
+		type_ids.iter()
+			.map(|type_id| self.manager_map.get(type_id).unwrap())
+			.min_by_key(|manager| manager.get_component_count())
+			.unwrap()
+			.collect_entity_ids(&mut entity_ids);
+		entity_ids
+	}
+
+	// Registers a component type defined at runtime. Returns None if the
+	// size and alignment are invalid. Registering a name again returns the
+	// id of the existing one.
+	pub fn register_dynamic_component(&mut self, layout: ComponentLayout) -> Option<ComponentId> {
+		if let Some(index) = self.dynamic_name_map.get(layout.get_name()) {
+			return Some(ComponentId::Dynamic(*index));
+		}
+		let name = layout.get_name().to_string();
+		let storage = match DynamicStorage::new(layout) {
+			Some(storage) => storage,
+			None => {
+				// @TODO: Better error handling
+				println!("Invalid component layout {}", name);
+				return None;
+			}
+		};
+		self.dynamic_storages.push(storage);
+		self.dynamic_name_map.insert(name, self.dynamic_storages.len() - 1);
+		Some(ComponentId::Dynamic(self.dynamic_storages.len() - 1))
+	}
+
+	pub fn get_component_id<T: 'static + Component>(&self) -> ComponentId {
+		ComponentId::Static(TypeId::of::<T>())
+	}
+
+	pub fn get_dynamic_component_id(&self, name: &str) -> Option<ComponentId> {
+		self.dynamic_name_map.get(name).map(|index| ComponentId::Dynamic(*index))
+	}
+
+	// In registration order
+	pub fn get_dynamic_component_ids(&self) -> Vec<ComponentId> {
+		(0..self.dynamic_storages.len()).map(ComponentId::Dynamic).collect()
+	}
+
+	pub fn borrow_dynamic_component_layout(&self, component_id: ComponentId) -> Option<&ComponentLayout> {
+		self.borrow_dynamic_storage(component_id).map(|storage| storage.borrow_layout())
+	}
+
+	// The bytes are moved into the world. If the layout has a drop fn the
+	// caller must not drop the value the bytes came from.
+	pub fn add_dynamic_component_to_entity(&mut self, entity_id: usize, component_id: ComponentId, bytes: &[u8]) -> &mut Self {
+		let added = match self.borrow_dynamic_storage_mut(component_id) {
+			Some(storage) => storage.add(entity_id, bytes),
+			None => {
+				// @TODO: Better error handling
+				println!("Unknown component");
+				return self;
+			}
+		};
+		if !added {
+			// @TODO: Better error handling
+			println!("Component already added or size mismatch");
+		}
+		self
+	}
+
+	pub fn remove_dynamic_component_from_entity(&mut self, entity_id: usize, component_id: ComponentId) -> &mut Self {
+		match self.borrow_dynamic_storage_mut(component_id) {
+			Some(storage) => storage.remove(entity_id),
+			None => {
+				// @TODO: Better error handling
+				println!("Unknown component");
+			}
+		};
+		self
+	}
+
+	pub fn borrow_dynamic_component(&self, entity_id: usize, component_id: ComponentId) -> Option<&[u8]> {
+		self.borrow_dynamic_storage(component_id)?.borrow(entity_id)
+	}
+
+	pub fn borrow_dynamic_component_mut(&mut self, entity_id: usize, component_id: ComponentId) -> Option<&mut [u8]> {
+		self.borrow_dynamic_storage_mut(component_id)?.borrow_mut(entity_id)
+	}
+
+	// Type-erased, works for both static and dynamic components
+	pub fn has_component_id(&self, entity_id: usize, component_id: ComponentId) -> bool {
+		match component_id {
+			ComponentId::Static(type_id) => self.has_component_type_id(entity_id, type_id),
+			ComponentId::Dynamic(index) => match self.dynamic_storages.get(index) {
+				Some(storage) => storage.has(entity_id),
+				None => false
+			}
+		}
+	}
+
+	// Type-erased query. Returns the ids of the entities having all the
+	// static and dynamic components.
+	pub fn get_entity_ids_for(&self, component_ids: &[ComponentId]) -> Vec<usize> {
+		let mut entity_ids = Vec::new();
+		if component_ids.is_empty() {
+			return entity_ids;
+		}
+		match component_ids[0] {
+			ComponentId::Static(type_id) => {
+				if self.is_registered_type_id(type_id) {
+					self.collect_entity_ids(&[type_id], &mut entity_ids);
+				}
+			},
+			ComponentId::Dynamic(index) => {
+				if let Some(storage) = self.dynamic_storages.get(index) {
+					entity_ids.extend_from_slice(storage.borrow_entity_ids());
+				}
+			}
+		};
+		entity_ids.retain(|entity_id| {
+			component_ids[1..].iter().all(|component_id| self.has_component_id(*entity_id, *component_id))
+		});
+		entity_ids
+	}
+
+	fn borrow_dynamic_storage(&self, component_id: ComponentId) -> Option<&DynamicStorage> {
+		match component_id {
+			ComponentId::Dynamic(index) => self.dynamic_storages.get(index),
+			ComponentId::Static(_) => None
+		}
+	}
+
+	fn borrow_dynamic_storage_mut(&mut self, component_id: ComponentId) -> Option<&mut DynamicStorage> {
+		match component_id {
+			ComponentId::Dynamic(index) => self.dynamic_storages.get_mut(index),
+			ComponentId::Static(_) => None
+		}
+	}
+
+	pub fn borrow_commands_mut(&mut self) -> &mut Commands {
+		&mut self.commands
+	}
+
+	// Commands pushed by the applied ones are applied too
+	pub fn apply_commands(&mut self) {
+		while !self.commands.is_empty() {
+			for command in self.commands.take() {
+				command(self);
+			}
+		}
+	}
+
+	pub fn borrow_type_registry(&self) -> &TypeRegistry {
+		&self.type_registry
+	}
+
+	pub fn borrow_type_registry_mut(&mut self) -> &mut TypeRegistry {
+		&mut self.type_registry
+	}
+
+	// path is "ComponentName.field_name" of a reflectable component
+	pub fn get_field(&self, entity_id: usize, path: &str) -> Option<Value> {
+		self.type_registry.get_field(self, entity_id, path)
+	}
+
+	// Returns false if the entity doesn't have the component, the field
+	// doesn't exist or the value type doesn't match
+	pub fn set_field(&mut self, entity_id: usize, path: &str, value: &Value) -> bool {
+		match self.type_registry.find_field_setter(path) {
+			Some((set_field, field_name)) => set_field(self, entity_id, field_name, value),
+			None => false
+		}
+	}
+
+	// Adds the default value of a constructible component by its name.
+	// Returns false if there is no such component.
+	pub fn add_default_component_to_entity(&mut self, entity_id: usize, name: &str) -> bool {
+		match self.type_registry.borrow_type_info(name).and_then(|type_info| type_info.get_add_default_fn()) {
+			Some(add_default) => {
+				add_default(self, entity_id);
+				true
+			},
+			None => false
+		}
+	}
+
+	pub fn insert_resource<T: 'static>(&mut self, resource: T) -> &mut Self {
+		self.resource_manager.insert(resource);
+		self
+	}
+
+	pub fn insert_cloneable_resource<T: 'static + Clone>(&mut self, resource: T) -> &mut Self {
+		self.resource_manager.insert_cloneable(resource);
+		self
+	}
+
+	pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+		self.resource_manager.remove::<T>()
+	}
+
+	pub fn has_resource<T: 'static>(&self) -> bool {
+		self.resource_manager.has::<T>()
+	}
+
+	pub fn borrow_resource<T: 'static>(&self) -> Option<&T> {
+		self.resource_manager.borrow::<T>()
+	}
+
+	pub fn borrow_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		self.resource_manager.borrow_mut::<T>()
+	}
+
+	pub fn register_event<T: 'static>(&mut self) -> &mut Self {
+		if ! self.has_resource::<Events<T>>() {
+			self.insert_resource(Events::<T>::new());
+			self.event_clear_fns.push(clear_events::<T>);
+		}
+		self
+	}
+
+	pub fn send_event<T: 'static>(&mut self, event: T) -> &mut Self {
+		match self.borrow_resource_mut::<Events<T>>() {
+			Some(events) => events.send(event),
+			None => {
+				// @TODO: Better error handling
+				println!("Unknown event");
+			}
+		};
+		self
+	}
+
+	pub fn borrow_events<T: 'static>(&self) -> Option<&Events<T>> {
+		self.borrow_resource::<Events<T>>()
+	}
+
+	pub(crate) fn clear_events(&mut self) {
+		for i in 0..self.event_clear_fns.len() {
+			(self.event_clear_fns[i])(self);
+		}
+	}
+
+	// Returns a copy holding the entities, the frame counters and only the
+	// cloneable component managers and resources. In Archetype mode all the
+	// components need to be cloneable, otherwise the type id of one which is
+	// not is returned.
+	pub(crate) fn clone_cloneables(&self) -> Result<Self, TypeId> {
+		let mut manager_map = HashMap::new();
+		for (type_id, clone_fn) in self.clone_fn_map.iter() {
+			let manager = self.manager_map.get(type_id).unwrap();
+			manager_map.insert(*type_id, clone_fn(manager.as_ref()));
+		}
+		let archetype_storage = match self.archetype_storage.as_ref() {
+			Some(storage) => Some(storage.try_clone()?),
+			None => None
+		};
+		Ok(EntityManager {
+			entities: self.entities.clone(),
+			manager_map,
+			archetype_storage,
+			frame: self.frame,
+			updated_frame_map: self.updated_frame_map.clone(),
+			component_changes: ComponentChanges::new(),
+			mutation_map: HashMap::new(),
+			clone_fn_map: self.clone_fn_map.clone(),
+			dynamic_storages: Vec::new(),
+			dynamic_name_map: HashMap::new(),
+			type_registry: TypeRegistry::new(),
+			commands: Commands::new(),
+			hook_registry: HookRegistry::new(),
+			resource_manager: self.resource_manager.clone_cloneables(),
+			event_clear_fns: Vec::new()
+		})
+	}
+
+	// Counterpart of clone_cloneables(). Components which are not cloneable
+	// are removed since their entity ids may be dead or reused. Resources
+	// which are not cloneable are left as they are.
+	pub(crate) fn restore_cloneables(&mut self, src: &Self) {
+		self.entities = src.entities.clone();
+		let mut entity_ids = Vec::new();
+		for (type_id, manager) in self.manager_map.iter_mut() {
+			if src.clone_fn_map.contains_key(type_id) {
+				continue;
+			}
+			entity_ids.clear();
+			manager.collect_entity_ids(&mut entity_ids);
+			for entity_id in entity_ids.iter() {
+				manager.remove(*entity_id);
+			}
+		}
+		for storage in self.dynamic_storages.iter_mut() {
+			for entity_id in storage.borrow_entity_ids().clone() {
+				storage.remove(entity_id);
+			}
+		}
+		for (type_id, clone_fn) in src.clone_fn_map.iter() {
+			let manager = src.manager_map.get(type_id).unwrap();
+			self.manager_map.insert(*type_id, clone_fn(manager.as_ref()));
+			self.clone_fn_map.insert(*type_id, *clone_fn);
+		}
+		// Complete since clone_cloneables() succeeded
+		if let Some(Ok(mut storage)) = src.archetype_storage.as_ref().map(|storage| storage.try_clone()) {
+			if let Some(current) = self.archetype_storage.as_ref() {
+				storage.add_registrations(current);
+			}
+			self.archetype_storage = Some(storage);
+		}
+		self.frame = src.frame;
+		for (type_id, updated_frame) in src.updated_frame_map.iter() {
+			self.updated_frame_map.insert(*type_id, *updated_frame);
+		}
+		self.component_changes.invalidate();
+		self.resource_manager.restore_cloneables(&src.resource_manager);
+	}
+
 	fn has_component_manager<T: 'static + Component>(&self) -> bool {
 		let type_id = TypeId::of::<T>();
 		self.manager_map.contains_key(&type_id)
 	}
 
-	fn borrow_component_manager<T: 'static + Component>(&self) -> &ComponentManager<T> {
+	fn borrow_component_manager<T: 'static + Component>(&self) -> &T::Storage {
 		let type_id = TypeId::of::<T>();
-		cast_manager(self.manager_map.get(&type_id).unwrap().as_ref())
+		cast_manager::<T>(self.manager_map.get(&type_id).unwrap().as_ref())
 	}
 
-	fn borrow_component_manager_mut<T: 'static + Component>(&mut self) -> &mut ComponentManager<T> {
+	fn borrow_component_manager_mut<T: 'static + Component>(&mut self) -> &mut T::Storage {
 		let type_id = TypeId::of::<T>();
-		cast_manager_mut(self.manager_map.get_mut(&type_id).unwrap().as_mut())
+		cast_manager_mut::<T>(self.manager_map.get_mut(&type_id).unwrap().as_mut())
 	}
 }
 
-// @TODO: Write comment
-fn cast_manager_mut_unsafe<T: 'static + Component>
-	(manager: &Box<dyn ComponentManagerTrait>) -> &mut ComponentManager<T> {
-	let ptr = cast_manager(manager.as_ref())
-		as *const ComponentManager<T> as *mut ComponentManager<T>;
-	unsafe { transmute(ptr) }
+// Otherwise a component would be mutably borrowed twice
+pub(crate) fn assert_distinct_type_ids(type_ids: &[TypeId]) {
+	for (i, type_id) in type_ids.iter().enumerate() {
+		assert!(!type_ids[..i].contains(type_id), "The component types must be distinct");
+	}
+}
+
+// Detaches the returned reference from the manager_map borrow so that
+// multiple component managers can be mutably borrowed at the same time.
+// The caller must not request the same component type twice.
+fn cast_manager_mut_unsafe<'a, T: 'static + Component>
+	(manager: &mut dyn ComponentManagerTrait) -> &'a mut T::Storage {
+	let ptr = cast_manager_mut::<T>(manager) as *mut T::Storage;
+	unsafe { &mut *ptr }
+}
+
+fn clear_events<T: 'static>(manager: &mut EntityManager) {
+	if let Some(events) = manager.borrow_resource_mut::<Events<T>>() {
+		events.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::component::Component;
+	use crate::dynamic::ComponentLayout;
+	use crate::storage::DenseStorage;
+	use super::{EntityIdAccessor, EntityManager, MoveEntityError, StorageMode};
+
+	struct Position {
+		x: f32
+	}
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	// Not in the type registry
+	struct Velocity;
+
+	impl Component for Velocity {
+		type Storage = DenseStorage<Self>;
+	}
+
+	fn create_manager(storage_mode: StorageMode) -> (EntityManager, usize) {
+		let mut manager = EntityManager::with_storage_mode(storage_mode);
+		manager.register::<Position>();
+		manager.borrow_type_registry_mut().register_named::<Position>("Position");
+		let entity_id = manager.create_entity();
+		manager.add_component_to_entity(entity_id, Position { x: 1.0 });
+		(manager, entity_id)
+	}
+
+	fn move_entity(storage_mode: StorageMode) {
+		let (mut src, entity_id) = create_manager(storage_mode);
+		let mut dst = EntityManager::with_storage_mode(storage_mode);
+		let layout = ComponentLayout::new("Health", 4, 4);
+		let component_id = src.register_dynamic_component(layout.clone()).unwrap();
+		src.add_dynamic_component_to_entity(entity_id, component_id, &7_u32.to_ne_bytes());
+
+		let dst_entity_id = src.move_entity_to(&mut dst, entity_id).unwrap();
+
+		assert!(!src.has_entity(entity_id));
+		assert_eq!(dst.borrow_component::<Position>(dst_entity_id).unwrap().x, 1.0);
+		let dst_component_id = dst.get_dynamic_component_id("Health").unwrap();
+		assert!(dst.has_component_id(dst_entity_id, dst_component_id));
+	}
+
+	fn reject_unmovable(storage_mode: StorageMode) {
+		let (mut src, entity_id) = create_manager(storage_mode);
+		let mut dst = EntityManager::with_storage_mode(storage_mode);
+
+		assert!(matches!(src.move_entity_to(&mut dst, entity_id + 1), Err(MoveEntityError::UnknownEntity(_))));
+
+		src.register::<Velocity>();
+		src.add_component_to_entity(entity_id, Velocity);
+		assert!(matches!(src.move_entity_to(&mut dst, entity_id), Err(MoveEntityError::UnregisteredComponent(_))));
+		src.remove_component_from_entity::<Velocity>(entity_id);
+
+		let component_id = src.register_dynamic_component(ComponentLayout::new("Health", 4, 4)).unwrap();
+		src.add_dynamic_component_to_entity(entity_id, component_id, &7_u32.to_ne_bytes());
+		dst.register_dynamic_component(ComponentLayout::new("Health", 8, 8));
+		assert!(matches!(src.move_entity_to(&mut dst, entity_id), Err(MoveEntityError::LayoutMismatch(_))));
+
+		// Nothing is moved
+		assert!(src.has_component::<Position>(entity_id));
+		assert!(src.has_component_id(entity_id, component_id));
+		assert!(dst.get_entity_ids().is_empty());
+	}
+
+	#[test]
+	fn move_entity_per_component() {
+		move_entity(StorageMode::PerComponent);
+	}
+
+	#[test]
+	fn move_entity_archetype() {
+		move_entity(StorageMode::Archetype);
+	}
+
+	#[test]
+	fn reject_unmovable_per_component() {
+		reject_unmovable(StorageMode::PerComponent);
+	}
+
+	#[test]
+	fn reject_unmovable_archetype() {
+		reject_unmovable(StorageMode::Archetype);
+	}
+
+	fn sorted(ids: &[usize]) -> Vec<usize> {
+		let mut ids = ids.to_vec();
+		ids.sort_unstable();
+		ids
+	}
+
+	fn update_query_caches(storage_mode: StorageMode) {
+		let mut manager = EntityManager::with_storage_mode(storage_mode);
+		manager.register::<Position>().register::<Velocity>();
+		let mut accessor = EntityIdAccessor::new();
+		for i in 0..6 {
+			let entity_id = manager.create_entity();
+			manager.add_component_to_entity(entity_id, Position { x: i as f32 });
+			if i % 2 == 0 {
+				manager.add_component_to_entity(entity_id, Velocity);
+			}
+		}
+		assert_eq!(accessor.borrow_ids::<Position>(&manager).unwrap(), &vec![0, 1, 2, 3, 4, 5]);
+		assert_eq!(accessor.borrow_ids_for_pair::<Position, Velocity>(&manager).unwrap(), &vec![0, 2, 4]);
+
+		manager.remove_entity(0);
+		manager.remove_component_from_entity::<Velocity>(2);
+		manager.add_component_to_entity(3, Velocity);
+		let entity_id = manager.create_entity();
+		manager.add_component_to_entity(entity_id, Position { x: 6.0 });
+
+		assert_eq!(sorted(accessor.borrow_ids::<Position>(&manager).unwrap()), vec![0, 1, 2, 3, 4, 5]);
+		assert_eq!(sorted(accessor.borrow_ids_for_pair::<Position, Velocity>(&manager).unwrap()), vec![3, 4]);
+	}
+
+	#[test]
+	fn update_query_caches_per_component() {
+		update_query_caches(StorageMode::PerComponent);
+	}
+
+	#[test]
+	fn update_query_caches_archetype() {
+		update_query_caches(StorageMode::Archetype);
+	}
 }
@@ -0,0 +1,36 @@
+// Event queue stored as a resource. Events sent between World::update()
+// calls or by a system are visible to the following systems in the same
+// update and are cleared at the end of the update.
+pub struct Events<T> {
+	events: Vec<T>
+}
+
+impl<T> Default for Events<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> Events<T> {
+	pub fn new() -> Self {
+		Events {
+			events: Vec::new()
+		}
+	}
+
+	pub fn send(&mut self, event: T) {
+		self.events.push(event);
+	}
+
+	pub fn borrow_events(&self) -> &Vec<T> {
+		&self.events
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.events.is_empty()
+	}
+
+	pub fn clear(&mut self) {
+		self.events.clear();
+	}
+}
@@ -0,0 +1,168 @@
+#[cfg(feature = "serde")]
+use std::collections::HashMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::checksum::{StableHash, StableHasher};
+use super::component::Component;
+use super::storage::DenseStorage;
+use super::entity_manager::EntityManager;
+#[cfg(feature = "serde")]
+use super::serialization::MapEntities;
+
+// Saved and loaded with World::register_serializable_hierarchy()
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Parent {
+	entity_id: usize
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Children {
+	entity_ids: Vec<usize>
+}
+
+impl Component for Parent {
+	type Storage = DenseStorage<Self>;
+}
+impl Component for Children {
+	type Storage = DenseStorage<Self>;
+}
+
+impl StableHash for Parent {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		self.entity_id.stable_hash(hasher);
+	}
+}
+
+impl StableHash for Children {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		self.entity_ids.stable_hash(hasher);
+	}
+}
+
+#[cfg(feature = "serde")]
+impl MapEntities for Parent {
+	fn map_entities(&mut self, entity_map: &HashMap<usize, usize>) {
+		if let Some(entity_id) = entity_map.get(&self.entity_id) {
+			self.entity_id = *entity_id;
+		}
+	}
+}
+
+#[cfg(feature = "serde")]
+impl MapEntities for Children {
+	fn map_entities(&mut self, entity_map: &HashMap<usize, usize>) {
+		for entity_id in self.entity_ids.iter_mut() {
+			if let Some(mapped_id) = entity_map.get(entity_id) {
+				*entity_id = *mapped_id;
+			}
+		}
+	}
+}
+
+impl Parent {
+	pub fn get(&self) -> usize {
+		self.entity_id
+	}
+}
+
+impl Children {
+	pub fn borrow_entity_ids(&self) -> &Vec<usize> {
+		&self.entity_ids
+	}
+}
+
+impl EntityManager {
+	// Parent and Children are registered on demand so that worlds
+	// not using the hierarchy don't need to know about them.
+	pub fn set_parent(&mut self, child_id: usize, parent_id: usize) -> &mut Self {
+		if child_id == parent_id {
+			// @TODO: Better error handling
+			println!("Entity can not be its own parent");
+			return self;
+		}
+
+		if !self.has_entity(child_id) {
+			// @TODO: Better error handling
+			println!("Unknown child entity {}", child_id);
+			return self;
+		}
+
+		if !self.has_entity(parent_id) {
+			// @TODO: Better error handling
+			println!("Unknown parent entity {}", parent_id);
+			return self;
+		}
+
+		// Otherwise the hierarchy would have a cycle
+		let mut ancestor_id = parent_id;
+		while let Some(parent) = self.borrow_component::<Parent>(ancestor_id) {
+			if parent.entity_id == child_id {
+				// @TODO: Better error handling
+				println!("Entity can not be a child of its descendant");
+				return self;
+			}
+			ancestor_id = parent.entity_id;
+		}
+
+		self.register_cloneable::<Parent>();
+		self.register_cloneable::<Children>();
+		self.remove_parent(child_id);
+
+		self.add_component_to_entity(child_id, Parent {
+			entity_id: parent_id
+		});
+
+		match self.borrow_component_mut::<Children>(parent_id) {
+			Some(children) => children.entity_ids.push(child_id),
+			None => {
+				self.add_component_to_entity(parent_id, Children {
+					entity_ids: vec![child_id]
+				});
+			}
+		};
+
+		self
+	}
+
+	pub fn remove_parent(&mut self, child_id: usize) -> &mut Self {
+		let parent_id = match self.borrow_component::<Parent>(child_id) {
+			Some(parent) => parent.entity_id,
+			None => return self
+		};
+
+		self.remove_component_from_entity::<Parent>(child_id);
+
+		let is_empty = match self.borrow_component_mut::<Children>(parent_id) {
+			Some(children) => {
+				children.entity_ids.retain(|id| *id != child_id);
+				children.entity_ids.is_empty()
+			},
+			None => false
+		};
+
+		if is_empty {
+			self.remove_component_from_entity::<Children>(parent_id);
+		}
+
+		self
+	}
+
+	// Called on entity removal. The removed entity is detached from its
+	// parent and its children become roots.
+	pub(crate) fn detach_from_hierarchy(&mut self, entity_id: usize) {
+		self.remove_parent(entity_id);
+
+		let child_ids = match self.borrow_component::<Children>(entity_id) {
+			Some(children) => children.entity_ids.clone(),
+			None => return
+		};
+
+		for child_id in child_ids {
+			self.remove_component_from_entity::<Parent>(child_id);
+		}
+	}
+}
@@ -0,0 +1,153 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::entity_manager::EntityManager;
+
+// Called with the entity id. The component is readable with
+// EntityManager::borrow_component(): the new value in on_add and
+// on_insert, the old value in on_replace and on_remove. Hooks run in the
+// middle of adding or removing, so structural changes, e.g. removing
+// entities, should be pushed to EntityManager::borrow_commands_mut().
+pub type Hook = Rc<dyn Fn(&mut EntityManager, usize)>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HookKind {
+	OnAdd, // The entity didn't have the component
+	OnInsert, // Added or replaced, after OnAdd or OnReplace
+	OnReplace, // Replaced or removed, before OnRemove
+	OnRemove // Removed, also by entity removal
+}
+
+#[derive(Default)]
+struct ComponentHooks {
+	on_add: Vec<Hook>,
+	on_insert: Vec<Hook>,
+	on_replace: Vec<Hook>,
+	on_remove: Vec<Hook>
+}
+
+impl ComponentHooks {
+	fn borrow_hooks_mut(&mut self, kind: HookKind) -> &mut Vec<Hook> {
+		match kind {
+			HookKind::OnAdd => &mut self.on_add,
+			HookKind::OnInsert => &mut self.on_insert,
+			HookKind::OnReplace => &mut self.on_replace,
+			HookKind::OnRemove => &mut self.on_remove
+		}
+	}
+
+	fn borrow_hooks(&self, kind: HookKind) -> &Vec<Hook> {
+		match kind {
+			HookKind::OnAdd => &self.on_add,
+			HookKind::OnInsert => &self.on_insert,
+			HookKind::OnReplace => &self.on_replace,
+			HookKind::OnRemove => &self.on_remove
+		}
+	}
+}
+
+pub(crate) struct HookRegistry {
+	hooks_map: HashMap<TypeId, ComponentHooks>
+}
+
+impl HookRegistry {
+	pub(crate) fn new() -> Self {
+		HookRegistry {
+			hooks_map: HashMap::new()
+		}
+	}
+
+	pub(crate) fn add(&mut self, type_id: TypeId, kind: HookKind, hook: Hook) {
+		self.hooks_map.entry(type_id).or_default().borrow_hooks_mut(kind).push(hook);
+	}
+
+	pub(crate) fn has(&self, type_id: TypeId, kind: HookKind) -> bool {
+		match self.hooks_map.get(&type_id) {
+			Some(hooks) => !hooks.borrow_hooks(kind).is_empty(),
+			None => false
+		}
+	}
+
+	pub(crate) fn is_empty(&self) -> bool {
+		self.hooks_map.is_empty()
+	}
+
+	// Cloned so that the hooks can be called with the EntityManager
+	// owning the registry
+	pub(crate) fn get(&self, type_id: TypeId, kind: HookKind) -> Vec<Hook> {
+		match self.hooks_map.get(&type_id) {
+			Some(hooks) => hooks.borrow_hooks(kind).clone(),
+			None => Vec::new()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::component::Component;
+	use crate::entity_manager::{EntityIdAccessor, EntityManager};
+	use crate::storage::DenseStorage;
+	use crate::system::System;
+	use crate::world::World;
+
+	struct Health;
+
+	impl Component for Health {
+		type Storage = DenseStorage<Self>;
+	}
+
+	struct Name {
+		name: &'static str
+	}
+
+	impl Component for Name {
+		type Storage = DenseStorage<Self>;
+	}
+
+	fn log(manager: &mut EntityManager, entry: String) {
+		manager.borrow_resource_mut::<Vec<String>>().unwrap().push(entry);
+	}
+
+	struct SpawnSystem;
+
+	impl System for SpawnSystem {
+		fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+			manager.borrow_commands_mut().spawn((Health, Name { name: "Dragon" }));
+		}
+	}
+
+	fn create_world() -> World {
+		let mut world = World::new();
+		world
+			.register_component::<Health>()
+			.register_component::<Name>()
+			.insert_resource(Vec::<String>::new())
+			.on_add::<Health, _>(|manager, entity_id| {
+				// Health comes first in the bundles, so Name isn't added yet
+				let has_name = manager.has_component::<Name>(entity_id);
+				log(manager, format!("hook {}", has_name));
+				manager.borrow_commands_mut().push(move |manager| {
+					let name = manager.borrow_component::<Name>(entity_id).unwrap().name;
+					log(manager, format!("command {}", name));
+				});
+			});
+		world
+	}
+
+	#[test]
+	fn command_after_bundle() {
+		let mut world = create_world();
+		let entity_id = world.create_entity();
+		world.add_bundle_to_entity(entity_id, (Health, Name { name: "Slime" }));
+		assert_eq!(world.borrow_resource::<Vec<String>>().unwrap(), &vec!["hook false", "command Slime"]);
+	}
+
+	#[test]
+	fn command_after_spawned_bundle() {
+		let mut world = create_world();
+		world.add_system(SpawnSystem);
+		world.update();
+		assert_eq!(world.borrow_resource::<Vec<String>>().unwrap(), &vec!["hook false", "command Dragon"]);
+	}
+}
@@ -0,0 +1,410 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use super::entity_manager::{EntityIdAccessor, EntityManager};
+use super::system::System;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MouseButton {
+	Left,
+	Right,
+	Middle,
+	Other(u16)
+}
+
+// A key or a button. Keys are named by the platform, e.g. KeyboardEvent.code
+// "ArrowLeft" or "KeyA" on the web.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+	Key(String),
+	Mouse(MouseButton)
+}
+
+// Pointer positions are in the platform's units, e.g. canvas pixels.
+// Touches are reported as the pointer and the left button.
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputEvent {
+	PointerMoved {
+		x: f32,
+		y: f32
+	},
+	PointerLeft,
+	ButtonPressed(MouseButton),
+	ButtonReleased(MouseButton),
+	KeyPressed(String),
+	KeyReleased(String)
+}
+
+// Safe queue the platform's event loop pushes events to, from any thread.
+// Cloned from Input::get_queue().
+#[derive(Clone, Default)]
+pub struct InputQueue {
+	events: Arc<Mutex<VecDeque<InputEvent>>>
+}
+
+impl InputQueue {
+	pub fn new() -> Self {
+		InputQueue {
+			events: Arc::new(Mutex::new(VecDeque::new()))
+		}
+	}
+
+	pub fn push(&self, event: InputEvent) {
+		self.events.lock().unwrap().push_back(event);
+	}
+
+	fn drain(&self) -> Vec<InputEvent> {
+		self.events.lock().unwrap().drain(..).collect()
+	}
+}
+
+// Pushes events to the queue when Input is updated, e.g. a fake input
+// for headless tests or a platform which needs polling
+pub trait InputSource {
+	fn poll(&mut self, queue: &InputQueue);
+}
+
+// Fake input source pushing scripted events, one step per update
+//
+// ScriptedInput::new()
+//     .step(vec![InputEvent::KeyPressed("Space".to_string())])
+//     .wait(2)
+//     .step(vec![InputEvent::KeyReleased("Space".to_string())])
+pub struct ScriptedInput {
+	steps: VecDeque<Vec<InputEvent>>
+}
+
+impl Default for ScriptedInput {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ScriptedInput {
+	pub fn new() -> Self {
+		ScriptedInput {
+			steps: VecDeque::new()
+		}
+	}
+
+	pub fn step(mut self, events: Vec<InputEvent>) -> Self {
+		self.steps.push_back(events);
+		self
+	}
+
+	// Updates without events
+	pub fn wait(mut self, updates: usize) -> Self {
+		for _ in 0..updates {
+			self.steps.push_back(Vec::new());
+		}
+		self
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.steps.is_empty()
+	}
+}
+
+impl InputSource for ScriptedInput {
+	fn poll(&mut self, queue: &InputQueue) {
+		for event in self.steps.pop_front().unwrap_or_default() {
+			queue.push(event);
+		}
+	}
+}
+
+// Resource holding the input state of the current update. Updated from
+// the queued events by InputSystem, see World::add_input().
+pub struct Input {
+	queue: InputQueue,
+	sources: Vec<Box<dyn InputSource>>,
+	pointer_position: Option<[f32; 2]>,
+	pointer_moved: bool,
+	pressed: HashSet<Binding>,
+	just_pressed: HashSet<Binding>,
+	just_released: HashSet<Binding>,
+	actions: HashMap<String, Vec<Binding>>
+}
+
+impl Default for Input {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Input {
+	pub fn new() -> Self {
+		Input {
+			queue: InputQueue::new(),
+			sources: Vec::new(),
+			pointer_position: None,
+			pointer_moved: false,
+			pressed: HashSet::new(),
+			just_pressed: HashSet::new(),
+			just_released: HashSet::new(),
+			actions: HashMap::new()
+		}
+	}
+
+	// For the platform's event loop
+	pub fn get_queue(&self) -> InputQueue {
+		self.queue.clone()
+	}
+
+	pub fn add_source<T: 'static + InputSource>(&mut self, source: T) -> &mut Self {
+		self.sources.push(Box::new(source));
+		self
+	}
+
+	// The action is active while any of its bindings is
+	pub fn bind(&mut self, action: &str, binding: Binding) -> &mut Self {
+		self.actions.entry(action.to_string()).or_default().push(binding);
+		self
+	}
+
+	pub fn unbind(&mut self, action: &str) -> &mut Self {
+		self.actions.remove(action);
+		self
+	}
+
+	// None until the pointer moves, and after it leaves
+	pub fn get_pointer_position(&self) -> Option<[f32; 2]> {
+		self.pointer_position
+	}
+
+	// Moved in this update
+	pub fn is_pointer_moved(&self) -> bool {
+		self.pointer_moved
+	}
+
+	pub fn is_pressed(&self, binding: &Binding) -> bool {
+		self.pressed.contains(binding)
+	}
+
+	// Pressed in this update
+	pub fn is_just_pressed(&self, binding: &Binding) -> bool {
+		self.just_pressed.contains(binding)
+	}
+
+	// Released in this update
+	pub fn is_just_released(&self, binding: &Binding) -> bool {
+		self.just_released.contains(binding)
+	}
+
+	pub fn is_key_pressed(&self, key: &str) -> bool {
+		self.is_pressed(&Binding::Key(key.to_string()))
+	}
+
+	pub fn is_key_just_pressed(&self, key: &str) -> bool {
+		self.is_just_pressed(&Binding::Key(key.to_string()))
+	}
+
+	pub fn is_key_just_released(&self, key: &str) -> bool {
+		self.is_just_released(&Binding::Key(key.to_string()))
+	}
+
+	pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+		self.is_pressed(&Binding::Mouse(button))
+	}
+
+	pub fn is_button_just_pressed(&self, button: MouseButton) -> bool {
+		self.is_just_pressed(&Binding::Mouse(button))
+	}
+
+	pub fn is_button_just_released(&self, button: MouseButton) -> bool {
+		self.is_just_released(&Binding::Mouse(button))
+	}
+
+	pub fn is_action_pressed(&self, action: &str) -> bool {
+		self.any_binding(action, |binding| self.is_pressed(binding))
+	}
+
+	// The first of its bindings is pressed in this update
+	pub fn is_action_just_pressed(&self, action: &str) -> bool {
+		self.any_binding(action, |binding| self.is_just_pressed(binding)) &&
+			!self.any_binding(action, |binding| self.is_pressed(binding) && !self.is_just_pressed(binding))
+	}
+
+	// The last of its bindings is released in this update
+	pub fn is_action_just_released(&self, action: &str) -> bool {
+		self.any_binding(action, |binding| self.is_just_released(binding)) &&
+			!self.is_action_pressed(action)
+	}
+
+	fn any_binding<F: Fn(&Binding) -> bool>(&self, action: &str, f: F) -> bool {
+		match self.actions.get(action) {
+			Some(bindings) => bindings.iter().any(f),
+			None => false
+		}
+	}
+
+	// Applies the events queued since the last update
+	pub fn update(&mut self) {
+		self.pointer_moved = false;
+		self.just_pressed.clear();
+		self.just_released.clear();
+		for source in self.sources.iter_mut() {
+			source.poll(&self.queue);
+		}
+		for event in self.queue.drain() {
+			self.apply_event(event);
+		}
+	}
+
+	fn apply_event(&mut self, event: InputEvent) {
+		let (binding, pressed) = match event {
+			InputEvent::PointerMoved { x, y } => {
+				self.pointer_position = Some([x, y]);
+				self.pointer_moved = true;
+				return;
+			},
+			InputEvent::PointerLeft => {
+				self.pointer_position = None;
+				return;
+			},
+			InputEvent::ButtonPressed(button) => (Binding::Mouse(button), true),
+			InputEvent::ButtonReleased(button) => (Binding::Mouse(button), false),
+			InputEvent::KeyPressed(key) => (Binding::Key(key), true),
+			InputEvent::KeyReleased(key) => (Binding::Key(key), false)
+		};
+		match pressed {
+			// Key repeats are not just pressed
+			true => if self.pressed.insert(binding.clone()) {
+				self.just_pressed.insert(binding);
+			},
+			// A press and a release in the same update are both seen
+			false => if self.pressed.remove(&binding) {
+				self.just_released.insert(binding);
+			}
+		};
+	}
+}
+
+// Updates the Input resource. Runs before the systems reading the input.
+pub struct InputSystem;
+
+impl System for InputSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		if let Some(input) = manager.borrow_resource_mut::<Input>() {
+			input.update();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Binding, Input, InputEvent, MouseButton, ScriptedInput};
+	use crate::world::World;
+
+	fn create_world(source: ScriptedInput) -> World {
+		let mut input = Input::new();
+		input
+			.add_source(source)
+			.bind("jump", Binding::Key("Space".to_string()))
+			.bind("jump", Binding::Mouse(MouseButton::Left));
+		let mut world = World::new();
+		world.add_input(input);
+		world
+	}
+
+	fn borrow_input(world: &World) -> &Input {
+		world.borrow_resource::<Input>().unwrap()
+	}
+
+	#[test]
+	fn key_just_pressed_and_released() {
+		let mut world = create_world(ScriptedInput::new()
+			.step(vec![InputEvent::KeyPressed("Space".to_string())])
+			// Key repeat
+			.step(vec![InputEvent::KeyPressed("Space".to_string())])
+			.step(vec![InputEvent::KeyReleased("Space".to_string())])
+			.wait(1));
+
+		world.update();
+		assert!(borrow_input(&world).is_key_pressed("Space"));
+		assert!(borrow_input(&world).is_key_just_pressed("Space"));
+
+		world.update();
+		assert!(borrow_input(&world).is_key_pressed("Space"));
+		assert!(!borrow_input(&world).is_key_just_pressed("Space"));
+
+		world.update();
+		assert!(!borrow_input(&world).is_key_pressed("Space"));
+		assert!(borrow_input(&world).is_key_just_released("Space"));
+
+		world.update();
+		assert!(!borrow_input(&world).is_key_just_released("Space"));
+	}
+
+	#[test]
+	fn press_and_release_in_one_update() {
+		let mut world = create_world(ScriptedInput::new()
+			.step(vec![
+				InputEvent::ButtonPressed(MouseButton::Left),
+				InputEvent::ButtonReleased(MouseButton::Left)
+			]));
+
+		world.update();
+		let input = borrow_input(&world);
+		assert!(!input.is_button_pressed(MouseButton::Left));
+		assert!(input.is_button_just_pressed(MouseButton::Left));
+		assert!(input.is_button_just_released(MouseButton::Left));
+	}
+
+	#[test]
+	fn action_with_two_bindings() {
+		let mut world = create_world(ScriptedInput::new()
+			.step(vec![InputEvent::KeyPressed("Space".to_string())])
+			.step(vec![InputEvent::ButtonPressed(MouseButton::Left)])
+			.step(vec![InputEvent::KeyReleased("Space".to_string())])
+			.step(vec![InputEvent::ButtonReleased(MouseButton::Left)]));
+
+		world.update();
+		assert!(borrow_input(&world).is_action_just_pressed("jump"));
+
+		// Already pressed by the other binding
+		world.update();
+		assert!(borrow_input(&world).is_action_pressed("jump"));
+		assert!(!borrow_input(&world).is_action_just_pressed("jump"));
+
+		world.update();
+		assert!(borrow_input(&world).is_action_pressed("jump"));
+		assert!(!borrow_input(&world).is_action_just_released("jump"));
+
+		world.update();
+		assert!(!borrow_input(&world).is_action_pressed("jump"));
+		assert!(borrow_input(&world).is_action_just_released("jump"));
+	}
+
+	#[test]
+	fn pointer() {
+		let mut world = create_world(ScriptedInput::new()
+			.step(vec![InputEvent::PointerMoved { x: 10.0, y: 20.0 }])
+			.wait(1)
+			.step(vec![InputEvent::PointerLeft]));
+
+		assert_eq!(borrow_input(&world).get_pointer_position(), None);
+
+		world.update();
+		assert_eq!(borrow_input(&world).get_pointer_position(), Some([10.0, 20.0]));
+		assert!(borrow_input(&world).is_pointer_moved());
+
+		world.update();
+		assert_eq!(borrow_input(&world).get_pointer_position(), Some([10.0, 20.0]));
+		assert!(!borrow_input(&world).is_pointer_moved());
+
+		world.update();
+		assert_eq!(borrow_input(&world).get_pointer_position(), None);
+	}
+
+	#[test]
+	fn queued_events() {
+		let mut world = create_world(ScriptedInput::new());
+		let queue = borrow_input(&world).get_queue();
+		queue.push(InputEvent::KeyPressed("KeyA".to_string()));
+		assert!(!borrow_input(&world).is_key_pressed("KeyA"));
+
+		world.update();
+		assert!(borrow_input(&world).is_key_just_pressed("KeyA"));
+	}
+}
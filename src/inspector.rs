@@ -0,0 +1,388 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Number};
+
+use super::reflect::Value;
+use super::world::World;
+
+pub const DEFAULT_PORT: u16 = 7411;
+
+// How often the listener thread checks for new clients and the shutdown
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+// Requests are JSON objects, one per line, with a "cmd" field. Each request
+// is answered with a JSON line having "ok": true and the result, or
+// "ok": false and "error".
+//
+// {"cmd": "status"} -> {"ok": true, "frame": 12, "paused": false, "entities": 3}
+// {"cmd": "entities"} -> {"ok": true, "entities": [{"id": 0, "components": ["Position"]}]}
+// {"cmd": "get", "entity": 0, "path": "Position.x"} -> {"ok": true, "value": 1.5}
+// {"cmd": "get", "entity": 0, "path": "Position"} -> {"ok": true, "value": {"x": 1.5, "y": 0}}
+// {"cmd": "set", "entity": 0, "path": "Position.x", "value": 2.0} -> {"ok": true}
+// {"cmd": "pause"}, {"cmd": "resume"}, {"cmd": "step", "frames": 1} -> {"ok": true}
+// {"cmd": "systems"} -> {"ok": true, "systems": [{"name": "MoveSystem", "micros": 12}]}
+// {"cmd": "dump"} -> {"ok": true, "dump": "..."}
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+	Status,
+	Entities,
+	Get {
+		entity: usize,
+		path: String
+	},
+	Set {
+		entity: usize,
+		path: String,
+		value: serde_json::Value
+	},
+	Pause,
+	Resume,
+	Step {
+		#[serde(default = "default_frames")]
+		frames: u64
+	},
+	Systems,
+	Dump
+}
+
+fn default_frames() -> u64 {
+	1
+}
+
+// A request line and where to send the response line
+type Message = (String, Sender<String>);
+
+// Accepts clients on a localhost port in background threads. The requests
+// are handled on the world's thread in poll(), called by World::update().
+// Dropping the Inspector closes the port and disconnects the clients.
+pub struct Inspector {
+	local_addr: SocketAddr,
+	receiver: Receiver<Message>,
+	shutdown: Arc<AtomicBool>,
+	listener_thread: Option<JoinHandle<()>>
+}
+
+impl Inspector {
+	// Port 0 binds any free port, see get_local_addr()
+	pub fn bind(port: u16) -> io::Result<Self> {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+		// Not blocking so that the listener thread notices the shutdown
+		listener.set_nonblocking(true)?;
+		let local_addr = listener.local_addr()?;
+		let (sender, receiver) = mpsc::channel();
+		let shutdown = Arc::new(AtomicBool::new(false));
+		let listener_shutdown = shutdown.clone();
+		let listener_thread = thread::spawn(move || {
+			listen(listener, sender, &listener_shutdown);
+		});
+		Ok(Inspector {
+			local_addr,
+			receiver,
+			shutdown,
+			listener_thread: Some(listener_thread)
+		})
+	}
+
+	pub fn get_local_addr(&self) -> SocketAddr {
+		self.local_addr
+	}
+
+	// Handles the received requests
+	pub fn poll(&self, world: &mut World) {
+		while let Ok((line, response_sender)) = self.receiver.try_recv() {
+			let response = match serde_json::from_str::<Request>(&line) {
+				Ok(request) => handle_request(world, request),
+				Err(error) => error_response(format!("Invalid request: {}", error))
+			};
+			let _ = response_sender.send(response.to_string());
+		}
+	}
+}
+
+impl Drop for Inspector {
+	fn drop(&mut self) {
+		self.shutdown.store(true, Ordering::Relaxed);
+		// Waits for the listener to be closed, so the port can be bound again
+		if let Some(listener_thread) = self.listener_thread.take() {
+			let _ = listener_thread.join();
+		}
+	}
+}
+
+fn listen(listener: TcpListener, sender: Sender<Message>, shutdown: &AtomicBool) {
+	let mut streams = Vec::new();
+	while !shutdown.load(Ordering::Relaxed) {
+		let stream = match listener.accept() {
+			Ok((stream, _)) => stream,
+			Err(ref error) if error.kind() == ErrorKind::WouldBlock => {
+				thread::sleep(ACCEPT_INTERVAL);
+				continue;
+			},
+			Err(_) => continue
+		};
+		// The stream may inherit the nonblocking mode of the listener
+		if stream.set_nonblocking(false).is_err() {
+			continue;
+		}
+		if let Ok(stream) = stream.try_clone() {
+			streams.push(stream);
+		}
+		let sender = sender.clone();
+		thread::spawn(move || {
+			// Errors mean the client or the Inspector is gone
+			let _ = serve_client(stream, sender);
+		});
+	}
+	// Ends the client threads waiting for requests
+	for stream in streams {
+		let _ = stream.shutdown(Shutdown::Both);
+	}
+}
+
+fn serve_client(stream: TcpStream, sender: Sender<Message>) -> io::Result<()> {
+	let mut writer = stream.try_clone()?;
+	let (response_sender, response_receiver) = mpsc::channel();
+	for line in BufReader::new(stream).lines() {
+		let line = line?;
+		if line.trim().is_empty() {
+			continue;
+		}
+		if sender.send((line, response_sender.clone())).is_err() {
+			break;
+		}
+		// Waits until the world handles the request in World::update()
+		match response_receiver.recv() {
+			Ok(response) => writeln!(writer, "{}", response)?,
+			Err(_) => break
+		};
+	}
+	Ok(())
+}
+
+fn handle_request(world: &mut World, request: Request) -> serde_json::Value {
+	match request {
+		Request::Status => json!({
+			"ok": true,
+			"frame": world.get_frame(),
+			"paused": world.is_paused(),
+			"entities": world.get_entity_ids().len()
+		}),
+		Request::Entities => {
+			let entities = world.get_entity_ids().into_iter().map(|entity_id| json!({
+				"id": entity_id,
+				"components": get_component_names(world, entity_id)
+			})).collect::<Vec<_>>();
+			json!({"ok": true, "entities": entities})
+		},
+		Request::Get { entity, path } => match get_value(world, entity, &path) {
+			Some(value) => json!({"ok": true, "value": value}),
+			None => error_response(format!("Entity {} has no {}", entity, path))
+		},
+		Request::Set { entity, path, value } => {
+			let value = match from_json(&value) {
+				Some(value) => value,
+				None => return error_response(format!("Unsupported value {}", value))
+			};
+			match world.set_field(entity, &path, &value) {
+				true => json!({"ok": true}),
+				false => error_response(format!("Can not set {} of entity {}", path, entity))
+			}
+		},
+		Request::Pause => {
+			world.pause();
+			json!({"ok": true})
+		},
+		Request::Resume => {
+			world.resume();
+			json!({"ok": true})
+		},
+		Request::Step { frames } => {
+			world.step(frames);
+			json!({"ok": true})
+		},
+		Request::Systems => {
+			let systems = world.borrow_system_timings().iter().map(|timing| json!({
+				"name": timing.name,
+				"micros": timing.duration.as_micros() as u64
+			})).collect::<Vec<_>>();
+			json!({"ok": true, "systems": systems})
+		},
+		Request::Dump => json!({"ok": true, "dump": world.dump()})
+	}
+}
+
+fn error_response(message: String) -> serde_json::Value {
+	json!({"ok": false, "error": message})
+}
+
+// Names of the components in the type registry and the dynamic components
+fn get_component_names(world: &World, entity_id: usize) -> Vec<String> {
+	let manager = world.borrow_entity_manager();
+	let mut names = manager.borrow_type_registry().borrow_type_infos().iter()
+		.filter(|type_info| type_info.has_component(manager, entity_id))
+		.map(|type_info| type_info.get_name().to_string())
+		.collect::<Vec<String>>();
+	for component_id in manager.get_dynamic_component_ids() {
+		if manager.has_component_id(entity_id, component_id) {
+			names.push(manager.borrow_dynamic_component_layout(component_id).unwrap().get_name().to_string());
+		}
+	}
+	names
+}
+
+// path is "Component.field", or "Component" for all the fields of
+// a reflectable component
+fn get_value(world: &World, entity_id: usize, path: &str) -> Option<serde_json::Value> {
+	if path.contains('.') {
+		return world.get_field(entity_id, path).map(to_json);
+	}
+	let manager = world.borrow_entity_manager();
+	let type_info = manager.borrow_type_registry().borrow_type_info(path)?;
+	if !type_info.has_component(manager, entity_id) {
+		return None;
+	}
+	match type_info.borrow_fields() {
+		Some(fields) => {
+			let mut values = Map::new();
+			for field in fields.iter() {
+				if let Some(value) = type_info.get_field(manager, entity_id, field.name) {
+					values.insert(field.name.to_string(), to_json(value));
+				}
+			}
+			Some(serde_json::Value::Object(values))
+		},
+		// Not reflectable, e.g. the Debug format
+		None => type_info.format_component(manager, entity_id).map(serde_json::Value::String)
+	}
+}
+
+fn to_json(value: Value) -> serde_json::Value {
+	match value {
+		Value::Bool(value) => serde_json::Value::Bool(value),
+		Value::Int(value) => serde_json::Value::Number(value.into()),
+		// NaN and infinity are not representable in JSON
+		Value::Float(value) => Number::from_f64(value)
+			.map(serde_json::Value::Number)
+			.unwrap_or(serde_json::Value::Null),
+		Value::String(value) => serde_json::Value::String(value)
+	}
+}
+
+fn from_json(value: &serde_json::Value) -> Option<Value> {
+	match value {
+		serde_json::Value::Bool(value) => Some(Value::Bool(*value)),
+		serde_json::Value::Number(number) => match number.as_i64() {
+			Some(value) => Some(Value::Int(value)),
+			None => number.as_f64().map(Value::Float)
+		},
+		serde_json::Value::String(value) => Some(Value::String(value.clone())),
+		_ => None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{BufRead, BufReader, Write};
+	use std::net::TcpStream;
+	use std::thread;
+	use std::time::{Duration, Instant};
+
+	use crate::component::Component;
+	use crate::reflect::{FieldInfo, Reflect, Value};
+	use crate::storage::DenseStorage;
+	use crate::world::World;
+
+	struct Health {
+		value: i64
+	}
+
+	impl Component for Health {
+		type Storage = DenseStorage<Self>;
+	}
+
+	impl Reflect for Health {
+		fn get_field_infos() -> Vec<FieldInfo> {
+			vec![FieldInfo {name: "value", type_name: "i64"}]
+		}
+
+		fn get_field(&self, name: &str) -> Option<Value> {
+			match name {
+				"value" => Some(Value::Int(self.value)),
+				_ => None
+			}
+		}
+
+		fn set_field(&mut self, name: &str, value: &Value) -> bool {
+			match (name, value) {
+				("value", Value::Int(value)) => self.value = *value,
+				_ => return false
+			};
+			true
+		}
+	}
+
+	// Sends the requests from another thread and updates the world until
+	// all the responses are received
+	fn request(world: &mut World, requests: &[&str]) -> Vec<serde_json::Value> {
+		let address = world.start_inspector(0).unwrap();
+		let lines = requests.iter().map(|request| request.to_string()).collect::<Vec<String>>();
+		let client = thread::spawn(move || {
+			let mut stream = TcpStream::connect(address).unwrap();
+			let mut reader = BufReader::new(stream.try_clone().unwrap());
+			lines.iter().map(|line| {
+				writeln!(stream, "{}", line).unwrap();
+				let mut response = String::new();
+				reader.read_line(&mut response).unwrap();
+				serde_json::from_str::<serde_json::Value>(&response).unwrap()
+			}).collect::<Vec<_>>()
+		});
+		let start = Instant::now();
+		while !client.is_finished() {
+			assert!(start.elapsed() < Duration::from_secs(10), "The inspector didn't respond");
+			world.update();
+			thread::sleep(Duration::from_millis(1));
+		}
+		world.stop_inspector();
+		client.join().unwrap()
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut world = World::new();
+		world.register_reflectable_component::<Health>("Health");
+		let entity_id = world.create_entity();
+		world.add_component_to_entity(entity_id, Health { value: 3 });
+
+		let responses = request(&mut world, &[
+			r#"{"cmd": "get", "entity": 0, "path": "Health.value"}"#,
+			r#"{"cmd": "set", "entity": 0, "path": "Health.value", "value": 5}"#,
+			r#"{"cmd": "entities"}"#,
+			r#"{"cmd": "get", "entity": 1, "path": "Health.value"}"#,
+			r#"{"cmd": "unknown"}"#
+		]);
+
+		assert_eq!(responses[0], serde_json::json!({"ok": true, "value": 3}));
+		assert_eq!(responses[1], serde_json::json!({"ok": true}));
+		assert_eq!(responses[2], serde_json::json!({"ok": true, "entities": [{"id": 0, "components": ["Health"]}]}));
+		assert_eq!(responses[3]["ok"], false);
+		assert_eq!(responses[4]["ok"], false);
+		assert_eq!(world.borrow_component::<Health>(entity_id).unwrap().value, 5);
+	}
+
+	#[test]
+	fn restart_on_same_port() {
+		let mut world = World::new();
+		let address = world.start_inspector(0).unwrap();
+		world.stop_inspector();
+		assert_eq!(world.start_inspector(address.port()).unwrap(), address);
+		world.stop_inspector();
+	}
+}
@@ -4,3 +4,35 @@ pub mod entity;
 pub mod entity_manager;
 pub mod component;
 pub mod component_manager;
+pub mod bundle;
+pub mod combinations;
+pub mod commands;
+pub mod hooks;
+pub mod storage;
+pub mod dynamic;
+pub mod archetype;
+pub mod resource_manager;
+pub mod events;
+pub mod reflect;
+pub mod type_registry;
+pub mod profiler;
+pub mod checksum;
+mod dump;
+pub mod hierarchy;
+pub mod transform;
+pub mod spatial;
+pub mod input;
+pub mod time;
+pub mod tween;
+#[cfg(feature = "serde")]
+pub mod serialization;
+#[cfg(feature = "serde")]
+pub mod replay;
+#[cfg(feature = "serde")]
+pub mod scene;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "inspector")]
+pub mod inspector;
+#[cfg(feature = "collision2d")]
+pub mod collision2d;
@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+// Wall times of something measured once per frame, over the last
+// window frames
+pub struct TimingStats {
+	name: String,
+	samples: VecDeque<Duration>
+}
+
+impl TimingStats {
+	fn new(name: &str) -> Self {
+		TimingStats {
+			name: name.to_string(),
+			samples: VecDeque::new()
+		}
+	}
+
+	fn push(&mut self, duration: Duration, window: usize) {
+		if self.samples.len() == window {
+			self.samples.pop_front();
+		}
+		self.samples.push_back(duration);
+	}
+
+	pub fn get_name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn get_sample_count(&self) -> usize {
+		self.samples.len()
+	}
+
+	pub fn get_last(&self) -> Duration {
+		self.samples.back().copied().unwrap_or_default()
+	}
+
+	pub fn get_min(&self) -> Duration {
+		self.samples.iter().min().copied().unwrap_or_default()
+	}
+
+	pub fn get_max(&self) -> Duration {
+		self.samples.iter().max().copied().unwrap_or_default()
+	}
+
+	pub fn get_avg(&self) -> Duration {
+		match self.samples.len() {
+			0 => Duration::ZERO,
+			count => self.samples.iter().sum::<Duration>() / count as u32
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+enum SpanKind {
+	Frame,
+	System(usize), // index in systems
+	Commands(usize) // index in systems
+}
+
+#[derive(Clone, Copy)]
+struct Span {
+	kind: SpanKind,
+	start: Duration, // Since FrameStats::origin
+	duration: Duration
+}
+
+// Start, command flush start and end of a system in a frame
+pub(crate) type SystemSpan = (Instant, Instant, Instant);
+
+// Resource inserted by World::start_profiling(). Holds the wall times of
+// each system and of the command flush after it, and of whole frames,
+// over the last window frames.
+pub struct FrameStats {
+	window: usize,
+	origin: Instant,
+	frame_count: u64,
+	frame: TimingStats,
+	systems: Vec<TimingStats>, // Same order with the systems in World
+	commands: Vec<TimingStats>, // Same order with the systems in World
+	spans: VecDeque<Vec<Span>> // Per frame, for the trace
+}
+
+impl FrameStats {
+	pub fn new(window: usize) -> Self {
+		FrameStats {
+			window: window.max(1),
+			origin: Instant::now(),
+			frame_count: 0,
+			frame: TimingStats::new("frame"),
+			systems: Vec::new(),
+			commands: Vec::new(),
+			spans: VecDeque::new()
+		}
+	}
+
+	pub fn get_window(&self) -> usize {
+		self.window
+	}
+
+	// Frames recorded since the profiling started
+	pub fn get_frame_count(&self) -> u64 {
+		self.frame_count
+	}
+
+	pub fn borrow_frame_stats(&self) -> &TimingStats {
+		&self.frame
+	}
+
+	pub fn borrow_system_stats(&self) -> &Vec<TimingStats> {
+		&self.systems
+	}
+
+	// The command flushes after each system
+	pub fn borrow_command_stats(&self) -> &Vec<TimingStats> {
+		&self.commands
+	}
+
+	// The system names are given in the running order
+	pub(crate) fn record_frame(&mut self, names: &[&str], frame_start: Instant, system_spans: &[SystemSpan]) {
+		// Systems added after the profiling started
+		for name in names[self.systems.len()..].iter() {
+			self.systems.push(TimingStats::new(name));
+			self.commands.push(TimingStats::new(&format!("{} commands", name)));
+		}
+
+		let frame_end = system_spans.last().map(|span| span.2).unwrap_or(frame_start);
+		let mut spans = vec![self.create_span(SpanKind::Frame, frame_start, frame_end)];
+		self.frame.push(frame_end - frame_start, self.window);
+
+		for (index, (start, commands_start, end)) in system_spans.iter().enumerate() {
+			self.systems[index].push(*commands_start - *start, self.window);
+			self.commands[index].push(*end - *commands_start, self.window);
+			spans.push(self.create_span(SpanKind::System(index), *start, *commands_start));
+			spans.push(self.create_span(SpanKind::Commands(index), *commands_start, *end));
+		}
+
+		if self.spans.len() == self.window {
+			self.spans.pop_front();
+		}
+		self.spans.push_back(spans);
+		self.frame_count += 1;
+	}
+
+	fn create_span(&self, kind: SpanKind, start: Instant, end: Instant) -> Span {
+		Span {
+			kind,
+			start: start.saturating_duration_since(self.origin),
+			duration: end - start
+		}
+	}
+
+	// Chrome trace event JSON of the last window frames. Open it in
+	// chrome://tracing or https://ui.perfetto.dev
+	pub fn write_chrome_trace<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+		writeln!(writer, "{{\"traceEvents\":[")?;
+		let first_frame = self.frame_count - self.spans.len() as u64;
+		let mut first = true;
+		for (frame_index, spans) in self.spans.iter().enumerate() {
+			for span in spans.iter() {
+				let (name, category) = match span.kind {
+					SpanKind::Frame => (self.frame.get_name(), "frame"),
+					SpanKind::System(index) => (self.systems[index].get_name(), "system"),
+					SpanKind::Commands(index) => (self.commands[index].get_name(), "commands")
+				};
+				if !first {
+					writeln!(writer, ",")?;
+				}
+				first = false;
+				write!(
+					writer,
+					"{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":1,\"args\":{{\"frame\":{}}}}}",
+					escape_json(name),
+					category,
+					span.start.as_secs_f64() * 1_000_000.0,
+					span.duration.as_secs_f64() * 1_000_000.0,
+					first_frame + frame_index as u64
+				)?;
+			}
+		}
+		writeln!(writer, "\n]}}")
+	}
+
+	pub fn to_chrome_trace(&self) -> String {
+		let mut bytes = Vec::new();
+		// Writing to a Vec never fails
+		self.write_chrome_trace(&mut bytes).unwrap();
+		String::from_utf8(bytes).unwrap()
+	}
+}
+
+fn escape_json(text: &str) -> String {
+	let mut escaped = String::new();
+	for c in text.chars() {
+		match c {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+			c => escaped.push(c)
+		};
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread;
+	use std::time::{Duration, Instant};
+
+	use crate::entity_manager::{EntityIdAccessor, EntityManager};
+	use crate::system::System;
+	use crate::world::World;
+	use super::FrameStats;
+
+	fn millis(millis: u64) -> Duration {
+		Duration::from_millis(millis)
+	}
+
+	#[test]
+	fn spans() {
+		let mut stats = FrameStats::new(2);
+		for frame in 0..3 {
+			let start = Instant::now() + millis(frame * 10);
+			let spans = [
+				(start, start + millis(1), start + millis(3)),
+				(start + millis(3), start + millis(4 + frame), start + millis(5 + frame))
+			];
+			stats.record_frame(&["Move", "Spawn"], start, &spans);
+		}
+
+		assert_eq!(stats.get_frame_count(), 3);
+		let frame = stats.borrow_frame_stats();
+		assert_eq!(frame.get_sample_count(), 2);
+		assert_eq!((frame.get_min(), frame.get_max(), frame.get_last()), (millis(6), millis(7), millis(7)));
+		let spawn = &stats.borrow_system_stats()[1];
+		assert_eq!(spawn.get_name(), "Spawn");
+		assert_eq!(spawn.get_avg(), Duration::from_micros(2500));
+		assert_eq!(stats.borrow_command_stats()[0].get_name(), "Move commands");
+		assert_eq!(stats.borrow_command_stats()[0].get_last(), millis(2));
+
+		// A frame span and two spans per system for each of the last two frames
+		let trace = serde_json::from_str::<serde_json::Value>(&stats.to_chrome_trace()).unwrap();
+		let events = trace["traceEvents"].as_array().unwrap();
+		assert_eq!(events.len(), 10);
+		assert_eq!(events[0]["name"], "frame");
+		assert_eq!(events[0]["args"]["frame"], 1);
+		assert_eq!(events[3]["name"], "Spawn");
+		assert_eq!(events[3]["dur"], 2000.0);
+		assert_eq!(events[5]["args"]["frame"], 2);
+	}
+
+	struct SleepSystem;
+
+	impl System for SleepSystem {
+		fn update(&mut self, _manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+			thread::sleep(millis(2));
+		}
+	}
+
+	#[test]
+	fn world_profiling() {
+		let mut world = World::new();
+		world.add_system(SleepSystem);
+		world.update();
+		world.start_profiling(8);
+		world.update();
+		world.update();
+
+		let stats = world.stop_profiling().unwrap();
+		assert_eq!(stats.get_frame_count(), 2);
+		let system = &stats.borrow_system_stats()[0];
+		assert_eq!(system.get_name(), "SleepSystem");
+		assert!(system.get_min() >= millis(2));
+		assert!(stats.borrow_frame_stats().get_min() >= system.get_min());
+		assert!(world.borrow_frame_stats().is_none());
+	}
+}
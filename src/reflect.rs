@@ -0,0 +1,124 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+#[cfg(feature = "derive")]
+pub use ecs_rust_derive::Reflect;
+
+// Dynamically typed field value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	Bool(bool),
+	Int(i64),
+	Float(f64),
+	String(String)
+}
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Value::Bool(value) => write!(f, "{}", value),
+			Value::Int(value) => write!(f, "{}", value),
+			Value::Float(value) => write!(f, "{}", value),
+			Value::String(value) => write!(f, "{:?}", value)
+		}
+	}
+}
+
+// Field types which can be read and written as Value
+pub trait ReflectValue: Sized {
+	fn to_value(&self) -> Value;
+	// None if the value has a different type or is out of range.
+	// Ints are accepted for float fields.
+	fn from_value(value: &Value) -> Option<Self>;
+}
+
+macro_rules! impl_reflect_value_for_int {
+	($($t:ty),*) => {
+		$(
+			impl ReflectValue for $t {
+				fn to_value(&self) -> Value {
+					Value::Int(*self as i64)
+				}
+
+				fn from_value(value: &Value) -> Option<Self> {
+					match value {
+						Value::Int(value) => <$t>::try_from(*value).ok(),
+						_ => None
+					}
+				}
+			}
+		)*
+	};
+}
+
+impl_reflect_value_for_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl ReflectValue for f32 {
+	fn to_value(&self) -> Value {
+		Value::Float(*self as f64)
+	}
+
+	fn from_value(value: &Value) -> Option<Self> {
+		match value {
+			Value::Float(value) => Some(*value as f32),
+			Value::Int(value) => Some(*value as f32),
+			_ => None
+		}
+	}
+}
+
+impl ReflectValue for f64 {
+	fn to_value(&self) -> Value {
+		Value::Float(*self)
+	}
+
+	fn from_value(value: &Value) -> Option<Self> {
+		match value {
+			Value::Float(value) => Some(*value),
+			Value::Int(value) => Some(*value as f64),
+			_ => None
+		}
+	}
+}
+
+impl ReflectValue for bool {
+	fn to_value(&self) -> Value {
+		Value::Bool(*self)
+	}
+
+	fn from_value(value: &Value) -> Option<Self> {
+		match value {
+			Value::Bool(value) => Some(*value),
+			_ => None
+		}
+	}
+}
+
+impl ReflectValue for String {
+	fn to_value(&self) -> Value {
+		Value::String(self.clone())
+	}
+
+	fn from_value(value: &Value) -> Option<Self> {
+		match value {
+			Value::String(value) => Some(value.clone()),
+			_ => None
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct FieldInfo {
+	pub name: &'static str,
+	pub type_name: &'static str
+}
+
+// Field access by name for components whose type isn't known at compile
+// time, e.g. in inspectors and scripts. Implement by hand or with
+// #[derive(Reflect)] of the derive feature.
+pub trait Reflect {
+	fn get_field_infos() -> Vec<FieldInfo> where Self: Sized;
+	fn get_field(&self, name: &str) -> Option<Value>;
+	// Returns false if the field doesn't exist or the value type doesn't match
+	fn set_field(&mut self, name: &str, value: &Value) -> bool;
+}
@@ -0,0 +1,556 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, SerializeStruct, SerializeTuple};
+
+use super::entity_manager::EntityManager;
+use super::world::World;
+
+type CloneFn = fn(&dyn Any) -> Box<dyn Any>;
+type SerializeFn = fn(&dyn Any) -> &dyn erased_serde::Serialize;
+type DeserializeFn = fn(&mut dyn erased_serde::Deserializer) -> Result<Box<dyn Any>, erased_serde::Error>;
+type ApplyFn = fn(&mut EntityManager, Box<dyn Any>);
+
+struct InputType {
+	name: String,
+	clone: CloneFn,
+	serialize: SerializeFn,
+	deserialize: DeserializeFn,
+	apply: ApplyFn
+}
+
+// Resources and events which are injected into the world from outside,
+// keyed by stable names so that they can be written into replay files.
+pub struct InputRegistry {
+	inputs: Vec<InputType>,
+	name_map: HashMap<String, usize>, // name -> index in inputs
+	type_map: HashMap<TypeId, usize> // TypeId -> index in inputs
+}
+
+impl Default for InputRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl InputRegistry {
+	pub fn new() -> Self {
+		InputRegistry {
+			inputs: Vec::new(),
+			name_map: HashMap::new(),
+			type_map: HashMap::new()
+		}
+	}
+
+	pub fn register_resource<T>(&mut self, name: &str) -> &mut Self
+	where T: 'static + Clone + Serialize + for<'de> Deserialize<'de> {
+		self.register::<T>(name, apply_resource::<T>)
+	}
+
+	pub fn register_event<T>(&mut self, name: &str) -> &mut Self
+	where T: 'static + Clone + Serialize + for<'de> Deserialize<'de> {
+		self.register::<T>(name, apply_event::<T>)
+	}
+
+	fn register<T>(&mut self, name: &str, apply: ApplyFn) -> &mut Self
+	where T: 'static + Clone + Serialize + for<'de> Deserialize<'de> {
+		let type_id = TypeId::of::<T>();
+		if self.type_map.contains_key(&type_id) || self.name_map.contains_key(name) {
+			// @TODO: Better error handling
+			println!("Input {} is already registered", name);
+			return self;
+		}
+		self.inputs.push(InputType {
+			name: name.to_string(),
+			clone: clone_input::<T>,
+			serialize: serialize_input::<T>,
+			deserialize: deserialize_input::<T>,
+			apply
+		});
+		let index = self.inputs.len() - 1;
+		self.name_map.insert(name.to_string(), index);
+		self.type_map.insert(type_id, index);
+		self
+	}
+
+	fn find_index<T: 'static>(&self) -> Option<usize> {
+		self.type_map.get(&TypeId::of::<T>()).copied()
+	}
+}
+
+fn clone_input<T: 'static + Clone>(input: &dyn Any) -> Box<dyn Any> {
+	Box::new(input.downcast_ref::<T>().unwrap().clone())
+}
+
+fn serialize_input<T: 'static + Serialize>(input: &dyn Any) -> &dyn erased_serde::Serialize {
+	input.downcast_ref::<T>().unwrap()
+}
+
+fn deserialize_input<T>(deserializer: &mut dyn erased_serde::Deserializer) -> Result<Box<dyn Any>, erased_serde::Error>
+where T: 'static + for<'de> Deserialize<'de> {
+	Ok(Box::new(erased_serde::deserialize::<T>(deserializer)?))
+}
+
+fn apply_resource<T: 'static>(manager: &mut EntityManager, input: Box<dyn Any>) {
+	manager.insert_resource(*input.downcast::<T>().unwrap());
+}
+
+fn apply_event<T: 'static>(manager: &mut EntityManager, input: Box<dyn Any>) {
+	manager.send_event(*input.downcast::<T>().unwrap());
+}
+
+struct ReplayFrame {
+	inputs: Vec<(usize, Box<dyn Any>)>, // (index in InputRegistry, input)
+	checksum: Option<u64> // World checksum after the update
+}
+
+// Inputs injected before each World::update() call, in the order they
+// were injected.
+pub struct Replay {
+	frames: Vec<ReplayFrame>
+}
+
+impl Default for Replay {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Replay {
+	pub fn new() -> Self {
+		Replay {
+			frames: Vec::new()
+		}
+	}
+
+	pub fn get_frame_count(&self) -> usize {
+		self.frames.len()
+	}
+
+	pub fn get_checksum(&self, frame: usize) -> Option<u64> {
+		self.frames.get(frame).and_then(|frame| frame.checksum)
+	}
+}
+
+pub(crate) struct Recorder {
+	replay: Replay,
+	pending_inputs: Vec<(usize, Box<dyn Any>)>,
+	checksum_fn: Option<fn(&World) -> u64>
+}
+
+impl Recorder {
+	pub(crate) fn new(checksum_fn: Option<fn(&World) -> u64>) -> Self {
+		Recorder {
+			replay: Replay::new(),
+			pending_inputs: Vec::new(),
+			checksum_fn
+		}
+	}
+
+	pub(crate) fn record<T: 'static>(&mut self, registry: &InputRegistry, input: &T) {
+		if let Some(index) = registry.find_index::<T>() {
+			self.pending_inputs.push((index, (registry.inputs[index].clone)(input)));
+		}
+	}
+
+	pub(crate) fn end_frame(&mut self, world: &World) {
+		self.replay.frames.push(ReplayFrame {
+			inputs: self.pending_inputs.drain(..).collect(),
+			checksum: self.checksum_fn.map(|checksum_fn| checksum_fn(world))
+		});
+	}
+
+	// Inputs injected after the last update are dropped
+	pub(crate) fn finish(self) -> Replay {
+		self.replay
+	}
+}
+
+// Injects the recorded inputs of a frame into the manager
+pub(crate) fn apply_inputs(manager: &mut EntityManager, registry: &InputRegistry, replay: &Replay, frame: usize) {
+	for (index, input) in replay.frames[frame].inputs.iter() {
+		let input_type = &registry.inputs[*index];
+		(input_type.apply)(manager, (input_type.clone)(input.as_ref()));
+	}
+}
+
+#[derive(Debug)]
+pub struct ReplayDivergence {
+	pub frame: usize,
+	pub expected: u64,
+	pub actual: u64
+}
+
+impl fmt::Display for ReplayDivergence {
+	fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		write!(formatter, "Replay diverged at frame {}, expected checksum {:x} but got {:x}",
+			self.frame, self.expected, self.actual)
+	}
+}
+
+impl std::error::Error for ReplayDivergence {}
+
+// Serializes a replay as a sequence of
+// { inputs: [(name, input), ...], checksum }.
+pub struct ReplaySerializer<'a> {
+	replay: &'a Replay,
+	registry: &'a InputRegistry
+}
+
+impl<'a> ReplaySerializer<'a> {
+	pub fn new(replay: &'a Replay, registry: &'a InputRegistry) -> Self {
+		ReplaySerializer {
+			replay,
+			registry
+		}
+	}
+}
+
+impl<'a> Serialize for ReplaySerializer<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut seq = serializer.serialize_seq(Some(self.replay.frames.len()))?;
+		for frame in self.replay.frames.iter() {
+			seq.serialize_element(&FrameSerializer {
+				frame,
+				registry: self.registry
+			})?;
+		}
+		seq.end()
+	}
+}
+
+struct FrameSerializer<'a> {
+	frame: &'a ReplayFrame,
+	registry: &'a InputRegistry
+}
+
+impl<'a> Serialize for FrameSerializer<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut state = serializer.serialize_struct("Frame", 2)?;
+		state.serialize_field("inputs", &InputsSerializer {
+			frame: self.frame,
+			registry: self.registry
+		})?;
+		state.serialize_field("checksum", &self.frame.checksum)?;
+		state.end()
+	}
+}
+
+struct InputsSerializer<'a> {
+	frame: &'a ReplayFrame,
+	registry: &'a InputRegistry
+}
+
+impl<'a> Serialize for InputsSerializer<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut seq = serializer.serialize_seq(Some(self.frame.inputs.len()))?;
+		for (index, input) in self.frame.inputs.iter() {
+			let input_type = &self.registry.inputs[*index];
+			seq.serialize_element(&InputSerializer {
+				name: &input_type.name,
+				input: (input_type.serialize)(input.as_ref())
+			})?;
+		}
+		seq.end()
+	}
+}
+
+struct InputSerializer<'a> {
+	name: &'a str,
+	input: &'a dyn erased_serde::Serialize
+}
+
+impl<'a> Serialize for InputSerializer<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut tuple = serializer.serialize_tuple(2)?;
+		tuple.serialize_element(self.name)?;
+		tuple.serialize_element(self.input)?;
+		tuple.end()
+	}
+}
+
+// Counterpart of ReplaySerializer
+pub struct ReplayDeserializer<'a> {
+	registry: &'a InputRegistry
+}
+
+impl<'a> ReplayDeserializer<'a> {
+	pub fn new(registry: &'a InputRegistry) -> Self {
+		ReplayDeserializer {
+			registry
+		}
+	}
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for ReplayDeserializer<'a> {
+	type Value = Replay;
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Replay, D::Error> {
+		deserializer.deserialize_seq(self)
+	}
+}
+
+impl<'de, 'a> Visitor<'de> for ReplayDeserializer<'a> {
+	type Value = Replay;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a sequence of replay frames")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Replay, A::Error> {
+		let mut replay = Replay::new();
+		while let Some(frame) = seq.next_element_seed(FrameDeserializer {
+			registry: self.registry
+		})? {
+			replay.frames.push(frame);
+		}
+		Ok(replay)
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum FrameField {
+	Inputs,
+	Checksum
+}
+
+struct FrameDeserializer<'a> {
+	registry: &'a InputRegistry
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for FrameDeserializer<'a> {
+	type Value = ReplayFrame;
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<ReplayFrame, D::Error> {
+		deserializer.deserialize_struct("Frame", &["inputs", "checksum"], self)
+	}
+}
+
+impl<'de, 'a> Visitor<'de> for FrameDeserializer<'a> {
+	type Value = ReplayFrame;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a replay frame")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ReplayFrame, A::Error> {
+		let inputs = seq.next_element_seed(InputsDeserializer {
+			registry: self.registry
+		})?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+		let checksum = seq.next_element::<Option<u64>>()?
+			.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+		Ok(ReplayFrame {
+			inputs,
+			checksum
+		})
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ReplayFrame, A::Error> {
+		let mut inputs = None;
+		let mut checksum = None;
+		while let Some(key) = map.next_key::<FrameField>()? {
+			match key {
+				FrameField::Inputs => {
+					if inputs.is_some() {
+						return Err(de::Error::duplicate_field("inputs"));
+					}
+					inputs = Some(map.next_value_seed(InputsDeserializer {
+						registry: self.registry
+					})?);
+				},
+				FrameField::Checksum => {
+					if checksum.is_some() {
+						return Err(de::Error::duplicate_field("checksum"));
+					}
+					checksum = Some(map.next_value::<Option<u64>>()?);
+				}
+			}
+		}
+		Ok(ReplayFrame {
+			inputs: inputs.ok_or_else(|| de::Error::missing_field("inputs"))?,
+			checksum: checksum.unwrap_or(None)
+		})
+	}
+}
+
+struct InputsDeserializer<'a> {
+	registry: &'a InputRegistry
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for InputsDeserializer<'a> {
+	type Value = Vec<(usize, Box<dyn Any>)>;
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+		deserializer.deserialize_seq(self)
+	}
+}
+
+impl<'de, 'a> Visitor<'de> for InputsDeserializer<'a> {
+	type Value = Vec<(usize, Box<dyn Any>)>;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a sequence of inputs")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let mut inputs = Vec::new();
+		while let Some(input) = seq.next_element_seed(InputDeserializer {
+			registry: self.registry
+		})? {
+			inputs.push(input);
+		}
+		Ok(inputs)
+	}
+}
+
+struct InputDeserializer<'a> {
+	registry: &'a InputRegistry
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for InputDeserializer<'a> {
+	type Value = (usize, Box<dyn Any>);
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+		deserializer.deserialize_tuple(2, self)
+	}
+}
+
+impl<'de, 'a> Visitor<'de> for InputDeserializer<'a> {
+	type Value = (usize, Box<dyn Any>);
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a (name, input) pair")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let name = seq.next_element::<String>()?
+			.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+		let index = *self.registry.name_map.get(&name)
+			.ok_or_else(|| de::Error::custom(format!("Unknown input {}", name)))?;
+		let input = seq.next_element_seed(InputValueDeserializer {
+			input_type: &self.registry.inputs[index]
+		})?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+		Ok((index, input))
+	}
+}
+
+struct InputValueDeserializer<'a> {
+	input_type: &'a InputType
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for InputValueDeserializer<'a> {
+	type Value = Box<dyn Any>;
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Box<dyn Any>, D::Error> {
+		let mut erased = <dyn erased_serde::Deserializer>::erase(deserializer);
+		(self.input_type.deserialize)(&mut erased).map_err(de::Error::custom)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::{Deserialize, Serialize};
+
+	use crate::checksum::{StableHash, StableHasher};
+	use crate::component::Component;
+	use crate::entity_manager::{EntityIdAccessor, EntityManager};
+	use crate::storage::DenseStorage;
+	use crate::system::System;
+	use crate::world::World;
+	use super::Replay;
+
+	struct Position {
+		x: i64
+	}
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	impl StableHash for Position {
+		fn stable_hash(&self, hasher: &mut StableHasher) {
+			self.x.stable_hash(hasher);
+		}
+	}
+
+	#[derive(Clone, Serialize, Deserialize)]
+	struct Push {
+		dx: i64
+	}
+
+	// Moves the entities by the pushes, scaled by the entity id so that
+	// the entities diverge
+	struct PushSystem;
+
+	impl System for PushSystem {
+		fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+			let dx = manager.borrow_events::<Push>().unwrap().borrow_events().iter()
+				.map(|push| push.dx)
+				.sum::<i64>();
+			manager.for_each_mut::<Position, _>(|entity_id, position| {
+				position.x += dx * (entity_id as i64 + 1);
+			});
+		}
+	}
+
+	fn create_world(x: i64) -> World {
+		let mut world = World::new();
+		world
+			.register_hashable_component::<Position>("Position")
+			.register_input_event::<Push>("Push")
+			.add_system(PushSystem);
+		for _ in 0..3 {
+			let entity_id = world.create_entity();
+			world.add_component_to_entity(entity_id, Position { x });
+		}
+		world
+	}
+
+	fn record() -> (World, Replay) {
+		let mut world = create_world(0);
+		world.start_recording(Some(World::checksum));
+		for frame in 0..10 {
+			if frame % 3 != 0 {
+				world.send_event(Push { dx: frame });
+			}
+			world.update();
+		}
+		let replay = world.stop_recording().unwrap();
+		(world, replay)
+	}
+
+	#[test]
+	fn replay_gives_same_checksum() {
+		let (recorded, replay) = record();
+		assert_eq!(replay.get_frame_count(), 10);
+
+		let mut world = create_world(0);
+		assert!(world.replay(&replay, Some(World::checksum)).is_ok());
+		assert_eq!(world.checksum(), recorded.checksum());
+		assert_eq!(world.get_frame(), recorded.get_frame());
+	}
+
+	#[test]
+	fn saved_replay_gives_same_checksum() {
+		let (recorded, replay) = record();
+		let mut json = Vec::new();
+		recorded.save_replay(&replay, &mut serde_json::Serializer::new(&mut json)).unwrap();
+
+		let mut world = create_world(0);
+		let replay = world.load_replay(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+		assert!(world.replay(&replay, Some(World::checksum)).is_ok());
+		assert_eq!(world.checksum(), recorded.checksum());
+	}
+
+	#[test]
+	fn replay_detects_divergence() {
+		let (_, replay) = record();
+		let mut world = create_world(1);
+		let divergence = world.replay(&replay, Some(World::checksum)).unwrap_err();
+		assert_eq!(divergence.frame, 0);
+	}
+}
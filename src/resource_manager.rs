@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::any::{Any, TypeId};
+
+type CloneFn = fn(&dyn Any) -> Box<dyn Any>;
+
+// Resources are singleton values which aren't tied to any entity,
+// e.g. time, user input, or game settings.
+pub struct ResourceManager {
+	resource_map: HashMap<TypeId, Box<dyn Any>>,
+	clone_fn_map: HashMap<TypeId, CloneFn> // Only cloneable resources
+}
+
+impl Default for ResourceManager {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ResourceManager {
+	pub fn new() -> Self {
+		ResourceManager {
+			resource_map: HashMap::new(),
+			clone_fn_map: HashMap::new()
+		}
+	}
+
+	// Replaces the resource if the same type is already inserted
+	pub fn insert<T: 'static>(&mut self, resource: T) {
+		let type_id = TypeId::of::<T>();
+		self.resource_map.insert(type_id, Box::new(resource));
+		self.clone_fn_map.remove(&type_id);
+	}
+
+	// Cloneable resources are captured by world snapshots
+	pub fn insert_cloneable<T: 'static + Clone>(&mut self, resource: T) {
+		let type_id = TypeId::of::<T>();
+		self.resource_map.insert(type_id, Box::new(resource));
+		self.clone_fn_map.insert(type_id, clone_resource::<T>);
+	}
+
+	pub fn remove<T: 'static>(&mut self) -> Option<T> {
+		let type_id = TypeId::of::<T>();
+		self.clone_fn_map.remove(&type_id);
+		self.resource_map.remove(&type_id)
+			.map(|resource| *resource.downcast::<T>().unwrap())
+	}
+
+	pub fn has<T: 'static>(&self) -> bool {
+		self.resource_map.contains_key(&TypeId::of::<T>())
+	}
+
+	pub fn borrow<T: 'static>(&self) -> Option<&T> {
+		self.resource_map.get(&TypeId::of::<T>())
+			.map(|resource| resource.downcast_ref::<T>().unwrap())
+	}
+
+	pub fn borrow_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		self.resource_map.get_mut(&TypeId::of::<T>())
+			.map(|resource| resource.downcast_mut::<T>().unwrap())
+	}
+
+	// Returns a copy holding only the cloneable resources
+	pub fn clone_cloneables(&self) -> Self {
+		let mut resource_map = HashMap::new();
+		for (type_id, clone_fn) in self.clone_fn_map.iter() {
+			let resource = self.resource_map.get(type_id).unwrap();
+			resource_map.insert(*type_id, clone_fn(resource.as_ref()));
+		}
+		ResourceManager {
+			resource_map,
+			clone_fn_map: self.clone_fn_map.clone()
+		}
+	}
+
+	// Overwrites the cloneable resources with the ones in src.
+	// Non-cloneable resources are left as they are.
+	pub fn restore_cloneables(&mut self, src: &Self) {
+		for type_id in self.clone_fn_map.keys() {
+			self.resource_map.remove(type_id);
+		}
+		for (type_id, clone_fn) in src.clone_fn_map.iter() {
+			let resource = src.resource_map.get(type_id).unwrap();
+			self.resource_map.insert(*type_id, clone_fn(resource.as_ref()));
+		}
+		self.clone_fn_map = src.clone_fn_map.clone();
+	}
+}
+
+fn clone_resource<T: 'static + Clone>(resource: &dyn Any) -> Box<dyn Any> {
+	Box::new(resource.downcast_ref::<T>().unwrap().clone())
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use super::component::Component;
+use super::storage::DenseStorage;
+
+// Identifies an instance of a scene loaded with World::load_scene().
+// The same scene can be loaded several times, e.g. rooms.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SceneInstanceId(usize);
+
+// Added to the entities created by World::load_scene()
+#[derive(Clone, Debug)]
+pub struct SceneMember {
+	instance_id: SceneInstanceId
+}
+
+impl Component for SceneMember {
+	type Storage = DenseStorage<Self>;
+}
+
+impl SceneMember {
+	pub(crate) fn new(instance_id: SceneInstanceId) -> Self {
+		SceneMember {
+			instance_id
+		}
+	}
+
+	pub fn get_instance_id(&self) -> SceneInstanceId {
+		self.instance_id
+	}
+}
+
+// Saved id -> new id maps of the loaded scene instances
+pub(crate) struct SceneInstances {
+	next_id: usize,
+	entity_maps: HashMap<SceneInstanceId, HashMap<usize, usize>>
+}
+
+impl SceneInstances {
+	pub(crate) fn new() -> Self {
+		SceneInstances {
+			next_id: 0,
+			entity_maps: HashMap::new()
+		}
+	}
+
+	pub(crate) fn create_instance_id(&mut self) -> SceneInstanceId {
+		self.next_id += 1;
+		SceneInstanceId(self.next_id - 1)
+	}
+
+	pub(crate) fn insert(&mut self, instance_id: SceneInstanceId, entity_map: HashMap<usize, usize>) {
+		self.entity_maps.insert(instance_id, entity_map);
+	}
+
+	pub(crate) fn remove(&mut self, instance_id: SceneInstanceId) -> Option<HashMap<usize, usize>> {
+		self.entity_maps.remove(&instance_id)
+	}
+
+	pub(crate) fn borrow_entity_map(&self, instance_id: SceneInstanceId) -> Option<&HashMap<usize, usize>> {
+		self.entity_maps.get(&instance_id)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::hierarchy::{Children, Parent};
+	use crate::world::World;
+
+	fn create_world() -> World {
+		let mut world = World::new();
+		world.register_serializable_hierarchy();
+		world
+	}
+
+	#[test]
+	fn load_scene_maps_hierarchy() {
+		// Room 0 with children 1 and 2
+		let mut saved = create_world();
+		let room_id = saved.create_entity();
+		for _ in 0..2 {
+			let child_id = saved.create_entity();
+			saved.set_parent(child_id, room_id);
+		}
+		let mut json = Vec::new();
+		saved.save_scene(&saved.get_entity_ids(), &mut serde_json::Serializer::new(&mut json)).unwrap();
+
+		let mut world = create_world();
+		world.create_entity();
+		let instance_ids = [
+			world.load_scene(&mut serde_json::Deserializer::from_slice(&json)).unwrap(),
+			world.load_scene(&mut serde_json::Deserializer::from_slice(&json)).unwrap()
+		];
+
+		for instance_id in instance_ids.iter() {
+			let entity_map = world.borrow_scene_entity_map(*instance_id).unwrap().clone();
+			let loaded_room_id = entity_map[&room_id];
+			assert_ne!(loaded_room_id, room_id);
+			let child_ids = world.borrow_component::<Children>(loaded_room_id).unwrap().borrow_entity_ids().clone();
+			assert_eq!(child_ids, vec![entity_map[&1], entity_map[&2]]);
+			for child_id in child_ids {
+				assert_eq!(world.borrow_component::<Parent>(child_id).unwrap().get(), loaded_room_id);
+			}
+		}
+
+		// The loaded hierarchy is a live one
+		world.unload_scene(instance_ids[0]);
+		let room_id = world.borrow_scene_entity_map(instance_ids[1]).unwrap()[&room_id];
+		world.remove_entity(room_id);
+		assert!(world.get_entity_ids().iter()
+			.all(|entity_id| world.borrow_component::<Parent>(*entity_id).is_none()));
+	}
+}
@@ -0,0 +1,385 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, AST};
+
+use super::dynamic::ComponentId;
+use super::entity_manager::{EntityIdAccessor, EntityManager};
+use super::reflect::Value;
+use super::system::System;
+
+#[derive(Clone, Debug)]
+pub struct ScriptError {
+	pub system_name: String,
+	pub message: String
+}
+
+impl fmt::Display for ScriptError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Script error in {}: {}", self.system_name, self.message)
+	}
+}
+
+impl Error for ScriptError {}
+
+// Resource holding the errors of the script systems in their last
+// updates, so it doesn't grow with a script failing every update. A
+// failing script stops at the error and the following systems run as usual.
+pub struct ScriptErrors {
+	errors: Vec<ScriptError>
+}
+
+impl Default for ScriptErrors {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ScriptErrors {
+	pub fn new() -> Self {
+		ScriptErrors {
+			errors: Vec::new()
+		}
+	}
+
+	pub fn borrow_errors(&self) -> &Vec<ScriptError> {
+		&self.errors
+	}
+
+	pub fn clear(&mut self) {
+		self.errors.clear();
+	}
+
+	fn remove_system_errors(&mut self, system_name: &str) {
+		self.errors.retain(|error| error.system_name != system_name);
+	}
+}
+
+// Resource holding values shared by scripts and Rust code, read and
+// written with get_resource() and set_resource() in scripts
+pub struct ScriptResources {
+	values: HashMap<String, Dynamic>
+}
+
+impl Default for ScriptResources {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ScriptResources {
+	pub fn new() -> Self {
+		ScriptResources {
+			values: HashMap::new()
+		}
+	}
+
+	pub fn get(&self, name: &str) -> Option<&Dynamic> {
+		self.values.get(name)
+	}
+
+	pub fn set<T: Into<Dynamic>>(&mut self, name: &str, value: T) {
+		self.values.insert(name.to_string(), value.into());
+	}
+
+	pub fn remove(&mut self, name: &str) -> Option<Dynamic> {
+		self.values.remove(name)
+	}
+}
+
+type Shared = Rc<RefCell<EntityManager>>;
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// A system written in Rhai. The script runs once per World::update().
+// Components are accessed by their type registry names, so they need to
+// be registered as reflectable, or constructible to be created.
+//
+// let ids = query(["Position", "Velocity"]);
+// for id in ids {
+//     set(id, "Position.x", get(id, "Position.x") + get(id, "Velocity.x"));
+// }
+// if get_resource("score") > 10 { create_entity(#{ Position: #{ x: 0.0, y: 0.0 } }); }
+// remove_entity(ids[0]);
+//
+// create_entity() and remove_entity() are applied as commands after the script returns.
+pub struct ScriptSystem {
+	name: String,
+	engine: Engine,
+	ast: AST,
+	path: Option<PathBuf>, // Only for scripts loaded from files
+	modified: Option<SystemTime>,
+	// Kept until the script is reloaded successfully
+	reload_error: Option<ScriptError>,
+	// The messages of the last update, to print an error only once while
+	// the script keeps failing with it
+	printed_messages: Vec<String>,
+	// The world's EntityManager is swapped in while the script runs so
+	// that the registered functions can access it
+	shared: Shared
+}
+
+impl ScriptSystem {
+	pub fn new(name: &str, source: &str) -> Result<Self, ScriptError> {
+		let shared = Rc::new(RefCell::new(EntityManager::new()));
+		let engine = create_engine(&shared);
+		let ast = compile(&engine, name, source)?;
+		Ok(ScriptSystem {
+			name: name.to_string(),
+			engine,
+			ast,
+			path: None,
+			modified: None,
+			reload_error: None,
+			printed_messages: Vec::new(),
+			shared
+		})
+	}
+
+	// The script is reloaded when the file is modified, so it can be edited
+	// while the application is running
+	pub fn from_file<P: AsRef<Path>>(name: &str, path: P) -> Result<Self, ScriptError> {
+		let path = path.as_ref().to_path_buf();
+		let source = read_file(name, &path)?;
+		let mut system = Self::new(name, &source)?;
+		system.modified = get_modified(&path);
+		system.path = Some(path);
+		Ok(system)
+	}
+
+	// For registering additional functions or setting limits
+	pub fn borrow_engine_mut(&mut self) -> &mut Engine {
+		&mut self.engine
+	}
+
+	fn reload_if_modified(&mut self) {
+		let path = match self.path.as_ref() {
+			Some(path) => path,
+			None => return
+		};
+		let modified = get_modified(path);
+		if modified == self.modified {
+			return;
+		}
+		self.modified = modified;
+		let result = read_file(&self.name, path)
+			.and_then(|source| compile(&self.engine, &self.name, &source));
+		match result {
+			Ok(ast) => {
+				self.ast = ast;
+				self.reload_error = None;
+			},
+			// The previous script keeps running
+			Err(error) => self.reload_error = Some(error)
+		};
+	}
+
+	fn report_errors(&mut self, manager: &mut EntityManager, errors: Vec<ScriptError>) {
+		let messages = errors.iter().map(|error| error.message.clone()).collect::<Vec<String>>();
+		for error in errors.iter() {
+			if !self.printed_messages.contains(&error.message) {
+				// @TODO: Better error handling
+				println!("{}", error);
+			}
+		}
+		self.printed_messages = messages;
+
+		if errors.is_empty() {
+			return;
+		}
+		if !manager.has_resource::<ScriptErrors>() {
+			manager.insert_resource(ScriptErrors::new());
+		}
+		manager.borrow_resource_mut::<ScriptErrors>().unwrap().errors.extend(errors);
+	}
+}
+
+impl System for ScriptSystem {
+	fn get_name(&self) -> &str {
+		&self.name
+	}
+
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		if let Some(errors) = manager.borrow_resource_mut::<ScriptErrors>() {
+			errors.remove_system_errors(&self.name);
+		}
+
+		self.reload_if_modified();
+		// Pushed every update since the errors of the last update were
+		// removed above
+		let mut errors = self.reload_error.iter().cloned().collect::<Vec<ScriptError>>();
+
+		std::mem::swap(manager, &mut *self.shared.borrow_mut());
+		let result = self.engine.run_ast(&self.ast);
+		std::mem::swap(manager, &mut *self.shared.borrow_mut());
+
+		if let Err(error) = result {
+			errors.push(ScriptError {
+				system_name: self.name.clone(),
+				message: error.to_string()
+			});
+		}
+
+		self.report_errors(manager, errors);
+	}
+}
+
+fn compile(engine: &Engine, name: &str, source: &str) -> Result<AST, ScriptError> {
+	engine.compile(source).map_err(|error| ScriptError {
+		system_name: name.to_string(),
+		message: error.to_string()
+	})
+}
+
+fn read_file(name: &str, path: &Path) -> Result<String, ScriptError> {
+	fs::read_to_string(path).map_err(|error| ScriptError {
+		system_name: name.to_string(),
+		message: format!("{}: {}", path.display(), error)
+	})
+}
+
+fn get_modified(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn create_engine(shared: &Shared) -> Engine {
+	let mut engine = Engine::new();
+
+	let manager = shared.clone();
+	engine.register_fn("query", move |names: Array| -> ScriptResult<Array> {
+		let manager = manager.borrow();
+		let mut component_ids = Vec::new();
+		for name in names.into_iter() {
+			let name = name.into_string()?;
+			component_ids.push(find_component_id(&manager, &name)?);
+		}
+		Ok(manager.get_entity_ids_for(&component_ids).into_iter()
+			.map(|entity_id| Dynamic::from(entity_id as i64))
+			.collect())
+	});
+
+	let manager = shared.clone();
+	engine.register_fn("has", move |entity_id: i64, name: &str| -> ScriptResult<bool> {
+		let manager = manager.borrow();
+		let component_id = find_component_id(&manager, name)?;
+		Ok(manager.has_component_id(to_entity_id(entity_id)?, component_id))
+	});
+
+	let manager = shared.clone();
+	engine.register_fn("get", move |entity_id: i64, path: &str| -> ScriptResult<Dynamic> {
+		match manager.borrow().get_field(to_entity_id(entity_id)?, path) {
+			Some(value) => Ok(to_dynamic(value)),
+			None => Err(format!("Entity {} has no {}", entity_id, path).into())
+		}
+	});
+
+	let manager = shared.clone();
+	engine.register_fn("set", move |entity_id: i64, path: &str, value: Dynamic| -> ScriptResult<()> {
+		let value = to_value(&value)?;
+		match manager.borrow_mut().set_field(to_entity_id(entity_id)?, path, &value) {
+			true => Ok(()),
+			false => Err(format!("Can not set {} of entity {}", path, entity_id).into())
+		}
+	});
+
+	let manager = shared.clone();
+	engine.register_fn("create_entity", move |components: Map| -> ScriptResult<()> {
+		let mut manager = manager.borrow_mut();
+		let mut component_values = Vec::new();
+		for (name, fields) in components.into_iter() {
+			let constructible = manager.borrow_type_registry().borrow_type_info(&name)
+				.map(|type_info| type_info.is_constructible())
+				.unwrap_or(false);
+			if !constructible {
+				return Err(format!("{} is not a constructible component", name).into());
+			}
+			let mut field_values = Vec::new();
+			for (field_name, value) in fields.try_cast::<Map>().unwrap_or_default().into_iter() {
+				field_values.push((format!("{}.{}", name, field_name), to_value(&value)?));
+			}
+			component_values.push((name.to_string(), field_values));
+		}
+		manager.borrow_commands_mut().push(move |manager| {
+			let entity_id = manager.create_entity();
+			for (name, field_values) in component_values.iter() {
+				manager.add_default_component_to_entity(entity_id, name);
+				for (path, value) in field_values.iter() {
+					manager.set_field(entity_id, path, value);
+				}
+			}
+		});
+		Ok(())
+	});
+
+	let manager = shared.clone();
+	engine.register_fn("remove_entity", move |entity_id: i64| -> ScriptResult<()> {
+		let entity_id = to_entity_id(entity_id)?;
+		manager.borrow_mut().borrow_commands_mut().despawn(entity_id);
+		Ok(())
+	});
+
+	let manager = shared.clone();
+	engine.register_fn("get_resource", move |name: &str| -> Dynamic {
+		manager.borrow().borrow_resource::<ScriptResources>()
+			.and_then(|resources| resources.get(name).cloned())
+			.unwrap_or(Dynamic::UNIT)
+	});
+
+	let manager = shared.clone();
+	engine.register_fn("set_resource", move |name: &str, value: Dynamic| {
+		let mut manager = manager.borrow_mut();
+		if !manager.has_resource::<ScriptResources>() {
+			manager.insert_resource(ScriptResources::new());
+		}
+		manager.borrow_resource_mut::<ScriptResources>().unwrap().set(name, value);
+	});
+
+	engine
+}
+
+fn find_component_id(manager: &EntityManager, name: &str) -> ScriptResult<ComponentId> {
+	if let Some(type_info) = manager.borrow_type_registry().borrow_type_info(name) {
+		return Ok(ComponentId::Static(type_info.get_type_id()));
+	}
+	match manager.get_dynamic_component_id(name) {
+		Some(component_id) => Ok(component_id),
+		None => Err(format!("Unknown component {}", name).into())
+	}
+}
+
+fn to_entity_id(entity_id: i64) -> ScriptResult<usize> {
+	match entity_id >= 0 {
+		true => Ok(entity_id as usize),
+		false => Err(format!("Invalid entity id {}", entity_id).into())
+	}
+}
+
+fn to_dynamic(value: Value) -> Dynamic {
+	match value {
+		Value::Bool(value) => value.into(),
+		Value::Int(value) => value.into(),
+		Value::Float(value) => value.into(),
+		Value::String(value) => value.into()
+	}
+}
+
+fn to_value(value: &Dynamic) -> ScriptResult<Value> {
+	if let Ok(value) = value.as_bool() {
+		return Ok(Value::Bool(value));
+	}
+	if let Ok(value) = value.as_int() {
+		return Ok(Value::Int(value));
+	}
+	if let Ok(value) = value.as_float() {
+		return Ok(Value::Float(value));
+	}
+	if value.is_string() {
+		return Ok(Value::String(value.clone().into_string()?));
+	}
+	Err(format!("Unsupported value type {}", value.type_name()).into())
+}
@@ -0,0 +1,526 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq, SerializeStruct};
+
+use super::component::Component;
+use super::entity_manager::EntityManager;
+
+type SerializeFn = for<'a> fn(&'a EntityManager, usize) -> Option<&'a dyn erased_serde::Serialize>;
+type DeserializeFn = fn(&mut EntityManager, usize, &mut dyn erased_serde::Deserializer) -> Result<(), erased_serde::Error>;
+type MapEntitiesFn = fn(&mut EntityManager, usize, &HashMap<usize, usize>);
+
+// Implement this for components holding entity ids, e.g. a target to
+// follow, so that the ids are rewritten when the component is loaded into
+// new entities. entity_map is saved id -> new id. Ids not in entity_map
+// refer to entities which were not saved together.
+pub trait MapEntities {
+	fn map_entities(&mut self, entity_map: &HashMap<usize, usize>);
+}
+
+struct SerializableComponent {
+	name: String,
+	serialize: SerializeFn,
+	deserialize: DeserializeFn
+}
+
+// Serializable components keyed by stable names. TypeId is not stable
+// across builds so it can't be written into save data.
+pub struct SerializationRegistry {
+	components: Vec<SerializableComponent>,
+	name_map: HashMap<String, usize>, // name -> index in components
+	type_map: HashMap<TypeId, usize>, // TypeId -> index in components
+	map_entities_fns: HashMap<TypeId, MapEntitiesFn>
+}
+
+impl Default for SerializationRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl SerializationRegistry {
+	pub fn new() -> Self {
+		SerializationRegistry {
+			components: Vec::new(),
+			name_map: HashMap::new(),
+			type_map: HashMap::new(),
+			map_entities_fns: HashMap::new()
+		}
+	}
+
+	pub fn register<T>(&mut self, name: &str) -> &mut Self
+	where T: 'static + Component + Serialize + for<'de> Deserialize<'de> {
+		let type_id = TypeId::of::<T>();
+		if self.type_map.contains_key(&type_id) || self.name_map.contains_key(name) {
+			// @TODO: Better error handling
+			println!("Serializable component {} is already registered", name);
+			return self;
+		}
+		self.components.push(SerializableComponent {
+			name: name.to_string(),
+			serialize: serialize_component::<T>,
+			deserialize: deserialize_component::<T>
+		});
+		let index = self.components.len() - 1;
+		self.name_map.insert(name.to_string(), index);
+		self.type_map.insert(type_id, index);
+		self
+	}
+
+	pub fn register_map_entities<T: 'static + Component + MapEntities>(&mut self) -> &mut Self {
+		self.map_entities_fns.insert(TypeId::of::<T>(), map_component_entities::<T>);
+		self
+	}
+
+	// Rewrites the entity ids in the components of the loaded entities,
+	// the values of entity_map
+	pub fn map_entities(&self, manager: &mut EntityManager, entity_map: &HashMap<usize, usize>) {
+		for map_entities in self.map_entities_fns.values() {
+			for entity_id in entity_map.values() {
+				map_entities(manager, *entity_id, entity_map);
+			}
+		}
+	}
+
+	pub fn has<T: 'static + Component>(&self) -> bool {
+		self.type_map.contains_key(&TypeId::of::<T>())
+	}
+
+	pub fn get_name<T: 'static + Component>(&self) -> Option<&str> {
+		self.type_map.get(&TypeId::of::<T>())
+			.map(|index| self.components[*index].name.as_str())
+	}
+
+	fn find(&self, name: &str) -> Option<&SerializableComponent> {
+		self.name_map.get(name).map(|index| &self.components[*index])
+	}
+}
+
+fn serialize_component<T>(manager: &EntityManager, entity_id: usize) -> Option<&dyn erased_serde::Serialize>
+where T: 'static + Component + Serialize {
+	manager.borrow_component::<T>(entity_id)
+		.map(|component| component as &dyn erased_serde::Serialize)
+}
+
+fn deserialize_component<T>(manager: &mut EntityManager, entity_id: usize, deserializer: &mut dyn erased_serde::Deserializer) -> Result<(), erased_serde::Error>
+where T: 'static + Component + for<'de> Deserialize<'de> {
+	let component = erased_serde::deserialize::<T>(deserializer)?;
+	manager.add_component_to_entity(entity_id, component);
+	Ok(())
+}
+
+fn map_component_entities<T: 'static + Component + MapEntities>(manager: &mut EntityManager, entity_id: usize, entity_map: &HashMap<usize, usize>) {
+	if let Some(component) = manager.borrow_component_mut::<T>(entity_id) {
+		component.map_entities(entity_map);
+	}
+}
+
+// Serializes all the live entities, or the given ones, as a sequence of
+// { id, components: { name: component, ... } }.
+// Components not in the registry are skipped.
+pub struct WorldSerializer<'a> {
+	manager: &'a EntityManager,
+	registry: &'a SerializationRegistry,
+	entity_ids: Option<&'a [usize]>
+}
+
+impl<'a> WorldSerializer<'a> {
+	pub fn new(manager: &'a EntityManager, registry: &'a SerializationRegistry) -> Self {
+		WorldSerializer {
+			manager,
+			registry,
+			entity_ids: None
+		}
+	}
+
+	// Only the entities, e.g. a scene. Removed entities are skipped.
+	pub fn with_entity_ids(manager: &'a EntityManager, registry: &'a SerializationRegistry, entity_ids: &'a [usize]) -> Self {
+		WorldSerializer {
+			manager,
+			registry,
+			entity_ids: Some(entity_ids)
+		}
+	}
+}
+
+impl<'a> Serialize for WorldSerializer<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let entity_ids = match self.entity_ids {
+			Some(entity_ids) => entity_ids.iter()
+				.filter(|entity_id| self.manager.has_entity(**entity_id))
+				.copied()
+				.collect(),
+			None => self.manager.get_entity_ids()
+		};
+		let mut seq = serializer.serialize_seq(Some(entity_ids.len()))?;
+		for entity_id in entity_ids {
+			seq.serialize_element(&EntitySerializer {
+				manager: self.manager,
+				registry: self.registry,
+				entity_id
+			})?;
+		}
+		seq.end()
+	}
+}
+
+struct EntitySerializer<'a> {
+	manager: &'a EntityManager,
+	registry: &'a SerializationRegistry,
+	entity_id: usize
+}
+
+impl<'a> Serialize for EntitySerializer<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut state = serializer.serialize_struct("Entity", 2)?;
+		state.serialize_field("id", &self.entity_id)?;
+		state.serialize_field("components", &ComponentsSerializer {
+			manager: self.manager,
+			registry: self.registry,
+			entity_id: self.entity_id
+		})?;
+		state.end()
+	}
+}
+
+struct ComponentsSerializer<'a> {
+	manager: &'a EntityManager,
+	registry: &'a SerializationRegistry,
+	entity_id: usize
+}
+
+impl<'a> Serialize for ComponentsSerializer<'a> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		// Some formats need the length in advance
+		let components = self.registry.components.iter()
+			.filter_map(|c| (c.serialize)(self.manager, self.entity_id).map(|value| (&c.name, value)))
+			.collect::<Vec<_>>();
+		let mut map = serializer.serialize_map(Some(components.len()))?;
+		for (name, value) in components {
+			map.serialize_entry(name, value)?;
+		}
+		map.end()
+	}
+}
+
+// Counterpart of WorldSerializer. Creates a new entity for each serialized
+// entity and records saved id -> new id in entity_map.
+pub struct WorldDeserializer<'a> {
+	manager: &'a mut EntityManager,
+	registry: &'a SerializationRegistry,
+	entity_map: &'a mut HashMap<usize, usize>
+}
+
+impl<'a> WorldDeserializer<'a> {
+	pub fn new(
+		manager: &'a mut EntityManager,
+		registry: &'a SerializationRegistry,
+		entity_map: &'a mut HashMap<usize, usize>
+	) -> Self {
+		WorldDeserializer {
+			manager,
+			registry,
+			entity_map
+		}
+	}
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for WorldDeserializer<'a> {
+	type Value = ();
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+		deserializer.deserialize_seq(self)
+	}
+}
+
+impl<'de, 'a> Visitor<'de> for WorldDeserializer<'a> {
+	type Value = ();
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a sequence of entities")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+		while seq.next_element_seed(EntityDeserializer {
+			manager: &mut *self.manager,
+			registry: self.registry,
+			entity_map: &mut *self.entity_map
+		})?.is_some() {}
+		Ok(())
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum EntityField {
+	Id,
+	Components
+}
+
+struct EntityDeserializer<'a, 'b> {
+	manager: &'b mut EntityManager,
+	registry: &'a SerializationRegistry,
+	entity_map: &'b mut HashMap<usize, usize>
+}
+
+impl<'a, 'b> EntityDeserializer<'a, 'b> {
+	fn map_entity(&mut self, saved_id: usize) -> usize {
+		let manager = &mut self.manager;
+		*self.entity_map.entry(saved_id).or_insert_with(|| manager.create_entity())
+	}
+}
+
+impl<'de, 'a, 'b> DeserializeSeed<'de> for EntityDeserializer<'a, 'b> {
+	type Value = ();
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+		deserializer.deserialize_struct("Entity", &["id", "components"], self)
+	}
+}
+
+impl<'de, 'a, 'b> Visitor<'de> for EntityDeserializer<'a, 'b> {
+	type Value = ();
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("an entity")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+		let saved_id = seq.next_element::<usize>()?
+			.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+		let entity_id = self.map_entity(saved_id);
+		seq.next_element_seed(ComponentsDeserializer {
+			manager: self.manager,
+			registry: self.registry,
+			entity_id
+		})?.ok_or_else(|| de::Error::invalid_length(1, &"an entity"))?;
+		Ok(())
+	}
+
+	fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+		let mut entity_id = None;
+		let mut has_components = false;
+		while let Some(key) = map.next_key::<EntityField>()? {
+			match key {
+				EntityField::Id => {
+					if entity_id.is_some() {
+						return Err(de::Error::duplicate_field("id"));
+					}
+					let saved_id = map.next_value::<usize>()?;
+					entity_id = Some(self.map_entity(saved_id));
+				},
+				EntityField::Components => {
+					// Components are added as they are deserialized so the
+					// entity has to exist already
+					let entity_id = entity_id.ok_or_else(|| de::Error::custom("id must precede components"))?;
+					if has_components {
+						return Err(de::Error::duplicate_field("components"));
+					}
+					map.next_value_seed(ComponentsDeserializer {
+						manager: &mut *self.manager,
+						registry: self.registry,
+						entity_id
+					})?;
+					has_components = true;
+				}
+			}
+		}
+		if entity_id.is_none() {
+			return Err(de::Error::missing_field("id"));
+		}
+		Ok(())
+	}
+}
+
+struct ComponentsDeserializer<'a, 'b> {
+	manager: &'b mut EntityManager,
+	registry: &'a SerializationRegistry,
+	entity_id: usize
+}
+
+impl<'de, 'a, 'b> DeserializeSeed<'de> for ComponentsDeserializer<'a, 'b> {
+	type Value = ();
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+		deserializer.deserialize_map(self)
+	}
+}
+
+impl<'de, 'a, 'b> Visitor<'de> for ComponentsDeserializer<'a, 'b> {
+	type Value = ();
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("a map of components")
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+		while let Some(name) = map.next_key::<String>()? {
+			let component = self.registry.find(&name)
+				.ok_or_else(|| de::Error::custom(format!("Unknown component {}", name)))?;
+			map.next_value_seed(ComponentDeserializer {
+				manager: &mut *self.manager,
+				component,
+				entity_id: self.entity_id
+			})?;
+		}
+		Ok(())
+	}
+}
+
+struct ComponentDeserializer<'a, 'b> {
+	manager: &'b mut EntityManager,
+	component: &'a SerializableComponent,
+	entity_id: usize
+}
+
+impl<'de, 'a, 'b> DeserializeSeed<'de> for ComponentDeserializer<'a, 'b> {
+	type Value = ();
+
+	fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+		let mut erased = <dyn erased_serde::Deserializer>::erase(deserializer);
+		(self.component.deserialize)(self.manager, self.entity_id, &mut erased)
+			.map_err(de::Error::custom)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use bincode::Options;
+	use serde::{Deserialize, Serialize, Serializer};
+
+	use super::MapEntities;
+	use crate::component::Component;
+	use crate::storage::DenseStorage;
+	use crate::world::World;
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Position {
+		x: f32,
+		y: f32
+	}
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	#[derive(Serialize, Deserialize, PartialEq, Debug)]
+	struct Target {
+		entity_id: usize
+	}
+
+	impl Component for Target {
+		type Storage = DenseStorage<Self>;
+	}
+
+	impl MapEntities for Target {
+		fn map_entities(&mut self, entity_map: &HashMap<usize, usize>) {
+			if let Some(entity_id) = entity_map.get(&self.entity_id) {
+				self.entity_id = *entity_id;
+			}
+		}
+	}
+
+	struct SaveData<'a>(&'a World);
+
+	impl<'a> Serialize for SaveData<'a> {
+		fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			self.0.save(serializer)
+		}
+	}
+
+	fn create_world() -> World {
+		let mut world = World::new();
+		world
+			.register_serializable_component::<Position>("Position")
+			.register_serializable_component::<Target>("Target")
+			.register_map_entities_component::<Target>();
+		world
+	}
+
+	// Entities 1 and 2 targeting each other. Entity 0 is removed so that
+	// the ids change on load.
+	fn create_saved_world() -> World {
+		let mut world = create_world();
+		let removed_id = world.create_entity();
+		let entity_id1 = world.create_entity();
+		let entity_id2 = world.create_entity();
+		world.remove_entity(removed_id);
+		world
+			.add_component_to_entity(entity_id1, Position { x: 1.0, y: 2.0 })
+			.add_component_to_entity(entity_id1, Target { entity_id: entity_id2 })
+			.add_component_to_entity(entity_id2, Position { x: 3.0, y: 4.0 })
+			.add_component_to_entity(entity_id2, Target { entity_id: entity_id1 });
+		world
+	}
+
+	fn assert_loaded(saved: &World, loaded: &World, entity_map: &HashMap<usize, usize>) {
+		assert_eq!(saved.get_entity_ids(), vec![1, 2]);
+		assert_eq!(loaded.get_entity_ids().len(), 2);
+		assert_ne!(entity_map.get(&1), Some(&1));
+		for (saved_id, loaded_id) in entity_map.iter() {
+			assert!(loaded.has_entity(*loaded_id));
+			assert_eq!(
+				loaded.borrow_component::<Position>(*loaded_id),
+				saved.borrow_component::<Position>(*saved_id)
+			);
+			let saved_target_id = saved.borrow_component::<Target>(*saved_id).unwrap().entity_id;
+			assert_eq!(
+				loaded.borrow_component::<Target>(*loaded_id).unwrap().entity_id,
+				*entity_map.get(&saved_target_id).unwrap()
+			);
+		}
+	}
+
+	#[test]
+	fn json_round_trip() {
+		let saved = create_saved_world();
+		let json = serde_json::to_string(&SaveData(&saved)).unwrap();
+		let mut loaded = create_world();
+		let entity_map = loaded.load(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+		assert_loaded(&saved, &loaded, &entity_map);
+	}
+
+	#[test]
+	fn ron_round_trip() {
+		let saved = create_saved_world();
+		let text = ron::to_string(&SaveData(&saved)).unwrap();
+		let mut loaded = create_world();
+		let entity_map = loaded.load(&mut ron::Deserializer::from_str(&text).unwrap()).unwrap();
+		assert_loaded(&saved, &loaded, &entity_map);
+	}
+
+	#[test]
+	fn bincode_round_trip() {
+		let saved = create_saved_world();
+		let bytes = bincode::options().serialize(&SaveData(&saved)).unwrap();
+		let mut loaded = create_world();
+		let entity_map = loaded.load(&mut bincode::Deserializer::from_slice(&bytes, bincode::options())).unwrap();
+		assert_loaded(&saved, &loaded, &entity_map);
+	}
+
+	#[test]
+	fn load_replaces_existing_entities() {
+		let saved = create_saved_world();
+		let json = serde_json::to_string(&SaveData(&saved)).unwrap();
+		let mut loaded = create_world();
+		let entity_id = loaded.create_entity();
+		loaded.add_component_to_entity(entity_id, Position { x: 9.0, y: 9.0 });
+		let entity_map = loaded.load(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+		assert_loaded(&saved, &loaded, &entity_map);
+	}
+
+	#[test]
+	fn unknown_component_fails() {
+		let saved = create_saved_world();
+		let json = serde_json::to_string(&SaveData(&saved)).unwrap();
+		let mut loaded = World::new();
+		loaded.register_serializable_component::<Position>("Position");
+		assert!(loaded.load(&mut serde_json::Deserializer::from_str(&json)).is_err());
+	}
+}
@@ -0,0 +1,604 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use super::component::Component;
+use super::entity_manager::{EntityIdAccessor, EntityManager};
+use super::system::System;
+
+// Component holding a 2D position tracked by SpatialIndex
+pub trait SpatialPosition: Component {
+	fn get_position(&self) -> [f32; 2];
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+	pub min: [f32; 2],
+	pub max: [f32; 2]
+}
+
+impl Aabb {
+	pub fn new(min: [f32; 2], max: [f32; 2]) -> Self {
+		Aabb {
+			min,
+			max
+		}
+	}
+
+	pub fn from_center(center: [f32; 2], half_size: [f32; 2]) -> Self {
+		Aabb {
+			min: [center[0] - half_size[0], center[1] - half_size[1]],
+			max: [center[0] + half_size[0], center[1] + half_size[1]]
+		}
+	}
+
+	pub fn contains(&self, point: [f32; 2]) -> bool {
+		point[0] >= self.min[0] && point[0] <= self.max[0] &&
+			point[1] >= self.min[1] && point[1] <= self.max[1]
+	}
+
+	pub fn intersects(&self, other: &Aabb) -> bool {
+		self.min[0] <= other.max[0] && self.max[0] >= other.min[0] &&
+			self.min[1] <= other.max[1] && self.max[1] >= other.min[1]
+	}
+
+	fn get_center(&self) -> [f32; 2] {
+		[(self.min[0] + self.max[0]) * 0.5, (self.min[1] + self.max[1]) * 0.5]
+	}
+}
+
+// Data structure of SpatialIndex. The entities are removed with the
+// position they were inserted with.
+pub trait SpatialStructure {
+	fn insert(&mut self, entity_id: usize, position: [f32; 2]);
+	fn remove(&mut self, entity_id: usize, position: [f32; 2]);
+	// Appends the entities whose position is in the aabb to dst
+	fn collect_in_aabb(&self, aabb: &Aabb, dst: &mut Vec<usize>);
+	// The first radius tried by SpatialIndex::query_nearest()
+	fn get_search_radius(&self) -> f32;
+	fn clear(&mut self);
+}
+
+type Cell = Vec<(usize, [f32; 2])>;
+
+// Good for entities of similar sizes spread over an unbounded area.
+// Only the occupied cells are allocated.
+pub struct UniformGrid {
+	cell_size: f32,
+	cells: HashMap<(i32, i32), Cell>
+}
+
+impl UniformGrid {
+	pub fn new(cell_size: f32) -> Self {
+		UniformGrid {
+			cell_size,
+			cells: HashMap::new()
+		}
+	}
+
+	fn get_key(&self, position: [f32; 2]) -> (i32, i32) {
+		((position[0] / self.cell_size).floor() as i32, (position[1] / self.cell_size).floor() as i32)
+	}
+}
+
+impl SpatialStructure for UniformGrid {
+	fn insert(&mut self, entity_id: usize, position: [f32; 2]) {
+		let key = self.get_key(position);
+		self.cells.entry(key).or_default().push((entity_id, position));
+	}
+
+	fn remove(&mut self, entity_id: usize, position: [f32; 2]) {
+		let key = self.get_key(position);
+		if let Some(cell) = self.cells.get_mut(&key) {
+			if let Some(index) = cell.iter().position(|(id, _)| *id == entity_id) {
+				cell.swap_remove(index);
+			}
+			if cell.is_empty() {
+				self.cells.remove(&key);
+			}
+		}
+	}
+
+	fn collect_in_aabb(&self, aabb: &Aabb, dst: &mut Vec<usize>) {
+		let (min_x, min_y) = self.get_key(aabb.min);
+		let (max_x, max_y) = self.get_key(aabb.max);
+		let mut collect = |cell: &Cell| {
+			for (entity_id, position) in cell.iter() {
+				if aabb.contains(*position) {
+					dst.push(*entity_id);
+				}
+			}
+		};
+		let cell_count = (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1);
+		// Large areas are cheaper to handle by walking the occupied cells
+		if cell_count > self.cells.len() as i64 {
+			for ((x, y), cell) in self.cells.iter() {
+				if *x >= min_x && *x <= max_x && *y >= min_y && *y <= max_y {
+					collect(cell);
+				}
+			}
+			return;
+		}
+		for y in min_y..=max_y {
+			for x in min_x..=max_x {
+				if let Some(cell) = self.cells.get(&(x, y)) {
+					collect(cell);
+				}
+			}
+		}
+	}
+
+	fn get_search_radius(&self) -> f32 {
+		self.cell_size
+	}
+
+	fn clear(&mut self) {
+		self.cells.clear();
+	}
+}
+
+struct QuadNode {
+	bounds: Aabb,
+	depth: usize,
+	children: Option<usize>, // Index of the first of the four children in nodes
+	entities: Vec<(usize, [f32; 2])> // Only in leaves
+}
+
+impl QuadNode {
+	fn new(bounds: Aabb, depth: usize) -> Self {
+		QuadNode {
+			bounds,
+			depth,
+			children: None,
+			entities: Vec::new()
+		}
+	}
+}
+
+const QUAD_TREE_MAX_DEPTH: usize = 16;
+
+// Good for clustered entities in a known area. A leaf is split into four
+// when it has more than capacity entities. Entities outside the bounds
+// are kept in a list searched by every query.
+// @TODO: Merge the leaves when their entities are removed
+pub struct QuadTree {
+	nodes: Vec<QuadNode>, // nodes[0] is the root
+	capacity: usize,
+	outside: Vec<(usize, [f32; 2])>
+}
+
+impl QuadTree {
+	pub fn new(bounds: Aabb, capacity: usize) -> Self {
+		QuadTree {
+			nodes: vec![QuadNode::new(bounds, 0)],
+			capacity: capacity.max(1),
+			outside: Vec::new()
+		}
+	}
+
+	// 0: -x -y, 1: +x -y, 2: -x +y, 3: +x +y
+	fn get_quadrant(bounds: &Aabb, position: [f32; 2]) -> usize {
+		let center = bounds.get_center();
+		(position[0] >= center[0]) as usize + 2 * (position[1] >= center[1]) as usize
+	}
+
+	fn find_leaf(&self, position: [f32; 2]) -> usize {
+		let mut index = 0;
+		while let Some(children) = self.nodes[index].children {
+			index = children + Self::get_quadrant(&self.nodes[index].bounds, position);
+		}
+		index
+	}
+
+	fn split(&mut self, index: usize) {
+		let bounds = self.nodes[index].bounds;
+		let depth = self.nodes[index].depth + 1;
+		let center = bounds.get_center();
+		let children = self.nodes.len();
+		self.nodes.push(QuadNode::new(Aabb::new(bounds.min, center), depth));
+		self.nodes.push(QuadNode::new(Aabb::new([center[0], bounds.min[1]], [bounds.max[0], center[1]]), depth));
+		self.nodes.push(QuadNode::new(Aabb::new([bounds.min[0], center[1]], [center[0], bounds.max[1]]), depth));
+		self.nodes.push(QuadNode::new(Aabb::new(center, bounds.max), depth));
+		self.nodes[index].children = Some(children);
+		for (entity_id, position) in std::mem::take(&mut self.nodes[index].entities) {
+			self.nodes[children + Self::get_quadrant(&bounds, position)].entities.push((entity_id, position));
+		}
+	}
+}
+
+impl SpatialStructure for QuadTree {
+	fn insert(&mut self, entity_id: usize, position: [f32; 2]) {
+		if !self.nodes[0].bounds.contains(position) {
+			self.outside.push((entity_id, position));
+			return;
+		}
+		let index = self.find_leaf(position);
+		self.nodes[index].entities.push((entity_id, position));
+		if self.nodes[index].entities.len() > self.capacity && self.nodes[index].depth < QUAD_TREE_MAX_DEPTH {
+			self.split(index);
+		}
+	}
+
+	fn remove(&mut self, entity_id: usize, position: [f32; 2]) {
+		let entities = match self.nodes[0].bounds.contains(position) {
+			true => {
+				let index = self.find_leaf(position);
+				&mut self.nodes[index].entities
+			},
+			false => &mut self.outside
+		};
+		if let Some(index) = entities.iter().position(|(id, _)| *id == entity_id) {
+			entities.swap_remove(index);
+		}
+	}
+
+	fn collect_in_aabb(&self, aabb: &Aabb, dst: &mut Vec<usize>) {
+		let mut stack = vec![0];
+		while let Some(index) = stack.pop() {
+			let node = &self.nodes[index];
+			if !node.bounds.intersects(aabb) {
+				continue;
+			}
+			match node.children {
+				Some(children) => stack.extend(children..children + 4),
+				None => {
+					for (entity_id, position) in node.entities.iter() {
+						if aabb.contains(*position) {
+							dst.push(*entity_id);
+						}
+					}
+				}
+			};
+		}
+		for (entity_id, position) in self.outside.iter() {
+			if aabb.contains(*position) {
+				dst.push(*entity_id);
+			}
+		}
+	}
+
+	fn get_search_radius(&self) -> f32 {
+		let bounds = &self.nodes[0].bounds;
+		(bounds.max[0] - bounds.min[0]).max(bounds.max[1] - bounds.min[1]) / 16.0
+	}
+
+	fn clear(&mut self) {
+		let bounds = self.nodes[0].bounds;
+		self.nodes = vec![QuadNode::new(bounds, 0)];
+		self.outside.clear();
+	}
+}
+
+// Resource indexing the entities by the position of their T component.
+// Kept in sync by SpatialIndexSystem<T>, see World::add_spatial_index().
+// The query results are sorted by entity id, or by distance for
+// query_nearest(), so that they don't depend on the structure.
+pub struct SpatialIndex<T: SpatialPosition> {
+	positions: HashMap<usize, [f32; 2]>, // The positions in the structure
+	structure: Box<dyn SpatialStructure>,
+	component: PhantomData<fn() -> T>
+}
+
+impl<T: SpatialPosition> SpatialIndex<T> {
+	pub fn new(structure: Box<dyn SpatialStructure>) -> Self {
+		SpatialIndex {
+			positions: HashMap::new(),
+			structure,
+			component: PhantomData
+		}
+	}
+
+	pub fn with_grid(cell_size: f32) -> Self {
+		Self::new(Box::new(UniformGrid::new(cell_size)))
+	}
+
+	pub fn with_quad_tree(bounds: Aabb, capacity: usize) -> Self {
+		Self::new(Box::new(QuadTree::new(bounds, capacity)))
+	}
+
+	pub fn get_entity_count(&self) -> usize {
+		self.positions.len()
+	}
+
+	// The position at the last sync
+	pub fn get_position(&self, entity_id: usize) -> Option<[f32; 2]> {
+		self.positions.get(&entity_id).copied()
+	}
+
+	pub fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+		let mut entity_ids = Vec::new();
+		self.structure.collect_in_aabb(aabb, &mut entity_ids);
+		entity_ids.sort_unstable();
+		entity_ids
+	}
+
+	pub fn query_radius(&self, center: [f32; 2], radius: f32) -> Vec<usize> {
+		let mut entity_ids = self.query_aabb(&Aabb::from_center(center, [radius, radius]));
+		entity_ids.retain(|entity_id| get_distance_squared(self.positions[entity_id], center) <= radius * radius);
+		entity_ids
+	}
+
+	// The k nearest entities, nearest first
+	pub fn query_nearest(&self, point: [f32; 2], k: usize) -> Vec<usize> {
+		if k == 0 || self.positions.is_empty() {
+			return Vec::new();
+		}
+		// Grows the radius until it has k entities. The k nearest ones are
+		// in it because the others are outside.
+		let mut radius = self.structure.get_search_radius().max(f32::MIN_POSITIVE);
+		let mut entity_ids = loop {
+			let entity_ids = self.query_radius(point, radius);
+			if entity_ids.len() >= k || entity_ids.len() == self.positions.len() || !radius.is_finite() {
+				break entity_ids;
+			}
+			radius *= 2.0;
+		};
+		entity_ids.sort_by(|a, b| {
+			let distance_a = get_distance_squared(self.positions[a], point);
+			let distance_b = get_distance_squared(self.positions[b], point);
+			distance_a.total_cmp(&distance_b).then(a.cmp(b))
+		});
+		entity_ids.truncate(k);
+		entity_ids
+	}
+
+	// Updates the structure with the current positions, sorted by entity id.
+	// Moved entities are detected by comparing with the last positions.
+	pub fn sync(&mut self, positions: &[(usize, [f32; 2])]) {
+		let mut inserted = false;
+		for (entity_id, position) in positions.iter() {
+			match self.positions.insert(*entity_id, *position) {
+				Some(last_position) => if last_position != *position {
+					self.structure.remove(*entity_id, last_position);
+					self.structure.insert(*entity_id, *position);
+				},
+				None => {
+					self.structure.insert(*entity_id, *position);
+					inserted = true;
+				}
+			};
+		}
+		// Nothing is removed if all the entities were there
+		if !inserted && self.positions.len() == positions.len() {
+			return;
+		}
+		let removed_ids = self.positions.keys()
+			.filter(|entity_id| positions.binary_search_by_key(*entity_id, |(id, _)| *id).is_err())
+			.copied()
+			.collect::<Vec<usize>>();
+		for entity_id in removed_ids {
+			let position = self.positions.remove(&entity_id).unwrap();
+			self.structure.remove(entity_id, position);
+		}
+	}
+
+	// Updates the position of an entity, or removes it from the index if
+	// it has no position
+	pub fn update_position(&mut self, entity_id: usize, position: Option<[f32; 2]>) {
+		let last_position = match position {
+			Some(position) => self.positions.insert(entity_id, position),
+			None => self.positions.remove(&entity_id)
+		};
+		if last_position == position {
+			return;
+		}
+		if let Some(last_position) = last_position {
+			self.structure.remove(entity_id, last_position);
+		}
+		if let Some(position) = position {
+			self.structure.insert(entity_id, position);
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.positions.clear();
+		self.structure.clear();
+	}
+}
+
+fn get_distance_squared(a: [f32; 2], b: [f32; 2]) -> f32 {
+	let dx = a[0] - b[0];
+	let dy = a[1] - b[1];
+	dx * dx + dy * dy
+}
+
+// Syncs SpatialIndex<T> with the T components. Add it after the systems
+// moving the entities so that the later systems see the new positions.
+// Only the entities whose T components are added, removed or mutably
+// borrowed since the last update are visited. All of them are visited on
+// the first update, after a restore and after the accessors borrowing all
+// the T components, e.g. for_each_mut().
+pub struct SpatialIndexSystem<T: SpatialPosition> {
+	sequence: Option<u64>, // EntityManager::get_change_sequence() at the last update
+	component: PhantomData<fn() -> T>
+}
+
+impl<T: SpatialPosition> Default for SpatialIndexSystem<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T: SpatialPosition> SpatialIndexSystem<T> {
+	pub fn new() -> Self {
+		SpatialIndexSystem {
+			sequence: None,
+			component: PhantomData
+		}
+	}
+}
+
+impl<T: 'static + SpatialPosition> SpatialIndexSystem<T> {
+	// None if the changes since the last update are unknown
+	fn get_changed_ids(&self, manager: &mut EntityManager) -> Option<Vec<usize>> {
+		let mutated_ids = manager.take_mutated_ids::<T>();
+		let changes = manager.borrow_changes_since(self.sequence?)?;
+		let mut entity_ids = mutated_ids?;
+		let type_id = TypeId::of::<T>();
+		// None is an entity removal
+		entity_ids.extend(changes.iter()
+			.filter(|(_, change_type_id)| change_type_id.is_none_or(|change_type_id| change_type_id == type_id))
+			.map(|(entity_id, _)| *entity_id));
+		entity_ids.sort_unstable();
+		entity_ids.dedup();
+		Some(entity_ids)
+	}
+}
+
+impl<T: 'static + SpatialPosition> System for SpatialIndexSystem<T> {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		if !manager.has_resource::<SpatialIndex<T>>() {
+			self.sequence = None;
+			return;
+		}
+		manager.track_mutations::<T>();
+		let changed_ids = self.get_changed_ids(manager);
+		self.sequence = Some(manager.get_change_sequence());
+
+		if let Some(entity_ids) = changed_ids {
+			let positions = entity_ids.iter()
+				.map(|entity_id| (*entity_id, manager.borrow_component::<T>(*entity_id).map(|component| component.get_position())))
+				.collect::<Vec<(usize, Option<[f32; 2]>)>>();
+			let index = manager.borrow_resource_mut::<SpatialIndex<T>>().unwrap();
+			for (entity_id, position) in positions {
+				index.update_position(entity_id, position);
+			}
+			return;
+		}
+
+		let mut positions = match accessor.borrow_ids::<T>(manager) {
+			Some(entity_ids) => entity_ids.iter()
+				.map(|entity_id| (*entity_id, manager.borrow_component::<T>(*entity_id).unwrap().get_position()))
+				.collect::<Vec<(usize, [f32; 2])>>(),
+			None => Vec::new()
+		};
+		// The cached ids are not in order
+		positions.sort_unstable_by_key(|(entity_id, _)| *entity_id);
+		manager.borrow_resource_mut::<SpatialIndex<T>>().unwrap().sync(&positions);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::component::Component;
+	use crate::entity_manager::{EntityIdAccessor, EntityManager};
+	use crate::storage::DenseStorage;
+	use crate::system::System;
+	use crate::world::World;
+	use super::{get_distance_squared, Aabb, SpatialIndex, SpatialPosition};
+
+	struct Position {
+		position: [f32; 2]
+	}
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	impl SpatialPosition for Position {
+		fn get_position(&self) -> [f32; 2] {
+			self.position
+		}
+	}
+
+	// Deterministic pseudo random numbers in 0.0..100.0
+	struct Random {
+		state: u64
+	}
+
+	impl Random {
+		fn next(&mut self) -> f32 {
+			self.state = self.state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+			(self.state >> 40) as f32 / (1u64 << 24) as f32 * 100.0
+		}
+
+		fn next_position(&mut self) -> Position {
+			Position { position: [self.next(), self.next()] }
+		}
+	}
+
+	// Moves every entity on even frames, through for_each_mut()
+	struct MoveAllSystem;
+
+	impl System for MoveAllSystem {
+		fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+			if manager.get_frame() % 2 == 1 {
+				return;
+			}
+			manager.for_each_mut::<Position, _>(|entity_id, position| {
+				position.position[0] = (position.position[0] + entity_id as f32) % 100.0;
+			});
+		}
+	}
+
+	fn get_positions(world: &World) -> Vec<(usize, [f32; 2])> {
+		world.get_entity_ids().into_iter()
+			.filter_map(|entity_id| world.borrow_component::<Position>(entity_id)
+				.map(|position| (entity_id, position.position)))
+			.collect()
+	}
+
+	fn assert_queries(world: &World, random: &mut Random) {
+		let index = world.borrow_resource::<SpatialIndex<Position>>().unwrap();
+		let positions = get_positions(world);
+		assert_eq!(index.get_entity_count(), positions.len());
+
+		let aabb = Aabb::from_center([random.next(), random.next()], [20.0, 10.0]);
+		let expected = positions.iter()
+			.filter(|(_, position)| aabb.contains(*position))
+			.map(|(entity_id, _)| *entity_id)
+			.collect::<Vec<usize>>();
+		assert_eq!(index.query_aabb(&aabb), expected);
+
+		let center = [random.next(), random.next()];
+		let expected = positions.iter()
+			.filter(|(_, position)| get_distance_squared(*position, center) <= 15.0 * 15.0)
+			.map(|(entity_id, _)| *entity_id)
+			.collect::<Vec<usize>>();
+		assert_eq!(index.query_radius(center, 15.0), expected);
+
+		let mut expected = positions.clone();
+		expected.sort_by(|a, b| get_distance_squared(a.1, center).total_cmp(&get_distance_squared(b.1, center)).then(a.0.cmp(&b.0)));
+		let expected = expected.iter().take(5).map(|(entity_id, _)| *entity_id).collect::<Vec<usize>>();
+		assert_eq!(index.query_nearest(center, 5), expected);
+	}
+
+	fn match_brute_force(index: SpatialIndex<Position>) {
+		let mut world = World::new();
+		world
+			.register_component::<Position>()
+			.add_system(MoveAllSystem)
+			.add_spatial_index(index);
+		let mut random = Random { state: 42 };
+		for _ in 0..50 {
+			let entity_id = world.create_entity();
+			world.add_component_to_entity(entity_id, random.next_position());
+		}
+
+		for frame in 0..20 {
+			world.update();
+			assert_queries(&world, &mut random);
+
+			// Moves, removals and additions seen in the next update
+			let entity_ids = world.get_entity_ids();
+			let entity_id = entity_ids[frame * 7 % entity_ids.len()];
+			if let Some(position) = world.borrow_component_mut::<Position>(entity_id) {
+				position.position = [random.next(), random.next()];
+			}
+			world.remove_entity(entity_ids[frame * 11 % entity_ids.len()]);
+			world.remove_component_from_entity::<Position>(entity_ids[frame * 13 % entity_ids.len()]);
+			let entity_id = world.create_entity();
+			world.add_component_to_entity(entity_id, random.next_position());
+		}
+	}
+
+	#[test]
+	fn grid_matches_brute_force() {
+		match_brute_force(SpatialIndex::with_grid(10.0));
+	}
+
+	#[test]
+	fn quad_tree_matches_brute_force() {
+		match_brute_force(SpatialIndex::with_quad_tree(Aabb::new([0.0, 0.0], [100.0, 100.0]), 4));
+	}
+}
@@ -0,0 +1,462 @@
+use std::collections::{BTreeMap, HashMap};
+
+pub use super::component_manager::ComponentManager as DenseStorage;
+
+// Storage of a component type in PerComponent mode, chosen with
+// Component::Storage. Archetype mode ignores it and stores every
+// component in archetype columns.
+pub trait ComponentStorage: 'static + Default {
+	type Component: 'static;
+
+	fn has(&self, entity_id: usize) -> bool;
+	fn add(&mut self, entity_id: usize, component: Self::Component);
+	fn remove(&mut self, entity_id: usize);
+	// Removes and returns the component, e.g. to move it to another world
+	fn take(&mut self, entity_id: usize) -> Option<Self::Component>;
+	fn len(&self) -> usize;
+	fn borrow_component(&self, entity_id: usize) -> Option<&Self::Component>;
+	fn borrow_component_mut(&mut self, entity_id: usize) -> Option<&mut Self::Component>;
+	// The components of K distinct entities at once, e.g. for
+	// EntityManager::iter_combinations_mut(). None if any of the entities
+	// doesn't have the component. Panics if the entity ids aren't distinct.
+	fn borrow_components_disjoint_mut<const K: usize>(&mut self, entity_ids: [usize; K]) -> Option<[&mut Self::Component; K]>;
+
+	// Appends the ids of the entities having the component in the iteration order
+	fn collect_entity_ids(&self, dst: &mut Vec<usize>);
+	fn for_each_mut<F: FnMut(usize, &mut Self::Component)>(&mut self, f: F);
+
+	// Used for world snapshots
+	fn clone_storage(&self) -> Self where Self::Component: Clone;
+
+	fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	// Only storages keeping the components in a Vec return Some
+	fn borrow_dense_components(&self) -> Option<&Vec<Self::Component>> {
+		None
+	}
+
+	fn borrow_dense_components_mut(&mut self) -> Option<&mut Vec<Self::Component>> {
+		None
+	}
+}
+
+const INVALID_INDEX: usize = usize::MAX;
+
+pub(crate) fn assert_distinct_entity_ids(entity_ids: &[usize]) {
+	for (i, entity_id) in entity_ids.iter().enumerate() {
+		assert!(!entity_ids[..i].contains(entity_id), "The entity ids must be distinct");
+	}
+}
+
+fn unwrap_all<T, const K: usize>(components: [Option<T>; K]) -> Option<[T; K]> {
+	if components.iter().any(|component| component.is_none()) {
+		return None;
+	}
+	Some(components.map(|component| component.unwrap()))
+}
+
+// Like DenseStorage but entity ids index one flat array instead of pages,
+// saving an indirection per lookup. The array grows up to the largest entity
+// id having the component. Good for frequently toggled components.
+#[derive(Clone)]
+pub struct SparseSetStorage<T> {
+	components: Vec<T>,
+	entity_ids: Vec<usize>, // Same order with components
+	sparse: Vec<usize> // entity_id -> index in components or INVALID_INDEX
+}
+
+impl<T> Default for SparseSetStorage<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> SparseSetStorage<T> {
+	pub fn new() -> Self {
+		SparseSetStorage {
+			components: Vec::new(),
+			entity_ids: Vec::new(),
+			sparse: Vec::new()
+		}
+	}
+
+	fn get_index(&self, entity_id: usize) -> Option<usize> {
+		match self.sparse.get(entity_id) {
+			Some(index) if *index != INVALID_INDEX => Some(*index),
+			_ => None
+		}
+	}
+
+	pub fn borrow_entity_ids(&self) -> &Vec<usize> {
+		&self.entity_ids
+	}
+
+	pub fn borrow_components(&self) -> &Vec<T> {
+		&self.components
+	}
+}
+
+impl<T: 'static> ComponentStorage for SparseSetStorage<T> {
+	type Component = T;
+
+	fn has(&self, entity_id: usize) -> bool {
+		self.get_index(entity_id).is_some()
+	}
+
+	fn add(&mut self, entity_id: usize, component: T) {
+		if self.has(entity_id) {
+			return;
+		}
+		if self.sparse.len() <= entity_id {
+			self.sparse.resize(entity_id + 1, INVALID_INDEX);
+		}
+		self.sparse[entity_id] = self.components.len();
+		self.components.push(component);
+		self.entity_ids.push(entity_id);
+	}
+
+	fn remove(&mut self, entity_id: usize) {
+		let index = match self.get_index(entity_id) {
+			Some(index) => index,
+			None => return
+		};
+		self.sparse[*self.entity_ids.last().unwrap()] = index;
+		self.sparse[entity_id] = INVALID_INDEX;
+		self.components.swap_remove(index);
+		self.entity_ids.swap_remove(index);
+	}
+
+	fn take(&mut self, entity_id: usize) -> Option<T> {
+		let index = self.get_index(entity_id)?;
+		self.sparse[*self.entity_ids.last().unwrap()] = index;
+		self.sparse[entity_id] = INVALID_INDEX;
+		self.entity_ids.swap_remove(index);
+		Some(self.components.swap_remove(index))
+	}
+
+	fn len(&self) -> usize {
+		self.components.len()
+	}
+
+	fn borrow_component(&self, entity_id: usize) -> Option<&T> {
+		self.get_index(entity_id).map(move |index| &self.components[index])
+	}
+
+	fn borrow_component_mut(&mut self, entity_id: usize) -> Option<&mut T> {
+		match self.get_index(entity_id) {
+			Some(index) => Some(&mut self.components[index]),
+			None => None
+		}
+	}
+
+	fn borrow_components_disjoint_mut<const K: usize>(&mut self, entity_ids: [usize; K]) -> Option<[&mut T; K]> {
+		let mut indices = [0; K];
+		for (index, entity_id) in indices.iter_mut().zip(entity_ids.iter()) {
+			*index = self.get_index(*entity_id)?;
+		}
+		Some(self.components.get_disjoint_mut(indices).expect("The entity ids must be distinct"))
+	}
+
+	fn collect_entity_ids(&self, dst: &mut Vec<usize>) {
+		dst.extend_from_slice(&self.entity_ids);
+	}
+
+	fn for_each_mut<F: FnMut(usize, &mut T)>(&mut self, mut f: F) {
+		for (entity_id, component) in self.entity_ids.iter().zip(self.components.iter_mut()) {
+			f(*entity_id, component);
+		}
+	}
+
+	fn clone_storage(&self) -> Self where T: Clone {
+		self.clone()
+	}
+
+	fn borrow_dense_components(&self) -> Option<&Vec<T>> {
+		Some(&self.components)
+	}
+
+	fn borrow_dense_components_mut(&mut self) -> Option<&mut Vec<T>> {
+		Some(&mut self.components)
+	}
+}
+
+// For rare components. Memory is proportional to the number of entities
+// having the component. The iteration order is unspecified, use
+// BTreeMapStorage if systems depend on it, e.g. for replays.
+#[derive(Clone)]
+pub struct HashMapStorage<T> {
+	component_map: HashMap<usize, T>
+}
+
+impl<T> Default for HashMapStorage<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> HashMapStorage<T> {
+	pub fn new() -> Self {
+		HashMapStorage {
+			component_map: HashMap::new()
+		}
+	}
+}
+
+impl<T: 'static> ComponentStorage for HashMapStorage<T> {
+	type Component = T;
+
+	fn has(&self, entity_id: usize) -> bool {
+		self.component_map.contains_key(&entity_id)
+	}
+
+	fn add(&mut self, entity_id: usize, component: T) {
+		self.component_map.entry(entity_id).or_insert(component);
+	}
+
+	fn remove(&mut self, entity_id: usize) {
+		self.component_map.remove(&entity_id);
+	}
+
+	fn take(&mut self, entity_id: usize) -> Option<T> {
+		self.component_map.remove(&entity_id)
+	}
+
+	fn len(&self) -> usize {
+		self.component_map.len()
+	}
+
+	fn borrow_component(&self, entity_id: usize) -> Option<&T> {
+		self.component_map.get(&entity_id)
+	}
+
+	fn borrow_component_mut(&mut self, entity_id: usize) -> Option<&mut T> {
+		self.component_map.get_mut(&entity_id)
+	}
+
+	fn borrow_components_disjoint_mut<const K: usize>(&mut self, entity_ids: [usize; K]) -> Option<[&mut T; K]> {
+		unwrap_all(self.component_map.get_disjoint_mut(entity_ids.each_ref()))
+	}
+
+	fn collect_entity_ids(&self, dst: &mut Vec<usize>) {
+		dst.extend(self.component_map.keys());
+	}
+
+	fn for_each_mut<F: FnMut(usize, &mut T)>(&mut self, mut f: F) {
+		for (entity_id, component) in self.component_map.iter_mut() {
+			f(*entity_id, component);
+		}
+	}
+
+	fn clone_storage(&self) -> Self where T: Clone {
+		self.clone()
+	}
+}
+
+// For rare components, iterated in ascending entity id order
+#[derive(Clone)]
+pub struct BTreeMapStorage<T> {
+	component_map: BTreeMap<usize, T>
+}
+
+impl<T> Default for BTreeMapStorage<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> BTreeMapStorage<T> {
+	pub fn new() -> Self {
+		BTreeMapStorage {
+			component_map: BTreeMap::new()
+		}
+	}
+}
+
+impl<T: 'static> ComponentStorage for BTreeMapStorage<T> {
+	type Component = T;
+
+	fn has(&self, entity_id: usize) -> bool {
+		self.component_map.contains_key(&entity_id)
+	}
+
+	fn add(&mut self, entity_id: usize, component: T) {
+		self.component_map.entry(entity_id).or_insert(component);
+	}
+
+	fn remove(&mut self, entity_id: usize) {
+		self.component_map.remove(&entity_id);
+	}
+
+	fn take(&mut self, entity_id: usize) -> Option<T> {
+		self.component_map.remove(&entity_id)
+	}
+
+	fn len(&self) -> usize {
+		self.component_map.len()
+	}
+
+	fn borrow_component(&self, entity_id: usize) -> Option<&T> {
+		self.component_map.get(&entity_id)
+	}
+
+	fn borrow_component_mut(&mut self, entity_id: usize) -> Option<&mut T> {
+		self.component_map.get_mut(&entity_id)
+	}
+
+	// Walks the entities between the smallest and the largest entity id
+	fn borrow_components_disjoint_mut<const K: usize>(&mut self, entity_ids: [usize; K]) -> Option<[&mut T; K]> {
+		assert_distinct_entity_ids(&entity_ids);
+		let min = *entity_ids.iter().min()?;
+		let max = *entity_ids.iter().max()?;
+		let mut components = [(); K].map(|_| None);
+		for (entity_id, component) in self.component_map.range_mut(min..=max) {
+			if let Some(i) = entity_ids.iter().position(|id| id == entity_id) {
+				components[i] = Some(component);
+			}
+		}
+		unwrap_all(components)
+	}
+
+	fn collect_entity_ids(&self, dst: &mut Vec<usize>) {
+		dst.extend(self.component_map.keys());
+	}
+
+	fn for_each_mut<F: FnMut(usize, &mut T)>(&mut self, mut f: F) {
+		for (entity_id, component) in self.component_map.iter_mut() {
+			f(*entity_id, component);
+		}
+	}
+
+	fn clone_storage(&self) -> Self where T: Clone {
+		self.clone()
+	}
+}
+
+// For zero-sized tag components. Only a bit per entity is stored and every
+// entity borrows the same component instance. Taken components are created
+// with Default.
+#[derive(Clone)]
+pub struct MarkerStorage<T> {
+	marker: Option<T>, // The first added component
+	bits: Vec<u64>, // Bit per entity id
+	count: usize
+}
+
+impl<T> Default for MarkerStorage<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> MarkerStorage<T> {
+	const ZERO_SIZED: () = assert!(std::mem::size_of::<T>() == 0, "MarkerStorage is only for zero-sized components");
+
+	pub fn new() -> Self {
+		#[allow(clippy::let_unit_value)]
+		let _ = Self::ZERO_SIZED;
+		MarkerStorage {
+			marker: None,
+			bits: Vec::new(),
+			count: 0
+		}
+	}
+}
+
+impl<T: 'static + Default> ComponentStorage for MarkerStorage<T> {
+	type Component = T;
+
+	fn has(&self, entity_id: usize) -> bool {
+		match self.bits.get(entity_id / 64) {
+			Some(bits) => bits & (1 << (entity_id % 64)) != 0,
+			None => false
+		}
+	}
+
+	fn add(&mut self, entity_id: usize, component: T) {
+		if self.has(entity_id) {
+			return;
+		}
+		if self.bits.len() <= entity_id / 64 {
+			self.bits.resize(entity_id / 64 + 1, 0);
+		}
+		self.bits[entity_id / 64] |= 1 << (entity_id % 64);
+		self.count += 1;
+		if self.marker.is_none() {
+			self.marker = Some(component);
+		}
+	}
+
+	fn remove(&mut self, entity_id: usize) {
+		if !self.has(entity_id) {
+			return;
+		}
+		self.bits[entity_id / 64] &= !(1 << (entity_id % 64));
+		self.count -= 1;
+	}
+
+	fn take(&mut self, entity_id: usize) -> Option<T> {
+		if !self.has(entity_id) {
+			return None;
+		}
+		self.remove(entity_id);
+		Some(T::default())
+	}
+
+	fn len(&self) -> usize {
+		self.count
+	}
+
+	fn borrow_component(&self, entity_id: usize) -> Option<&T> {
+		match self.has(entity_id) {
+			true => self.marker.as_ref(),
+			false => None
+		}
+	}
+
+	fn borrow_component_mut(&mut self, entity_id: usize) -> Option<&mut T> {
+		match self.has(entity_id) {
+			true => self.marker.as_mut(),
+			false => None
+		}
+	}
+
+	fn borrow_components_disjoint_mut<const K: usize>(&mut self, entity_ids: [usize; K]) -> Option<[&mut T; K]> {
+		assert_distinct_entity_ids(&entity_ids);
+		if !entity_ids.iter().all(|entity_id| self.has(*entity_id)) {
+			return None;
+		}
+		let marker = self.marker.as_mut()? as *mut T;
+		// T is zero-sized, so the references don't overlap
+		Some([(); K].map(|_| unsafe { &mut *marker }))
+	}
+
+	fn collect_entity_ids(&self, dst: &mut Vec<usize>) {
+		for (i, bits) in self.bits.iter().enumerate() {
+			let mut bits = *bits;
+			while bits != 0 {
+				dst.push(i * 64 + bits.trailing_zeros() as usize);
+				bits &= bits - 1;
+			}
+		}
+	}
+
+	fn for_each_mut<F: FnMut(usize, &mut T)>(&mut self, mut f: F) {
+		let marker = match self.marker.as_mut() {
+			Some(marker) => marker,
+			None => return
+		};
+		for (i, bits) in self.bits.iter().enumerate() {
+			let mut bits = *bits;
+			while bits != 0 {
+				f(i * 64 + bits.trailing_zeros() as usize, marker);
+				bits &= bits - 1;
+			}
+		}
+	}
+
+	fn clone_storage(&self) -> Self where T: Clone {
+		self.clone()
+	}
+}
@@ -1,5 +1,20 @@
+use std::any::type_name;
+use std::time::Duration;
+
 use super::entity_manager::{EntityIdAccessor, EntityManager};
 
 pub trait System {
 	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor);
+
+	// Shown by the inspector and the profiler. The type name by default.
+	fn get_name(&self) -> &str {
+		type_name::<Self>()
+	}
+}
+
+// Name of a system and how long its last update took
+#[derive(Clone, Debug)]
+pub struct SystemTiming {
+	pub name: String,
+	pub duration: Duration
 }
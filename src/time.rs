@@ -0,0 +1,521 @@
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use super::component::Component;
+use super::entity_manager::{EntityIdAccessor, EntityManager};
+use super::storage::DenseStorage;
+use super::system::System;
+
+#[derive(Clone)]
+enum Clock {
+	Real(Option<Instant>), // The last update
+	Fixed(Duration),
+	Manual(Duration) // Advanced since the last update
+}
+
+// Resource holding the time of the current update. Updated by TimeSystem,
+// see World::add_time().
+#[derive(Clone)]
+pub struct Time {
+	clock: Clock,
+	delta: Duration,
+	elapsed: Duration
+}
+
+impl Default for Time {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Time {
+	// Measures the real time between updates. The delta of the first
+	// update is zero.
+	// Instant::now() panics on wasm32-unknown-unknown, use manual() there.
+	pub fn new() -> Self {
+		Time {
+			clock: Clock::Real(None),
+			delta: Duration::ZERO,
+			elapsed: Duration::ZERO
+		}
+	}
+
+	// Advances the same delta every update, e.g. for fixed steps, replays
+	// or headless tests
+	pub fn with_fixed_delta(delta: Duration) -> Self {
+		Time {
+			clock: Clock::Fixed(delta),
+			delta: Duration::ZERO,
+			elapsed: Duration::ZERO
+		}
+	}
+
+	// Advances by the deltas given to advance(), e.g. measured by the
+	// browser's requestAnimationFrame() on wasm32
+	pub fn manual() -> Self {
+		Time {
+			clock: Clock::Manual(Duration::ZERO),
+			delta: Duration::ZERO,
+			elapsed: Duration::ZERO
+		}
+	}
+
+	// Adds to the delta of the next update. Only for manual().
+	pub fn advance(&mut self, delta: Duration) -> &mut Self {
+		match &mut self.clock {
+			Clock::Manual(advanced) => *advanced += delta,
+			_ => {
+				// @TODO: Better error handling
+				println!("Time::advance() is only for Time::manual()");
+			}
+		};
+		self
+	}
+
+	// Since the last update
+	pub fn get_delta(&self) -> Duration {
+		self.delta
+	}
+
+	pub fn get_delta_seconds(&self) -> f32 {
+		self.delta.as_secs_f32()
+	}
+
+	// Sum of the deltas
+	pub fn get_elapsed(&self) -> Duration {
+		self.elapsed
+	}
+
+	pub fn get_elapsed_seconds(&self) -> f32 {
+		self.elapsed.as_secs_f32()
+	}
+
+	// @TODO: The real delta includes the time the world has been paused
+	pub fn update(&mut self) {
+		self.delta = match &mut self.clock {
+			Clock::Real(last) => {
+				let now = Instant::now();
+				let delta = match last {
+					Some(last) => now - *last,
+					None => Duration::ZERO
+				};
+				*last = Some(now);
+				delta
+			},
+			Clock::Fixed(delta) => *delta,
+			Clock::Manual(advanced) => std::mem::take(advanced)
+		};
+		self.elapsed += self.delta;
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimerMode {
+	Once,
+	Repeating
+}
+
+// Ticked by TimeSystem, which sends TimerFinished when it finishes
+#[derive(Clone, Debug)]
+pub struct Timer {
+	duration: Duration,
+	elapsed: Duration,
+	mode: TimerMode,
+	paused: bool,
+	finished: bool, // Once finished
+	times_finished: u32 // In the last tick
+}
+
+impl Component for Timer {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Timer {
+	pub fn new(duration: Duration, mode: TimerMode) -> Self {
+		Timer {
+			duration,
+			elapsed: Duration::ZERO,
+			mode,
+			paused: false,
+			finished: false,
+			times_finished: 0
+		}
+	}
+
+	pub fn once(duration: Duration) -> Self {
+		Self::new(duration, TimerMode::Once)
+	}
+
+	pub fn repeating(duration: Duration) -> Self {
+		Self::new(duration, TimerMode::Repeating)
+	}
+
+	pub fn get_duration(&self) -> Duration {
+		self.duration
+	}
+
+	// Keeps the elapsed time
+	pub fn set_duration(&mut self, duration: Duration) -> &mut Self {
+		self.duration = duration;
+		self
+	}
+
+	pub fn get_mode(&self) -> TimerMode {
+		self.mode
+	}
+
+	// Since the start or the last repeat
+	pub fn get_elapsed(&self) -> Duration {
+		self.elapsed
+	}
+
+	pub fn get_remaining(&self) -> Duration {
+		self.duration.saturating_sub(self.elapsed)
+	}
+
+	// 0.0 at the start, 1.0 when finished
+	pub fn get_fraction(&self) -> f32 {
+		match self.duration.is_zero() {
+			true => 1.0,
+			false => self.elapsed.as_secs_f32() / self.duration.as_secs_f32()
+		}
+	}
+
+	pub fn pause(&mut self) -> &mut Self {
+		self.paused = true;
+		self
+	}
+
+	pub fn resume(&mut self) -> &mut Self {
+		self.paused = false;
+		self
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	// A once timer stays finished until it is reset
+	pub fn is_finished(&self) -> bool {
+		match self.mode {
+			TimerMode::Once => self.finished,
+			TimerMode::Repeating => self.times_finished > 0
+		}
+	}
+
+	// Finished in the last tick
+	pub fn is_just_finished(&self) -> bool {
+		self.times_finished > 0
+	}
+
+	// More than once if a repeating timer is shorter than the delta
+	pub fn get_times_finished(&self) -> u32 {
+		self.times_finished
+	}
+
+	// Restarts, keeping the pause
+	pub fn reset(&mut self) -> &mut Self {
+		self.elapsed = Duration::ZERO;
+		self.finished = false;
+		self.times_finished = 0;
+		self
+	}
+
+	// Returns how many times the timer finished
+	pub fn tick(&mut self, delta: Duration) -> u32 {
+		self.times_finished = 0;
+		if self.paused {
+			return 0;
+		}
+		match self.mode {
+			TimerMode::Once => {
+				if !self.finished {
+					self.elapsed = self.elapsed.saturating_add(delta).min(self.duration);
+					if self.elapsed == self.duration {
+						self.finished = true;
+						self.times_finished = 1;
+					}
+				}
+			},
+			TimerMode::Repeating => {
+				self.elapsed = self.elapsed.saturating_add(delta);
+				if self.duration.is_zero() {
+					// Finishes every tick rather than infinitely
+					self.elapsed = Duration::ZERO;
+					self.times_finished = 1;
+				} else if self.elapsed >= self.duration {
+					let elapsed = self.elapsed.as_nanos();
+					let duration = self.duration.as_nanos();
+					let remainder = elapsed % duration;
+					self.elapsed = Duration::new((remainder / 1_000_000_000) as u64, (remainder % 1_000_000_000) as u32);
+					// Saturates for a delta of over u32::MAX durations
+					self.times_finished = u32::try_from(elapsed / duration).unwrap_or(u32::MAX);
+				}
+			}
+		};
+		self.times_finished
+	}
+}
+
+// Ready at first. Triggering it starts the cooldown, and TimeSystem sends
+// CooldownReady when it is over.
+#[derive(Clone, Debug)]
+pub struct Cooldown {
+	duration: Duration,
+	remaining: Duration,
+	paused: bool
+}
+
+impl Component for Cooldown {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Cooldown {
+	pub fn new(duration: Duration) -> Self {
+		Cooldown {
+			duration,
+			remaining: Duration::ZERO,
+			paused: false
+		}
+	}
+
+	pub fn get_duration(&self) -> Duration {
+		self.duration
+	}
+
+	// Applies from the next trigger
+	pub fn set_duration(&mut self, duration: Duration) -> &mut Self {
+		self.duration = duration;
+		self
+	}
+
+	pub fn get_remaining(&self) -> Duration {
+		self.remaining
+	}
+
+	pub fn is_ready(&self) -> bool {
+		self.remaining.is_zero()
+	}
+
+	// Returns false without restarting the cooldown if it isn't ready
+	pub fn trigger(&mut self) -> bool {
+		if !self.is_ready() {
+			return false;
+		}
+		self.remaining = self.duration;
+		true
+	}
+
+	// Makes it ready
+	pub fn reset(&mut self) -> &mut Self {
+		self.remaining = Duration::ZERO;
+		self
+	}
+
+	pub fn pause(&mut self) -> &mut Self {
+		self.paused = true;
+		self
+	}
+
+	pub fn resume(&mut self) -> &mut Self {
+		self.paused = false;
+		self
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	// Returns true if it got ready in this tick
+	pub fn tick(&mut self, delta: Duration) -> bool {
+		if self.paused || self.is_ready() {
+			return false;
+		}
+		self.remaining = self.remaining.saturating_sub(delta);
+		self.is_ready()
+	}
+}
+
+// The entity is despawned when the lifetime expires
+#[derive(Clone, Debug)]
+pub struct Lifetime {
+	timer: Timer
+}
+
+impl Component for Lifetime {
+	type Storage = DenseStorage<Self>;
+}
+
+impl Lifetime {
+	pub fn new(duration: Duration) -> Self {
+		Lifetime {
+			timer: Timer::once(duration)
+		}
+	}
+
+	pub fn get_remaining(&self) -> Duration {
+		self.timer.get_remaining()
+	}
+
+	// Extends or shortens the lifetime from now
+	pub fn set_remaining(&mut self, remaining: Duration) -> &mut Self {
+		self.timer.set_duration(remaining).reset();
+		self
+	}
+
+	pub fn pause(&mut self) -> &mut Self {
+		self.timer.pause();
+		self
+	}
+
+	pub fn resume(&mut self) -> &mut Self {
+		self.timer.resume();
+		self
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.timer.is_paused()
+	}
+}
+
+pub struct TimerFinished {
+	pub entity_id: usize,
+	pub times: u32 // More than once if a repeating timer is shorter than the delta
+}
+
+pub struct CooldownReady {
+	pub entity_id: usize
+}
+
+// Updates the Time resource, then ticks Timer, Cooldown and Lifetime by
+// its delta. Runs before the systems using them.
+pub struct TimeSystem;
+
+impl System for TimeSystem {
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		let delta = match manager.borrow_resource_mut::<Time>() {
+			Some(time) => {
+				time.update();
+				time.get_delta()
+			},
+			None => return
+		};
+
+		let mut finished_timers = Vec::new();
+		manager.for_each_mut::<Timer, _>(|id, timer| {
+			let times = timer.tick(delta);
+			if times > 0 {
+				finished_timers.push(TimerFinished {
+					entity_id: id,
+					times
+				});
+			}
+		});
+		for event in finished_timers {
+			manager.send_event(event);
+		}
+
+		let mut ready_ids = Vec::new();
+		manager.for_each_mut::<Cooldown, _>(|id, cooldown| {
+			if cooldown.tick(delta) {
+				ready_ids.push(id);
+			}
+		});
+		for entity_id in ready_ids {
+			manager.send_event(CooldownReady { entity_id });
+		}
+
+		let mut expired_ids = Vec::new();
+		manager.for_each_mut::<Lifetime, _>(|id, lifetime| {
+			if lifetime.timer.tick(delta) > 0 {
+				expired_ids.push(id);
+			}
+		});
+		// Despawned once the system returns
+		for entity_id in expired_ids {
+			manager.borrow_commands_mut().despawn(entity_id);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use crate::world::World;
+	use super::{Cooldown, Lifetime, Time, Timer};
+
+	fn millis(millis: u64) -> Duration {
+		Duration::from_millis(millis)
+	}
+
+	#[test]
+	fn once_timer() {
+		let mut timer = Timer::once(millis(100));
+		assert_eq!(timer.tick(millis(60)), 0);
+		assert_eq!(timer.tick(millis(60)), 1);
+		assert!(timer.is_finished() && timer.is_just_finished());
+		assert_eq!(timer.get_elapsed(), millis(100));
+		assert_eq!(timer.tick(millis(60)), 0);
+		assert!(timer.is_finished() && !timer.is_just_finished());
+
+		timer.reset().pause();
+		assert_eq!(timer.tick(millis(200)), 0);
+		timer.resume();
+		assert_eq!(timer.tick(millis(200)), 1);
+	}
+
+	#[test]
+	fn repeating_timer() {
+		let mut timer = Timer::repeating(millis(100));
+		assert_eq!(timer.tick(millis(250)), 2);
+		assert_eq!(timer.get_elapsed(), millis(50));
+		assert_eq!(timer.tick(millis(50)), 1);
+		assert_eq!(timer.get_elapsed(), Duration::ZERO);
+
+		// Saturates instead of truncating the count
+		let mut timer = Timer::repeating(Duration::from_nanos(1));
+		assert_eq!(timer.tick(Duration::from_secs(10)), u32::MAX);
+		assert_eq!(timer.get_elapsed(), Duration::ZERO);
+
+		let mut timer = Timer::repeating(Duration::ZERO);
+		assert_eq!(timer.tick(millis(10)), 1);
+	}
+
+	#[test]
+	fn cooldown() {
+		let mut cooldown = Cooldown::new(millis(100));
+		assert!(cooldown.is_ready());
+		assert!(cooldown.trigger());
+		assert!(!cooldown.trigger());
+		assert!(!cooldown.tick(millis(60)));
+		assert_eq!(cooldown.get_remaining(), millis(40));
+		assert!(cooldown.tick(millis(60)));
+		assert!(cooldown.is_ready());
+		// Got ready in the previous tick
+		assert!(!cooldown.tick(millis(60)));
+	}
+
+	#[test]
+	fn time_system() {
+		let mut world = World::new();
+		world.add_time(Time::manual());
+		let cooldown_id = world.create_entity();
+		let lifetime_id = world.create_entity();
+		let mut cooldown = Cooldown::new(millis(100));
+		cooldown.trigger();
+		world
+			.add_component_to_entity(cooldown_id, cooldown)
+			.add_component_to_entity(lifetime_id, Lifetime::new(millis(150)));
+
+		world.borrow_resource_mut::<Time>().unwrap().advance(millis(100));
+		world.update();
+		assert_eq!(world.borrow_resource::<Time>().unwrap().get_elapsed(), millis(100));
+		assert!(world.borrow_component::<Cooldown>(cooldown_id).unwrap().is_ready());
+		assert!(world.has_entity(lifetime_id));
+
+		world.borrow_resource_mut::<Time>().unwrap().advance(millis(50));
+		world.update();
+		assert!(!world.has_entity(lifetime_id));
+	}
+}
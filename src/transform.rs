@@ -0,0 +1,258 @@
+use super::checksum::{StableHash, StableHasher};
+use super::component::Component;
+use super::storage::DenseStorage;
+use super::entity_manager::{EntityIdAccessor, EntityManager};
+use super::hierarchy::{Children, Parent};
+use super::system::System;
+
+const IDENTITY: [f32; 16] = [
+	1.0, 0.0, 0.0, 0.0,
+	0.0, 1.0, 0.0, 0.0,
+	0.0, 0.0, 1.0, 0.0,
+	0.0, 0.0, 0.0, 1.0
+];
+
+// Transform relative to the parent entity, or to the world for root entities.
+// Setters mark the transform dirty so that TransformPropagationSystem
+// recomputes only the changed subtrees.
+#[derive(Clone)]
+pub struct LocalTransform {
+	translation: [f32; 3],
+	rotation: [f32; 4], // Quaternion, [x, y, z, w]
+	scale: [f32; 3],
+	dirty: bool
+}
+
+// World space matrix computed by TransformPropagationSystem. Column-major.
+#[derive(Clone)]
+pub struct GlobalTransform {
+	matrix: [f32; 16],
+	parent_id: Option<usize> // The parent the matrix was computed with
+}
+
+impl Component for LocalTransform {
+	type Storage = DenseStorage<Self>;
+}
+impl Component for GlobalTransform {
+	type Storage = DenseStorage<Self>;
+}
+
+impl StableHash for LocalTransform {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		self.translation.stable_hash(hasher);
+		self.rotation.stable_hash(hasher);
+		self.scale.stable_hash(hasher);
+		self.dirty.stable_hash(hasher);
+	}
+}
+
+impl StableHash for GlobalTransform {
+	fn stable_hash(&self, hasher: &mut StableHasher) {
+		self.matrix.stable_hash(hasher);
+		self.parent_id.stable_hash(hasher);
+	}
+}
+
+impl Default for LocalTransform {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl LocalTransform {
+	pub fn new() -> Self {
+		LocalTransform {
+			translation: [0.0, 0.0, 0.0],
+			rotation: [0.0, 0.0, 0.0, 1.0],
+			scale: [1.0, 1.0, 1.0],
+			dirty: true
+		}
+	}
+
+	pub fn from_2d(x: f32, y: f32, angle: f32, scale_x: f32, scale_y: f32) -> Self {
+		let mut transform = Self::new();
+		transform
+			.set_translation(x, y, 0.0)
+			.set_angle(angle)
+			.set_scale(scale_x, scale_y, 1.0);
+		transform
+	}
+
+	pub fn borrow_translation(&self) -> &[f32; 3] {
+		&self.translation
+	}
+
+	pub fn borrow_rotation(&self) -> &[f32; 4] {
+		&self.rotation
+	}
+
+	pub fn borrow_scale(&self) -> &[f32; 3] {
+		&self.scale
+	}
+
+	pub fn is_dirty(&self) -> bool {
+		self.dirty
+	}
+
+	pub fn set_translation(&mut self, x: f32, y: f32, z: f32) -> &mut Self {
+		self.translation = [x, y, z];
+		self.dirty = true;
+		self
+	}
+
+	pub fn set_rotation(&mut self, x: f32, y: f32, z: f32, w: f32) -> &mut Self {
+		self.rotation = [x, y, z, w];
+		self.dirty = true;
+		self
+	}
+
+	// Rotation around z axis, for 2D
+	pub fn set_angle(&mut self, angle: f32) -> &mut Self {
+		let half = angle * 0.5;
+		self.set_rotation(0.0, 0.0, half.sin(), half.cos())
+	}
+
+	pub fn set_scale(&mut self, x: f32, y: f32, z: f32) -> &mut Self {
+		self.scale = [x, y, z];
+		self.dirty = true;
+		self
+	}
+
+	fn compose(&self) -> [f32; 16] {
+		let [x, y, z, w] = self.rotation;
+		let [sx, sy, sz] = self.scale;
+		let [tx, ty, tz] = self.translation;
+		[
+			(1.0 - 2.0 * (y * y + z * z)) * sx,
+			2.0 * (x * y + w * z) * sx,
+			2.0 * (x * z - w * y) * sx,
+			0.0,
+			2.0 * (x * y - w * z) * sy,
+			(1.0 - 2.0 * (x * x + z * z)) * sy,
+			2.0 * (y * z + w * x) * sy,
+			0.0,
+			2.0 * (x * z + w * y) * sz,
+			2.0 * (y * z - w * x) * sz,
+			(1.0 - 2.0 * (x * x + y * y)) * sz,
+			0.0,
+			tx, ty, tz, 1.0
+		]
+	}
+}
+
+impl Default for GlobalTransform {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl GlobalTransform {
+	pub fn new() -> Self {
+		GlobalTransform {
+			matrix: IDENTITY,
+			parent_id: None
+		}
+	}
+
+	pub fn borrow_matrix(&self) -> &[f32; 16] {
+		&self.matrix
+	}
+
+	pub fn translation(&self) -> [f32; 3] {
+		[self.matrix[12], self.matrix[13], self.matrix[14]]
+	}
+}
+
+// Computes GlobalTransform of the entities having both LocalTransform and
+// GlobalTransform. A subtree is recomputed only if its root LocalTransform
+// is dirty or it has been reparented.
+pub struct TransformPropagationSystem;
+
+impl System for TransformPropagationSystem {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		let entity_ids = match accessor.borrow_ids_for_pair::<LocalTransform, GlobalTransform>(manager) {
+			Some(entity_ids) => entity_ids,
+			None => return
+		};
+
+		// (entity_id, parent_id, parent matrix, whether parent has been recomputed)
+		let mut stack = Vec::new();
+
+		for id in entity_ids.iter() {
+			// Entities whose parent lacks LocalTransform or GlobalTransform
+			// are handled as roots since the parent isn't traversed
+			let has_transformed_parent = match manager.borrow_component::<Parent>(*id) {
+				Some(parent) => manager.has_component::<LocalTransform>(parent.get()) &&
+					manager.has_component::<GlobalTransform>(parent.get()),
+				None => false
+			};
+			if !has_transformed_parent {
+				stack.push((*id, None, IDENTITY, false));
+			}
+		}
+
+		while let Some((id, parent_id, parent_matrix, parent_changed)) = stack.pop() {
+			let (matrix, changed) = match manager.borrow_component_pair_mut::<LocalTransform, GlobalTransform>(id) {
+				Some((local, global)) => {
+					let changed = parent_changed || local.dirty || global.parent_id != parent_id;
+					if changed {
+						global.matrix = multiply(&parent_matrix, &local.compose());
+						global.parent_id = parent_id;
+						local.dirty = false;
+					}
+					(global.matrix, changed)
+				},
+				None => continue
+			};
+
+			if let Some(children) = manager.borrow_component::<Children>(id) {
+				for child_id in children.borrow_entity_ids().iter() {
+					stack.push((*child_id, Some(id), matrix, changed));
+				}
+			}
+		}
+	}
+}
+
+fn multiply(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
+	let mut result = [0.0; 16];
+	for column in 0..4 {
+		for row in 0..4 {
+			let mut sum = 0.0;
+			for k in 0..4 {
+				sum += a[k * 4 + row] * b[column * 4 + k];
+			}
+			result[column * 4 + row] = sum;
+		}
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::world::World;
+	use super::{GlobalTransform, LocalTransform, TransformPropagationSystem};
+
+	#[test]
+	fn parent_without_local_transform_is_not_traversed() {
+		let mut world = World::new();
+		world
+			.register_component::<LocalTransform>()
+			.register_component::<GlobalTransform>()
+			.add_system(TransformPropagationSystem);
+		let parent_id = world.create_entity();
+		let child_id = world.create_entity();
+		let mut local = LocalTransform::new();
+		local.set_translation(1.0, 2.0, 0.0);
+		world
+			.add_component_to_entity(parent_id, GlobalTransform::new())
+			.add_component_to_entity(child_id, local)
+			.add_component_to_entity(child_id, GlobalTransform::new());
+		world.borrow_entity_manager_mut().set_parent(child_id, parent_id);
+
+		world.update();
+
+		// Handled as a root
+		assert_eq!(world.borrow_component::<GlobalTransform>(child_id).unwrap().translation(), [1.0, 2.0, 0.0]);
+	}
+}
@@ -0,0 +1,583 @@
+use std::f32::consts::PI;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use super::component::Component;
+use super::entity_manager::{EntityIdAccessor, EntityManager};
+use super::reflect::Value;
+use super::storage::DenseStorage;
+use super::system::System;
+use super::time::Time;
+
+// Standard easing curves, see https://easings.net
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Easing {
+	Linear,
+	QuadIn,
+	QuadOut,
+	QuadInOut,
+	CubicIn,
+	CubicOut,
+	CubicInOut,
+	SineIn,
+	SineOut,
+	SineInOut,
+	ExpoIn,
+	ExpoOut,
+	ExpoInOut,
+	BackIn,
+	BackOut,
+	BackInOut,
+	ElasticIn,
+	ElasticOut,
+	ElasticInOut,
+	BounceIn,
+	BounceOut,
+	BounceInOut
+}
+
+impl Easing {
+	// Maps 0.0..=1.0 to 0.0 at the start and 1.0 at the end. Back and
+	// elastic curves overshoot in between.
+	pub fn apply(&self, t: f32) -> f32 {
+		let t = t.clamp(0.0, 1.0);
+		match self {
+			Easing::Linear => t,
+			Easing::QuadIn => t * t,
+			Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+			Easing::QuadInOut => in_out(t, Easing::QuadIn),
+			Easing::CubicIn => t * t * t,
+			Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+			Easing::CubicInOut => in_out(t, Easing::CubicIn),
+			Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+			Easing::SineOut => (t * PI / 2.0).sin(),
+			Easing::SineInOut => (1.0 - (t * PI).cos()) / 2.0,
+			Easing::ExpoIn => match t == 0.0 {
+				true => 0.0,
+				false => 2.0_f32.powf(10.0 * t - 10.0)
+			},
+			Easing::ExpoOut => match t == 1.0 {
+				true => 1.0,
+				false => 1.0 - 2.0_f32.powf(-10.0 * t)
+			},
+			Easing::ExpoInOut => in_out(t, Easing::ExpoIn),
+			Easing::BackIn => {
+				let c = 1.70158;
+				(c + 1.0) * t * t * t - c * t * t
+			},
+			Easing::BackOut => 1.0 - Easing::BackIn.apply(1.0 - t),
+			Easing::BackInOut => in_out(t, Easing::BackIn),
+			Easing::ElasticIn => match t == 0.0 || t == 1.0 {
+				true => t,
+				false => -(2.0_f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * 2.0 * PI / 3.0).sin()
+			},
+			Easing::ElasticOut => 1.0 - Easing::ElasticIn.apply(1.0 - t),
+			Easing::ElasticInOut => in_out(t, Easing::ElasticIn),
+			Easing::BounceIn => 1.0 - Easing::BounceOut.apply(1.0 - t),
+			Easing::BounceOut => {
+				let (n, d) = (7.5625, 2.75);
+				if t < 1.0 / d {
+					n * t * t
+				} else if t < 2.0 / d {
+					let t = t - 1.5 / d;
+					n * t * t + 0.75
+				} else if t < 2.5 / d {
+					let t = t - 2.25 / d;
+					n * t * t + 0.9375
+				} else {
+					let t = t - 2.625 / d;
+					n * t * t + 0.984375
+				}
+			},
+			Easing::BounceInOut => in_out(t, Easing::BounceIn)
+		}
+	}
+}
+
+// The in curve for the first half and the mirrored one for the second
+fn in_out(t: f32, easing_in: Easing) -> f32 {
+	match t < 0.5 {
+		true => easing_in.apply(t * 2.0) / 2.0,
+		false => 1.0 - easing_in.apply((1.0 - t) * 2.0) / 2.0
+	}
+}
+
+pub fn lerp(from: f32, to: f32, ratio: f32) -> f32 {
+	from + (to - from) * ratio
+}
+
+// Writes the value at the eased ratio to the target component. Closures
+// taking (&mut T, ratio) are lenses.
+//
+// Tween::new(duration, Easing::QuadOut, move |position: &mut Position, ratio| {
+//     position.x = lerp(0.0, 10.0, ratio);
+// })
+pub trait Lens<T>: 'static {
+	fn lerp(&mut self, target: &mut T, ratio: f32);
+}
+
+impl<T, F: 'static + FnMut(&mut T, f32)> Lens<T> for F {
+	fn lerp(&mut self, target: &mut T, ratio: f32) {
+		self(target, ratio)
+	}
+}
+
+// Lens of a numeric field of a reflectable component of the entity, by
+// path, e.g. "Position.x". The path is resolved through the type registry
+// like World::get_field() and set_field(). Int fields are rounded.
+pub struct FieldLens {
+	path: String,
+	from: f64,
+	to: f64,
+	reported: bool // An unknown field is reported once, not every tick
+}
+
+impl FieldLens {
+	pub fn new(path: &str, from: f64, to: f64) -> Self {
+		FieldLens {
+			path: path.to_string(),
+			from,
+			to,
+			reported: false
+		}
+	}
+
+	pub fn lerp(&mut self, manager: &mut EntityManager, entity_id: usize, ratio: f32) {
+		let value = self.from + (self.to - self.from) * ratio as f64;
+		let value = match manager.get_field(entity_id, &self.path) {
+			Some(Value::Int(_)) => Value::Int(value.round() as i64),
+			Some(Value::Float(_)) => Value::Float(value),
+			_ => {
+				if !self.reported {
+					// @TODO: Better error handling
+					println!("Unknown numeric field {}", self.path);
+					self.reported = true;
+				}
+				return;
+			}
+		};
+		manager.set_field(entity_id, &self.path, &value);
+	}
+}
+
+enum StepLens<T> {
+	Component(Box<dyn Lens<T>>),
+	Field(FieldLens)
+}
+
+struct TweenStep<T> {
+	duration: Duration,
+	easing: Easing,
+	lens: StepLens<T>
+}
+
+// Animates the component T of the same entity, or the fields of its
+// reflectable components, playing the steps in sequence. Ticked by TweenSystem<T>, which sends TweenCompleted when the
+// last loop finishes. See World::add_tween().
+pub struct Tween<T> {
+	steps: Vec<TweenStep<T>>,
+	repeat: Option<u32>, // Loops after the first one, None for forever
+	yoyo: bool,
+	tag: u32,
+	paused: bool,
+	finished: bool,
+	loop_count: u32, // Finished loops
+	step_count: usize, // Finished steps in the current loop
+	elapsed: Duration // In the current step
+}
+
+impl<T: 'static> Component for Tween<T> {
+	type Storage = DenseStorage<Self>;
+}
+
+impl<T: 'static> Tween<T> {
+	pub fn new<L: Lens<T>>(duration: Duration, easing: Easing, lens: L) -> Self {
+		Self::empty().then(duration, easing, lens)
+	}
+
+	// Tween of a numeric field of a reflectable component by path, e.g.
+	// "Position.x". See FieldLens.
+	pub fn field(duration: Duration, easing: Easing, path: &str, from: f64, to: f64) -> Self {
+		Self::empty().then_field(duration, easing, path, from, to)
+	}
+
+	fn empty() -> Self {
+		Tween {
+			steps: Vec::new(),
+			repeat: Some(0),
+			yoyo: false,
+			tag: 0,
+			paused: false,
+			finished: false,
+			loop_count: 0,
+			step_count: 0,
+			elapsed: Duration::ZERO
+		}
+	}
+
+	// Adds a step played after the previous ones
+	pub fn then<L: Lens<T>>(mut self, duration: Duration, easing: Easing, lens: L) -> Self {
+		self.steps.push(TweenStep {
+			duration,
+			easing,
+			lens: StepLens::Component(Box::new(lens))
+		});
+		self
+	}
+
+	// Adds a step of a numeric field by path played after the previous ones
+	pub fn then_field(mut self, duration: Duration, easing: Easing, path: &str, from: f64, to: f64) -> Self {
+		self.steps.push(TweenStep {
+			duration,
+			easing,
+			lens: StepLens::Field(FieldLens::new(path, from, to))
+		});
+		self
+	}
+
+	// Plays the sequence 1 + times
+	pub fn with_repeat(mut self, times: u32) -> Self {
+		self.repeat = Some(times);
+		self
+	}
+
+	pub fn with_repeat_forever(mut self) -> Self {
+		self.repeat = None;
+		self
+	}
+
+	// Every other loop plays the sequence backward
+	pub fn with_yoyo(mut self, yoyo: bool) -> Self {
+		self.yoyo = yoyo;
+		self
+	}
+
+	// Reported in TweenCompleted to tell the tweens apart
+	pub fn with_tag(mut self, tag: u32) -> Self {
+		self.tag = tag;
+		self
+	}
+
+	pub fn get_tag(&self) -> u32 {
+		self.tag
+	}
+
+	pub fn pause(&mut self) -> &mut Self {
+		self.paused = true;
+		self
+	}
+
+	pub fn resume(&mut self) -> &mut Self {
+		self.paused = false;
+		self
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+
+	pub fn get_loop_count(&self) -> u32 {
+		self.loop_count
+	}
+
+	// Restarts from the first loop, keeping the pause. The target is
+	// updated in the next tick.
+	pub fn reset(&mut self) -> &mut Self {
+		self.finished = false;
+		self.loop_count = 0;
+		self.step_count = 0;
+		self.elapsed = Duration::ZERO;
+		self
+	}
+
+	fn is_backward(&self) -> bool {
+		self.yoyo && self.loop_count % 2 == 1
+	}
+
+	fn get_loop_duration(&self) -> Duration {
+		self.steps.iter().map(|step| step.duration).sum()
+	}
+}
+
+impl<T: 'static + Component> Tween<T> {
+	// Applies the current step at the elapsed time
+	fn apply(&mut self, manager: &mut EntityManager, entity_id: usize) {
+		let backward = self.is_backward();
+		let index = match backward {
+			true => self.steps.len() - 1 - self.step_count,
+			false => self.step_count
+		};
+		let step = &mut self.steps[index];
+		let ratio = match step.duration.is_zero() {
+			true => 1.0,
+			false => self.elapsed.as_secs_f32() / step.duration.as_secs_f32()
+		};
+		let ratio = match backward {
+			true => 1.0 - ratio,
+			false => ratio
+		};
+		let ratio = step.easing.apply(ratio);
+		match &mut step.lens {
+			StepLens::Component(lens) => if let Some(target) = manager.borrow_component_mut::<T>(entity_id) {
+				lens.lerp(target, ratio);
+			},
+			StepLens::Field(lens) => lens.lerp(manager, entity_id, ratio)
+		};
+	}
+
+	// Applies the steps to the entity, which the tween must be taken out
+	// of. Returns true if the tween finished in this tick.
+	fn tick(&mut self, delta: Duration, manager: &mut EntityManager, entity_id: usize) -> bool {
+		if self.paused || self.finished || self.steps.is_empty() {
+			return false;
+		}
+		let mut delta = delta;
+		loop {
+			let duration = match self.is_backward() {
+				true => self.steps[self.steps.len() - 1 - self.step_count].duration,
+				false => self.steps[self.step_count].duration
+			};
+			let remaining = duration - self.elapsed;
+			if delta < remaining {
+				self.elapsed += delta;
+				self.apply(manager, entity_id);
+				return false;
+			}
+
+			// The step ends at its exact end value
+			delta -= remaining;
+			self.elapsed = duration;
+			self.apply(manager, entity_id);
+			self.elapsed = Duration::ZERO;
+			self.step_count += 1;
+			if self.step_count < self.steps.len() {
+				continue;
+			}
+
+			self.step_count = 0;
+			self.loop_count += 1;
+			if let Some(repeat) = self.repeat {
+				if self.loop_count > repeat {
+					self.finished = true;
+					return true;
+				}
+			}
+			// Zero length loops are played once per tick
+			if delta.is_zero() || self.get_loop_duration().is_zero() {
+				return false;
+			}
+		}
+	}
+}
+
+pub struct TweenCompleted {
+	pub entity_id: usize,
+	pub tag: u32
+}
+
+// Ticks Tween<T> by the delta of the Time resource
+pub struct TweenSystem<T> {
+	_marker: PhantomData<T>
+}
+
+impl<T> Default for TweenSystem<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> TweenSystem<T> {
+	pub fn new() -> Self {
+		TweenSystem {
+			_marker: PhantomData
+		}
+	}
+}
+
+impl<T: 'static + Component> System for TweenSystem<T> {
+	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		let delta = match manager.borrow_resource::<Time>() {
+			Some(time) => time.get_delta(),
+			None => return
+		};
+		let entity_ids = match accessor.borrow_ids_for_pair::<Tween<T>, T>(manager) {
+			Some(entity_ids) => entity_ids.clone(),
+			None => return
+		};
+
+		for entity_id in entity_ids {
+			// Taken out so that the lenses can borrow the manager
+			let mut tween = match manager.borrow_component_mut::<Tween<T>>(entity_id) {
+				Some(tween) => std::mem::replace(tween, Tween::empty()),
+				None => continue
+			};
+			let finished = tween.tick(delta, manager, entity_id);
+			let tag = tween.tag;
+			// The lenses may have removed it
+			if let Some(current) = manager.borrow_component_mut::<Tween<T>>(entity_id) {
+				*current = tween;
+			}
+			if finished {
+				manager.send_event(TweenCompleted {
+					entity_id,
+					tag
+				});
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use crate::component::Component;
+	use crate::entity_manager::{EntityIdAccessor, EntityManager};
+	use crate::storage::DenseStorage;
+	use crate::system::System;
+	use crate::time::Time;
+	use crate::world::World;
+	use super::{lerp, Easing, FieldLens, Tween, TweenCompleted};
+
+	struct Position {
+		x: f32
+	}
+
+	impl Component for Position {
+		type Storage = DenseStorage<Self>;
+	}
+
+	// Collects the (entity_id, tag) of TweenCompleted
+	struct CompletedSystem;
+
+	impl System for CompletedSystem {
+		fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+			let completed = manager.borrow_events::<TweenCompleted>().unwrap().borrow_events().iter()
+				.map(|event| (event.entity_id, event.tag))
+				.collect::<Vec<(usize, u32)>>();
+			manager.borrow_resource_mut::<Vec<(usize, u32)>>().unwrap().extend(completed);
+		}
+	}
+
+	fn millis(millis: u64) -> Duration {
+		Duration::from_millis(millis)
+	}
+
+	fn create_manager() -> (EntityManager, usize) {
+		let mut manager = EntityManager::new();
+		manager.register::<Position>();
+		let entity_id = manager.create_entity();
+		manager.add_component_to_entity(entity_id, Position { x: 0.0 });
+		(manager, entity_id)
+	}
+
+	fn tick(tween: &mut Tween<Position>, delta: Duration, manager: &mut EntityManager, entity_id: usize) -> f32 {
+		tween.tick(delta, manager, entity_id);
+		manager.borrow_component::<Position>(entity_id).unwrap().x
+	}
+
+	fn move_x(from: f32, to: f32) -> impl FnMut(&mut Position, f32) {
+		move |position: &mut Position, ratio| position.x = lerp(from, to, ratio)
+	}
+
+	#[test]
+	fn easing_end_values() {
+		let easings = [
+			Easing::Linear, Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut,
+			Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut,
+			Easing::SineIn, Easing::SineOut, Easing::SineInOut,
+			Easing::ExpoIn, Easing::ExpoOut, Easing::ExpoInOut,
+			Easing::BackIn, Easing::BackOut, Easing::BackInOut,
+			Easing::ElasticIn, Easing::ElasticOut, Easing::ElasticInOut,
+			Easing::BounceIn, Easing::BounceOut, Easing::BounceInOut
+		];
+		for easing in easings.iter() {
+			assert!(easing.apply(0.0).abs() < 1e-6, "{:?} at 0", easing);
+			assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{:?} at 1", easing);
+		}
+	}
+
+	#[test]
+	fn steps_in_sequence() {
+		let (mut manager, entity_id) = create_manager();
+		let mut tween = Tween::new(millis(100), Easing::Linear, move_x(0.0, 10.0))
+			.then(millis(100), Easing::Linear, move_x(10.0, 30.0));
+
+		assert_eq!(tick(&mut tween, millis(50), &mut manager, entity_id), 5.0);
+		// Crosses into the second step
+		assert_eq!(tick(&mut tween, millis(100), &mut manager, entity_id), 20.0);
+		assert!(!tween.is_finished());
+		assert_eq!(tick(&mut tween, millis(100), &mut manager, entity_id), 30.0);
+		assert!(tween.is_finished());
+		assert_eq!(tick(&mut tween, millis(100), &mut manager, entity_id), 30.0);
+	}
+
+	#[test]
+	fn repeat_and_yoyo() {
+		let (mut manager, entity_id) = create_manager();
+		let mut tween = Tween::new(millis(100), Easing::Linear, move_x(0.0, 10.0))
+			.with_repeat(2)
+			.with_yoyo(true);
+
+		assert_eq!(tick(&mut tween, millis(100), &mut manager, entity_id), 10.0);
+		// The second loop plays backward
+		assert_eq!(tick(&mut tween, millis(25), &mut manager, entity_id), 7.5);
+		assert_eq!(tick(&mut tween, millis(75), &mut manager, entity_id), 0.0);
+		assert_eq!(tween.get_loop_count(), 2);
+		assert!(!tween.is_finished());
+		assert_eq!(tick(&mut tween, millis(100), &mut manager, entity_id), 10.0);
+		assert_eq!(tween.get_loop_count(), 3);
+		assert!(tween.is_finished());
+	}
+
+	#[test]
+	fn zero_duration_steps() {
+		let (mut manager, entity_id) = create_manager();
+		let mut tween = Tween::new(Duration::ZERO, Easing::Linear, move_x(0.0, 10.0));
+		assert_eq!(tick(&mut tween, Duration::ZERO, &mut manager, entity_id), 10.0);
+		assert!(tween.is_finished());
+
+		// A zero length loop is played once per tick
+		let mut tween = Tween::new(Duration::ZERO, Easing::Linear, move_x(0.0, 10.0)).with_repeat_forever();
+		tick(&mut tween, millis(100), &mut manager, entity_id);
+		tick(&mut tween, millis(100), &mut manager, entity_id);
+		assert_eq!(tween.get_loop_count(), 2);
+	}
+
+	#[test]
+	fn completed_event() {
+		let mut world = World::new();
+		world
+			.register_component::<Position>()
+			.add_time(Time::with_fixed_delta(millis(100)))
+			.add_tween::<Position>()
+			.add_system(CompletedSystem);
+		world.insert_resource(Vec::<(usize, u32)>::new());
+		let entity_id = world.create_entity();
+		world
+			.add_component_to_entity(entity_id, Position { x: 0.0 })
+			.add_component_to_entity(entity_id, Tween::new(millis(150), Easing::Linear, move_x(0.0, 10.0)).with_tag(7));
+
+		// The delta of the first update is the fixed one too
+		world.update();
+		assert!(world.borrow_resource::<Vec<(usize, u32)>>().unwrap().is_empty());
+		world.update();
+		world.update();
+		assert_eq!(world.borrow_resource::<Vec<(usize, u32)>>().unwrap(), &vec![(entity_id, 7)]);
+		assert_eq!(world.borrow_component::<Position>(entity_id).unwrap().x, 10.0);
+	}
+
+	#[test]
+	fn unknown_field_reported_once() {
+		let (mut manager, entity_id) = create_manager();
+		let mut lens = FieldLens::new("Position.y", 0.0, 1.0);
+		lens.lerp(&mut manager, entity_id, 0.5);
+		assert!(lens.reported);
+		lens.lerp(&mut manager, entity_id, 0.5);
+		assert!(lens.reported);
+	}
+}
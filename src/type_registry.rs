@@ -0,0 +1,295 @@
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::mem::{align_of, size_of};
+
+use super::component::Component;
+use super::entity_manager::EntityManager;
+use super::reflect::{FieldInfo, Reflect, Value};
+
+type HasFn = fn(&EntityManager, usize) -> bool;
+type AddDefaultFn = fn(&mut EntityManager, usize);
+type MoveFn = fn(&mut EntityManager, usize, &mut EntityManager, usize);
+type DebugFn = fn(&EntityManager, usize) -> Option<String>;
+type GetFieldFn = fn(&EntityManager, usize, &str) -> Option<Value>;
+pub(crate) type SetFieldFn = fn(&mut EntityManager, usize, &str, &Value) -> bool;
+
+#[derive(Clone)]
+struct ReflectFns {
+	get_field: GetFieldFn,
+	set_field: SetFieldFn
+}
+
+// What is known about a registered component type at runtime
+#[derive(Clone)]
+pub struct TypeInfo {
+	name: String,
+	type_id: TypeId,
+	type_name: &'static str,
+	size: usize,
+	align: usize,
+	fields: Option<Vec<FieldInfo>>, // Only reflectable components
+	has: HasFn,
+	move_component: MoveFn,
+	add_default: Option<AddDefaultFn>, // Only constructible components
+	debug: Option<DebugFn>, // Only debuggable components
+	reflect_fns: Option<ReflectFns>
+}
+
+impl TypeInfo {
+	// Stable name given on registration, the type name without the
+	// module path otherwise
+	pub fn get_name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn get_type_id(&self) -> TypeId {
+		self.type_id
+	}
+
+	// Not stable across builds, see std::any::type_name()
+	pub fn get_type_name(&self) -> &'static str {
+		self.type_name
+	}
+
+	pub fn get_size(&self) -> usize {
+		self.size
+	}
+
+	pub fn get_align(&self) -> usize {
+		self.align
+	}
+
+	pub fn borrow_fields(&self) -> Option<&Vec<FieldInfo>> {
+		self.fields.as_ref()
+	}
+
+	pub fn is_reflectable(&self) -> bool {
+		self.reflect_fns.is_some()
+	}
+
+	pub fn is_constructible(&self) -> bool {
+		self.add_default.is_some()
+	}
+
+	pub fn is_debuggable(&self) -> bool {
+		self.debug.is_some()
+	}
+
+	pub fn has_component(&self, manager: &EntityManager, entity_id: usize) -> bool {
+		(self.has)(manager, entity_id)
+	}
+
+	pub fn get_field(&self, manager: &EntityManager, entity_id: usize, field_name: &str) -> Option<Value> {
+		match self.reflect_fns.as_ref() {
+			Some(fns) => (fns.get_field)(manager, entity_id, field_name),
+			None => None
+		}
+	}
+
+	// Debug format of debuggable components, the reflected fields of
+	// reflectable components, the name otherwise. None if the entity
+	// doesn't have the component.
+	pub fn format_component(&self, manager: &EntityManager, entity_id: usize) -> Option<String> {
+		if !self.has_component(manager, entity_id) {
+			return None;
+		}
+		if let Some(debug) = self.debug {
+			return debug(manager, entity_id);
+		}
+		let fields = match self.fields.as_ref() {
+			Some(fields) if !fields.is_empty() => fields,
+			_ => return Some(self.name.clone())
+		};
+		let fields = fields.iter().map(|field| match self.get_field(manager, entity_id, field.name) {
+			Some(value) => format!("{}: {}", field.name, value),
+			None => format!("{}: ?", field.name)
+		}).collect::<Vec<String>>();
+		Some(format!("{} {{ {} }}", self.name, fields.join(", ")))
+	}
+
+	// The registry is owned by the EntityManager, so setting goes through
+	// the copied fn. See EntityManager::set_field().
+	pub(crate) fn get_set_field_fn(&self) -> Option<SetFieldFn> {
+		self.reflect_fns.as_ref().map(|fns| fns.set_field)
+	}
+
+	pub(crate) fn get_move_fn(&self) -> MoveFn {
+		self.move_component
+	}
+
+	pub(crate) fn get_add_default_fn(&self) -> Option<AddDefaultFn> {
+		self.add_default
+	}
+}
+
+pub struct TypeRegistry {
+	type_infos: Vec<TypeInfo>, // In registration order
+	type_map: HashMap<TypeId, usize>, // TypeId -> index in type_infos
+	name_map: HashMap<String, usize> // name -> index in type_infos
+}
+
+impl Default for TypeRegistry {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl TypeRegistry {
+	pub fn new() -> Self {
+		TypeRegistry {
+			type_infos: Vec::new(),
+			type_map: HashMap::new(),
+			name_map: HashMap::new()
+		}
+	}
+
+	// Does nothing if already registered
+	pub fn register<T: 'static + Component>(&mut self) -> &mut Self {
+		if self.type_map.contains_key(&TypeId::of::<T>()) {
+			return self;
+		}
+		let name = get_short_type_name(type_name::<T>());
+		self.name_map.insert(name.clone(), self.type_infos.len());
+		self.type_map.insert(TypeId::of::<T>(), self.type_infos.len());
+		self.type_infos.push(TypeInfo {
+			name,
+			type_id: TypeId::of::<T>(),
+			type_name: type_name::<T>(),
+			size: size_of::<T>(),
+			align: align_of::<T>(),
+			fields: None,
+			has: has_component::<T>,
+			move_component: move_component::<T>,
+			add_default: None,
+			debug: None,
+			reflect_fns: None
+		});
+		self
+	}
+
+	// Registers the component if needed and renames it
+	pub fn register_named<T: 'static + Component>(&mut self, name: &str) -> &mut Self {
+		self.register::<T>();
+		let index = *self.type_map.get(&TypeId::of::<T>()).unwrap();
+		let type_info = &mut self.type_infos[index];
+		if type_info.name != name {
+			self.name_map.remove(&type_info.name);
+			self.name_map.insert(name.to_string(), index);
+			type_info.name = name.to_string();
+		}
+		self
+	}
+
+	pub fn register_reflectable<T: 'static + Component + Reflect>(&mut self, name: &str) -> &mut Self {
+		self.register_named::<T>(name);
+		let index = *self.type_map.get(&TypeId::of::<T>()).unwrap();
+		let type_info = &mut self.type_infos[index];
+		type_info.fields = Some(T::get_field_infos());
+		type_info.reflect_fns = Some(ReflectFns {
+			get_field: get_field::<T>,
+			set_field: set_field::<T>
+		});
+		self
+	}
+
+	// Reflectable components which can also be created by name, e.g. by scripts
+	pub fn register_constructible<T: 'static + Component + Reflect + Default>(&mut self, name: &str) -> &mut Self {
+		self.register_reflectable::<T>(name);
+		let index = *self.type_map.get(&TypeId::of::<T>()).unwrap();
+		self.type_infos[index].add_default = Some(add_default_component::<T>);
+		self
+	}
+
+	// Components printed with Debug by World::dump()
+	pub fn register_debuggable<T: 'static + Component + Debug>(&mut self) -> &mut Self {
+		self.register::<T>();
+		let index = *self.type_map.get(&TypeId::of::<T>()).unwrap();
+		self.type_infos[index].debug = Some(format_component::<T>);
+		self
+	}
+
+	// Registers a copy of a type info of another registry, e.g. when an
+	// entity is moved to another world. Does nothing if already registered.
+	pub(crate) fn register_type_info(&mut self, type_info: &TypeInfo) {
+		if self.type_map.contains_key(&type_info.type_id) {
+			return;
+		}
+		self.name_map.insert(type_info.name.clone(), self.type_infos.len());
+		self.type_map.insert(type_info.type_id, self.type_infos.len());
+		self.type_infos.push(type_info.clone());
+	}
+
+	pub fn borrow_type_infos(&self) -> &Vec<TypeInfo> {
+		&self.type_infos
+	}
+
+	pub fn borrow_type_info(&self, name: &str) -> Option<&TypeInfo> {
+		self.name_map.get(name).map(|index| &self.type_infos[*index])
+	}
+
+	pub fn borrow_type_info_by_type_id(&self, type_id: TypeId) -> Option<&TypeInfo> {
+		self.type_map.get(&type_id).map(|index| &self.type_infos[*index])
+	}
+
+	// path is "ComponentName.field_name"
+	pub fn get_field(&self, manager: &EntityManager, entity_id: usize, path: &str) -> Option<Value> {
+		let (type_name, field_name) = split_path(path)?;
+		self.borrow_type_info(type_name)?.get_field(manager, entity_id, field_name)
+	}
+
+	// Returns the setter and the field name for the path
+	pub(crate) fn find_field_setter<'a>(&self, path: &'a str) -> Option<(SetFieldFn, &'a str)> {
+		let (type_name, field_name) = split_path(path)?;
+		let set_field = self.borrow_type_info(type_name)?.get_set_field_fn()?;
+		Some((set_field, field_name))
+	}
+}
+
+fn split_path(path: &str) -> Option<(&str, &str)> {
+	let mut split = path.splitn(2, '.');
+	match (split.next(), split.next()) {
+		(Some(type_name), Some(field_name)) => Some((type_name, field_name)),
+		_ => None
+	}
+}
+
+// "foo::bar::Baz<qux::Quux>" -> "Baz<qux::Quux>"
+pub(crate) fn get_short_type_name(type_name: &str) -> String {
+	let end = type_name.find('<').unwrap_or(type_name.len());
+	let start = match type_name[..end].rfind("::") {
+		Some(index) => index + 2,
+		None => 0
+	};
+	type_name[start..].to_string()
+}
+
+fn has_component<T: 'static + Component>(manager: &EntityManager, entity_id: usize) -> bool {
+	manager.has_component::<T>(entity_id)
+}
+
+fn move_component<T: 'static + Component>(src: &mut EntityManager, src_entity_id: usize, dst: &mut EntityManager, dst_entity_id: usize) {
+	if let Some(component) = src.take_component::<T>(src_entity_id) {
+		dst.register::<T>();
+		dst.add_component_to_entity(dst_entity_id, component);
+	}
+}
+
+fn add_default_component<T: 'static + Component + Default>(manager: &mut EntityManager, entity_id: usize) {
+	manager.add_component_to_entity(entity_id, T::default());
+}
+
+fn format_component<T: 'static + Component + Debug>(manager: &EntityManager, entity_id: usize) -> Option<String> {
+	manager.borrow_component::<T>(entity_id).map(|component| format!("{:?}", component))
+}
+
+fn get_field<T: 'static + Component + Reflect>(manager: &EntityManager, entity_id: usize, field_name: &str) -> Option<Value> {
+	manager.borrow_component::<T>(entity_id)?.get_field(field_name)
+}
+
+fn set_field<T: 'static + Component + Reflect>(manager: &mut EntityManager, entity_id: usize, field_name: &str, value: &Value) -> bool {
+	match manager.borrow_component_mut::<T>(entity_id) {
+		Some(component) => component.set_field(field_name, value),
+		None => false
+	}
+}
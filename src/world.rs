@@ -1,19 +1,132 @@
-use super::entity_manager::{EntityIdAccessor, EntityManager};
-use super::component::Component;
-use super::system::System;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+use super::entity_manager::{EntityIdAccessor, EntityManager, MoveEntityError, StorageMode};
+use super::component::{Component, ComponentRegistration};
+use super::bundle::Bundle;
+use super::entity::{Entity, WorldId};
+use super::hooks::HookKind;
+use super::system::{System, SystemTiming};
+use super::input::{Input, InputSystem};
+use super::spatial::{SpatialIndex, SpatialIndexSystem, SpatialPosition};
+use super::time::{Cooldown, CooldownReady, Lifetime, Time, TimeSystem, Timer, TimerFinished};
+use super::tween::{Tween, TweenCompleted, TweenSystem};
+#[cfg(feature = "serde")]
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeSeed};
+#[cfg(feature = "serde")]
+use super::serialization::{MapEntities, SerializationRegistry, WorldDeserializer, WorldSerializer};
+#[cfg(feature = "serde")]
+use super::scene::{SceneInstanceId, SceneInstances, SceneMember};
+#[cfg(feature = "serde")]
+use super::replay::{
+	InputRegistry,
+	Recorder,
+	Replay,
+	ReplayDeserializer,
+	ReplayDivergence,
+	ReplaySerializer,
+	apply_inputs
+};
+#[cfg(feature = "collision2d")]
+use super::collision2d::{Collider, CollisionEnded, CollisionIndex, CollisionStarted, CollisionSystem};
+#[cfg(feature = "inspector")]
+use std::io;
+#[cfg(feature = "inspector")]
+use std::net::SocketAddr;
+#[cfg(feature = "inspector")]
+use super::inspector::Inspector;
+use super::events::Events;
+use super::profiler::FrameStats;
+use super::checksum::{ChecksumRegistry, ChecksumReport, StableHash};
+use super::hierarchy::{Children, Parent};
+use super::reflect::{Reflect, Value};
+use super::type_registry::{TypeRegistry, get_short_type_name};
+use super::dynamic::{ComponentId, ComponentLayout};
+
+// Captured by World::snapshot(). Holds the entities, the frame counters and
+// the cloneable component managers and resources.
+pub struct WorldSnapshot {
+	entity_manager: EntityManager
+}
+
+// Returned by World::snapshot() in Archetype mode if a component is not
+// cloneable, since the entities couldn't be restored consistently
+#[derive(Debug)]
+pub struct SnapshotError {
+	pub component_name: String
+}
+
+impl fmt::Display for SnapshotError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Component {} is not cloneable, register it with register_cloneable_component()", self.component_name)
+	}
+}
+
+impl Error for SnapshotError {}
 
 pub struct World {
+	world_id: WorldId,
 	entity_manager: EntityManager,
 	entity_id_accessor: EntityIdAccessor,
-	systems: Vec<Box<dyn System>>
+	systems: Vec<Box<dyn System>>,
+	system_timings: Vec<SystemTiming>, // Same order with systems
+	paused: bool,
+	pending_steps: u64, // Frames to run while paused
+	#[cfg(feature = "inspector")]
+	inspector: Option<Inspector>,
+	checksum_registry: ChecksumRegistry,
+	#[cfg(feature = "serde")]
+	serialization_registry: SerializationRegistry,
+	#[cfg(feature = "serde")]
+	scene_instances: SceneInstances,
+	#[cfg(feature = "serde")]
+	input_registry: InputRegistry,
+	#[cfg(feature = "serde")]
+	recorder: Option<Recorder>
+}
+
+impl Default for World {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl fmt::Debug for World {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "systems: {}", self.systems.len())?;
+		f.write_str(&self.dump())
+	}
 }
 
 impl World {
 	pub fn new() -> Self {
+		Self::with_storage_mode(StorageMode::PerComponent)
+	}
+
+	pub fn with_storage_mode(storage_mode: StorageMode) -> Self {
 		World {
-			entity_manager: EntityManager::new(),
+			world_id: WorldId::new(),
+			entity_manager: EntityManager::with_storage_mode(storage_mode),
 			entity_id_accessor: EntityIdAccessor::new(),
-			systems: vec![]
+			systems: vec![],
+			system_timings: vec![],
+			paused: false,
+			pending_steps: 0,
+			#[cfg(feature = "inspector")]
+			inspector: None,
+			checksum_registry: ChecksumRegistry::new(),
+			#[cfg(feature = "serde")]
+			serialization_registry: SerializationRegistry::new(),
+			#[cfg(feature = "serde")]
+			scene_instances: SceneInstances::new(),
+			#[cfg(feature = "serde")]
+			input_registry: InputRegistry::new(),
+			#[cfg(feature = "serde")]
+			recorder: None
 		}
 	}
 
@@ -23,27 +136,747 @@ impl World {
 
 	pub fn remove_entity(&mut self, entity_id: usize) {
 		self.entity_manager.remove_entity(entity_id);
+		self.apply_hook_commands();
+	}
+
+	pub fn get_world_id(&self) -> WorldId {
+		self.world_id
+	}
+
+	// Handle of a live entity, tagged with this world
+	pub fn get_entity(&self, entity_id: usize) -> Option<Entity> {
+		match self.entity_manager.has_entity(entity_id) {
+			true => Some(Entity::new(self.world_id, entity_id)),
+			false => None
+		}
+	}
+
+	// False if the handle is of another world or the entity is removed
+	pub fn is_valid_entity(&self, entity: Entity) -> bool {
+		entity.get_world_id() == self.world_id && self.entity_manager.has_entity(entity.get_id())
+	}
+
+	// Panics if the handle is of another world
+	fn get_entity_id(&self, entity: Entity) -> usize {
+		assert!(entity.get_world_id() == self.world_id, "Entity {:?} is not of world {:?}", entity, self.world_id);
+		entity.get_id()
+	}
+
+	// The *_by_handle() methods are the entity id versions taking a handle.
+	// They panic if the handle is of another world.
+
+	pub fn remove_entity_by_handle(&mut self, entity: Entity) {
+		let entity_id = self.get_entity_id(entity);
+		self.remove_entity(entity_id);
+	}
+
+	pub fn has_component_by_handle<T: 'static + Component>(&self, entity: Entity) -> bool {
+		self.has_component::<T>(self.get_entity_id(entity))
+	}
+
+	pub fn borrow_component_by_handle<T: 'static + Component>(&self, entity: Entity) -> Option<&T> {
+		self.borrow_component::<T>(self.get_entity_id(entity))
+	}
+
+	pub fn borrow_component_mut_by_handle<T: 'static + Component>(&mut self, entity: Entity) -> Option<&mut T> {
+		let entity_id = self.get_entity_id(entity);
+		self.borrow_component_mut::<T>(entity_id)
+	}
+
+	pub fn add_component_to_entity_by_handle<T: 'static + Component>(&mut self, entity: Entity, component: T) -> &mut Self {
+		let entity_id = self.get_entity_id(entity);
+		self.add_component_to_entity(entity_id, component)
+	}
+
+	pub fn remove_component_from_entity_by_handle<T: 'static + Component>(&mut self, entity: Entity) -> &mut Self {
+		let entity_id = self.get_entity_id(entity);
+		self.remove_component_from_entity::<T>(entity_id)
+	}
+
+	// Moves the entity with its components to another world and returns
+	// its handle in that world. Components are moved type-erased through
+	// the type registry, so only the component types in the registry and
+	// the dynamic components are moved, the others are dropped. Component
+	// types unknown to the other world are registered there with the same
+	// names, reflection and Debug, but not as cloneable, serializable or
+	// hashable. Hierarchy links are not moved.
+	//
+	// Fails without moving anything if the handle is of another world, the
+	// entity is removed or a component can't be moved, see MoveEntityError.
+	pub fn move_entity_to(&mut self, other: &mut World, entity: Entity) -> Result<Entity, MoveEntityError> {
+		if !self.is_valid_entity(entity) {
+			return Err(MoveEntityError::UnknownEntity(entity.get_id()));
+		}
+		let entity_id = self.entity_manager.move_entity_to(&mut other.entity_manager, entity.get_id())?;
+		self.apply_hook_commands();
+		other.apply_hook_commands();
+		Ok(Entity::new(other.world_id, entity_id))
+	}
+
+	pub fn borrow_entity_manager(&self) -> &EntityManager {
+		&self.entity_manager
+	}
+
+	pub fn borrow_entity_manager_mut(&mut self) -> &mut EntityManager {
+		&mut self.entity_manager
+	}
+
+	pub fn has_entity(&self, entity_id: usize) -> bool {
+		self.entity_manager.has_entity(entity_id)
+	}
+
+	// Live entity ids in ascending order
+	pub fn get_entity_ids(&self) -> Vec<usize> {
+		self.entity_manager.get_entity_ids()
+	}
+
+	pub fn has_component<T: 'static + Component>(&self, entity_id: usize) -> bool {
+		self.entity_manager.has_component::<T>(entity_id)
+	}
+
+	pub fn borrow_component<T: 'static + Component>(&self, entity_id: usize) -> Option<&T> {
+		self.entity_manager.borrow_component::<T>(entity_id)
+	}
+
+	pub fn borrow_component_mut<T: 'static + Component>(&mut self, entity_id: usize) -> Option<&mut T> {
+		self.entity_manager.borrow_component_mut::<T>(entity_id)
+	}
+
+	pub fn get_frame(&self) -> u64 {
+		self.entity_manager.get_frame()
+	}
+
+	pub fn get_system_count(&self) -> usize {
+		self.systems.len()
+	}
+
+	// Readable listing of the live entities and their components, the free
+	// entity ids and the frame counters. Debuggable components are printed
+	// with Debug, reflectable ones with their fields and the others with
+	// their names. Components of types not in the type registry are listed
+	// by their short type names. The text is stable for the same world
+	// state, so it can be compared with golden files in tests.
+	pub fn dump(&self) -> String {
+		self.entity_manager.dump()
 	}
 
 	pub fn register_component<T: 'static + Component>(&mut self) -> &mut Self {
 		self.entity_manager.register::<T>();
+		self.entity_manager.borrow_type_registry_mut().register::<T>();
+		self
+	}
+
+	// Registers the component under a stable name used by the type registry
+	pub fn register_named_component<T: 'static + Component>(&mut self, name: &str) -> &mut Self {
+		self.entity_manager.register::<T>();
+		self.entity_manager.borrow_type_registry_mut().register_named::<T>(name);
+		self
+	}
+
+	// Registers the component and makes its fields readable and writable
+	// by path, e.g. "Position.x", with get_field() and set_field()
+	pub fn register_reflectable_component<T: 'static + Component + Reflect>(&mut self, name: &str) -> &mut Self {
+		self.entity_manager.register::<T>();
+		self.entity_manager.borrow_type_registry_mut().register_reflectable::<T>(name);
+		self
+	}
+
+	// Reflectable and also creatable by name, e.g. by scripts
+	pub fn register_constructible_component<T>(&mut self, name: &str) -> &mut Self
+	where T: 'static + Component + Reflect + Default {
+		self.entity_manager.register::<T>();
+		self.entity_manager.borrow_type_registry_mut().register_constructible::<T>(name);
+		self
+	}
+
+	// Registers the component and makes dump() print it with Debug
+	pub fn register_debuggable_component<T: 'static + Component + Debug>(&mut self) -> &mut Self {
+		self.entity_manager.register::<T>();
+		self.entity_manager.borrow_type_registry_mut().register_debuggable::<T>();
+		self
+	}
+
+	// Registers a component deriving Component with the options of
+	// its #[component(...)] attribute
+	pub fn register<T: 'static + ComponentRegistration>(&mut self) -> &mut Self {
+		T::register_to(self);
+		self
+	}
+
+	// Registers the component and makes it saved and loaded under the name.
+	// The name must be stable across builds.
+	#[cfg(feature = "serde")]
+	pub fn register_serializable_component<T>(&mut self, name: &str) -> &mut Self
+	where T: 'static + Component + Serialize + for<'de> Deserialize<'de> {
+		self.entity_manager.register::<T>();
+		self.entity_manager.borrow_type_registry_mut().register_named::<T>(name);
+		self.serialization_registry.register::<T>(name);
+		self
+	}
+
+	// Cloneable components are captured by snapshot()
+	pub fn register_cloneable_component<T: 'static + Component + Clone>(&mut self) -> &mut Self {
+		self.entity_manager.register_cloneable::<T>();
+		self.entity_manager.borrow_type_registry_mut().register::<T>();
+		self
+	}
+
+	// Registers the component and makes it covered by checksum()
+	pub fn register_hashable_component<T: 'static + Component + StableHash>(&mut self, name: &str) -> &mut Self {
+		self.entity_manager.register::<T>();
+		self.entity_manager.borrow_type_registry_mut().register_named::<T>(name);
+		self.checksum_registry.register::<T>(name);
 		self
 	}
 
 	pub fn add_system<T: 'static + System>(&mut self, system: T) -> &mut Self {
+		self.system_timings.push(SystemTiming {
+			name: get_short_type_name(system.get_name()),
+			duration: Duration::ZERO
+		});
 		self.systems.push(Box::new(system));
 		self
 	}
 
+	pub fn add_bundle_to_entity<T: Bundle>(&mut self, entity_id: usize, bundle: T) -> &mut Self {
+		self.entity_manager.add_bundle_to_entity(entity_id, bundle);
+		self.apply_hook_commands();
+		self
+	}
+
 	pub fn add_component_to_entity<T: 'static + Component>(&mut self, entity_id: usize, component: T) -> &mut Self {
 		self.entity_manager.add_component_to_entity(entity_id, component);
+		self.apply_hook_commands();
 		self
 	}
 
+	// Adds the component, or replaces the value if the entity already has it
+	pub fn insert_component_to_entity<T: 'static + Component>(&mut self, entity_id: usize, component: T) -> &mut Self {
+		self.entity_manager.insert_component_to_entity(entity_id, component);
+		self.apply_hook_commands();
+		self
+	}
+
+	pub fn remove_component_from_entity<T: 'static + Component>(&mut self, entity_id: usize) -> &mut Self {
+		self.entity_manager.remove_component_from_entity::<T>(entity_id);
+		self.apply_hook_commands();
+		self
+	}
+
+	// Lifecycle hooks of a component type, see hooks.rs. Called in the
+	// registration order.
+	pub fn on_add<T: 'static + Component, F: 'static + Fn(&mut EntityManager, usize)>(&mut self, hook: F) -> &mut Self {
+		self.entity_manager.add_hook::<T, F>(HookKind::OnAdd, hook);
+		self
+	}
+
+	pub fn on_insert<T: 'static + Component, F: 'static + Fn(&mut EntityManager, usize)>(&mut self, hook: F) -> &mut Self {
+		self.entity_manager.add_hook::<T, F>(HookKind::OnInsert, hook);
+		self
+	}
+
+	pub fn on_replace<T: 'static + Component, F: 'static + Fn(&mut EntityManager, usize)>(&mut self, hook: F) -> &mut Self {
+		self.entity_manager.add_hook::<T, F>(HookKind::OnReplace, hook);
+		self
+	}
+
+	pub fn on_remove<T: 'static + Component, F: 'static + Fn(&mut EntityManager, usize)>(&mut self, hook: F) -> &mut Self {
+		self.entity_manager.add_hook::<T, F>(HookKind::OnRemove, hook);
+		self
+	}
+
+	// Outside systems, the commands pushed by hooks are applied right away
+	fn apply_hook_commands(&mut self) {
+		if self.entity_manager.has_hooks() {
+			self.entity_manager.apply_commands();
+		}
+	}
+
+	pub fn set_parent(&mut self, child_id: usize, parent_id: usize) -> &mut Self {
+		self.entity_manager.set_parent(child_id, parent_id);
+		self.entity_manager.borrow_type_registry_mut()
+			.register_named::<Parent>("Parent")
+			.register_named::<Children>("Children");
+		self.apply_hook_commands();
+		self
+	}
+
+	pub fn remove_parent(&mut self, child_id: usize) -> &mut Self {
+		self.entity_manager.remove_parent(child_id);
+		self.apply_hook_commands();
+		self
+	}
+
+	pub fn borrow_type_registry(&self) -> &TypeRegistry {
+		self.entity_manager.borrow_type_registry()
+	}
+
+	// path is "ComponentName.field_name" of a reflectable component
+	pub fn get_field(&self, entity_id: usize, path: &str) -> Option<Value> {
+		self.entity_manager.get_field(entity_id, path)
+	}
+
+	pub fn set_field(&mut self, entity_id: usize, path: &str, value: &Value) -> bool {
+		self.entity_manager.set_field(entity_id, path, value)
+	}
+
+	// Components defined at runtime, e.g. by mods or scripts. Systems access
+	// them with the type-erased EntityManager APIs taking a ComponentId.
+	pub fn register_dynamic_component(&mut self, layout: ComponentLayout) -> Option<ComponentId> {
+		self.entity_manager.register_dynamic_component(layout)
+	}
+
+	pub fn get_component_id<T: 'static + Component>(&self) -> ComponentId {
+		self.entity_manager.get_component_id::<T>()
+	}
+
+	pub fn get_dynamic_component_id(&self, name: &str) -> Option<ComponentId> {
+		self.entity_manager.get_dynamic_component_id(name)
+	}
+
+	pub fn add_dynamic_component_to_entity(&mut self, entity_id: usize, component_id: ComponentId, bytes: &[u8]) -> &mut Self {
+		self.entity_manager.add_dynamic_component_to_entity(entity_id, component_id, bytes);
+		self
+	}
+
+	pub fn remove_dynamic_component_from_entity(&mut self, entity_id: usize, component_id: ComponentId) -> &mut Self {
+		self.entity_manager.remove_dynamic_component_from_entity(entity_id, component_id);
+		self
+	}
+
+	pub fn insert_resource<T: 'static>(&mut self, resource: T) -> &mut Self {
+		#[cfg(feature = "serde")]
+		self.record_input(&resource);
+		self.entity_manager.insert_resource(resource);
+		self
+	}
+
+	// Cloneable resources are captured by snapshot()
+	pub fn insert_cloneable_resource<T: 'static + Clone>(&mut self, resource: T) -> &mut Self {
+		#[cfg(feature = "serde")]
+		self.record_input(&resource);
+		self.entity_manager.insert_cloneable_resource(resource);
+		self
+	}
+
+	pub fn remove_resource<T: 'static>(&mut self) -> Option<T> {
+		self.entity_manager.remove_resource::<T>()
+	}
+
+	pub fn borrow_resource<T: 'static>(&self) -> Option<&T> {
+		self.entity_manager.borrow_resource::<T>()
+	}
+
+	pub fn borrow_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+		self.entity_manager.borrow_resource_mut::<T>()
+	}
+
+	pub fn register_event<T: 'static>(&mut self) -> &mut Self {
+		self.entity_manager.register_event::<T>();
+		self
+	}
+
+	pub fn send_event<T: 'static>(&mut self, event: T) -> &mut Self {
+		#[cfg(feature = "serde")]
+		self.record_input(&event);
+		self.entity_manager.send_event(event);
+		self
+	}
+
+	pub fn borrow_events<T: 'static>(&self) -> Option<&Events<T>> {
+		self.entity_manager.borrow_events::<T>()
+	}
+
+	// Stable hash over the entities and the hashable components. Worlds
+	// running the same simulation have the same checksum on any platform.
+	// Can be passed to start_recording() and replay().
+	pub fn checksum(&self) -> u64 {
+		self.checksum_registry.checksum(&self.entity_manager)
+	}
+
+	// Per component type and per entity checksums. Compare reports with
+	// ChecksumReport::find_divergence() to find where worlds diverge.
+	pub fn checksum_report(&self) -> ChecksumReport {
+		self.checksum_registry.report(&self.entity_manager)
+	}
+
+	// Fails in Archetype mode if any component is not cloneable
+	pub fn snapshot(&self) -> Result<WorldSnapshot, SnapshotError> {
+		match self.entity_manager.clone_cloneables() {
+			Ok(entity_manager) => Ok(WorldSnapshot {
+				entity_manager
+			}),
+			Err(type_id) => Err(SnapshotError {
+				component_name: match self.entity_manager.borrow_type_registry().borrow_type_info_by_type_id(type_id) {
+					Some(type_info) => type_info.get_name().to_string(),
+					None => format!("{:?}", type_id)
+				}
+			})
+		}
+	}
+
+	// Components which are not cloneable, including dynamic ones, are
+	// removed, and resources which are not cloneable are left as they are.
+	// Register them as cloneable to make restore() complete.
+	// The restored world iterates entities and components in the same order
+	// as the world at snapshot() did.
+	pub fn restore(&mut self, snapshot: &WorldSnapshot) {
+		// The id caches are rebuilt
+		self.entity_manager.restore_cloneables(&snapshot.entity_manager);
+	}
+
+	// While paused, update() does nothing but handling the inspector
+	// requests unless frames are requested with step()
+	pub fn pause(&mut self) {
+		self.paused = true;
+	}
+
+	pub fn resume(&mut self) {
+		self.paused = false;
+		self.pending_steps = 0;
+	}
+
+	// Pauses and runs the frames in the following update() calls,
+	// one frame per call
+	pub fn step(&mut self, frames: u64) {
+		self.paused = true;
+		self.pending_steps += frames;
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	// Records the wall time of each system, of the command flush after it
+	// and of each frame over the last window frames, into the FrameStats
+	// resource. Restarts if already profiling.
+	pub fn start_profiling(&mut self, window: usize) {
+		self.entity_manager.insert_resource(FrameStats::new(window));
+	}
+
+	pub fn stop_profiling(&mut self) -> Option<FrameStats> {
+		self.entity_manager.remove_resource::<FrameStats>()
+	}
+
+	pub fn borrow_frame_stats(&self) -> Option<&FrameStats> {
+		self.entity_manager.borrow_resource::<FrameStats>()
+	}
+
+	// Registers Timer, Cooldown, Lifetime and the timer events, inserts
+	// the Time resource and adds the system ticking them. Call before
+	// adding the systems using them. All of them are captured by snapshot().
+	pub fn add_time(&mut self, time: Time) -> &mut Self {
+		self
+			.register_cloneable_component::<Timer>()
+			.register_cloneable_component::<Cooldown>()
+			.register_cloneable_component::<Lifetime>()
+			.register_event::<TimerFinished>()
+			.register_event::<CooldownReady>();
+		self.entity_manager.insert_cloneable_resource(time);
+		self.add_system(TimeSystem {})
+	}
+
+	// Registers Tween<T> and TweenCompleted, and adds the system animating
+	// T. Ticked by the Time resource, see add_time().
+	pub fn add_tween<T: 'static + Component>(&mut self) -> &mut Self {
+		self
+			.register_component::<Tween<T>>()
+			.register_event::<TweenCompleted>()
+			.add_system(TweenSystem::<T>::new())
+	}
+
+	// Inserts the Input resource and adds the system updating it. Call
+	// before adding the systems reading the input. Events are pushed to
+	// the queue from Input::get_queue().
+	pub fn add_input(&mut self, input: Input) -> &mut Self {
+		self.entity_manager.insert_resource(input);
+		self.add_system(InputSystem {})
+	}
+
+	// Inserts the SpatialIndex<T> resource and adds the system keeping it
+	// in sync. Call after adding the systems moving the entities.
+	pub fn add_spatial_index<T: 'static + SpatialPosition>(&mut self, index: SpatialIndex<T>) -> &mut Self {
+		self.entity_manager.insert_resource(index);
+		self.add_system(SpatialIndexSystem::<T>::new())
+	}
+
+	// Registers Collider and the collision events, inserts the
+	// CollisionIndex<T> resource and adds the system updating it. Call
+	// after adding the systems moving the entities.
+	#[cfg(feature = "collision2d")]
+	pub fn add_collision2d<T: 'static + SpatialPosition>(&mut self, index: CollisionIndex<T>) -> &mut Self {
+		self
+			.register_component::<Collider>()
+			.register_event::<CollisionStarted>()
+			.register_event::<CollisionEnded>();
+		self.entity_manager.insert_resource(index);
+		self.add_system(CollisionSystem::<T>::new())
+	}
+
+	// Durations of the last update of each system including its command
+	// flush, in the running order. Measured only while profiling, see
+	// start_profiling(), or while an inspector is attached.
+	pub fn borrow_system_timings(&self) -> &Vec<SystemTiming> {
+		&self.system_timings
+	}
+
 	pub fn update(&mut self) {
-		for system in self.systems.iter_mut() {
+		#[cfg(feature = "inspector")]
+		if let Some(inspector) = self.inspector.take() {
+			inspector.poll(self);
+			self.inspector = Some(inspector);
+		}
+
+		if self.paused {
+			if self.pending_steps == 0 {
+				return;
+			}
+			self.pending_steps -= 1;
+		}
+
+		let change_sequence = self.entity_manager.get_change_sequence();
+		let profiling = self.entity_manager.has_resource::<FrameStats>();
+		#[cfg(feature = "inspector")]
+		let timing = profiling || self.inspector.is_some();
+		#[cfg(not(feature = "inspector"))]
+		let timing = profiling;
+		// Reading the clock isn't free, so only when someone looks
+		let frame_start = timing.then(Instant::now);
+		let mut system_spans = Vec::new();
+		for (system, system_timing) in self.systems.iter_mut().zip(self.system_timings.iter_mut()) {
+			if !timing {
+				system.update(&mut self.entity_manager, &mut self.entity_id_accessor);
+				self.entity_manager.apply_commands();
+				self.entity_manager.increment_frame();
+				continue;
+			}
+			let start = Instant::now();
 			system.update(&mut self.entity_manager, &mut self.entity_id_accessor);
+			let commands_start = Instant::now();
+			self.entity_manager.apply_commands();
+			let end = Instant::now();
+			system_timing.duration = end - start;
+			if profiling {
+				system_spans.push((start, commands_start, end));
+			}
 			self.entity_manager.increment_frame();
 		}
+		if profiling {
+			let names = self.system_timings.iter().map(|timing| timing.name.as_str()).collect::<Vec<&str>>();
+			self.entity_manager.borrow_resource_mut::<FrameStats>().unwrap()
+				.record_frame(&names, frame_start.unwrap(), &system_spans);
+		}
+		self.entity_manager.clear_events();
+		// The id caches have seen the component changes so far, and the
+		// systems the ones before this frame
+		self.entity_id_accessor.sync(&self.entity_manager);
+		self.entity_manager.clear_changes_before(change_sequence);
+
+		#[cfg(feature = "serde")]
+		if let Some(mut recorder) = self.recorder.take() {
+			recorder.end_frame(self);
+			self.recorder = Some(recorder);
+		}
+	}
+
+	// Starts accepting inspector clients on the localhost port, 0 for any
+	// free port. Requests are handled in update(). See inspector.rs.
+	#[cfg(feature = "inspector")]
+	pub fn start_inspector(&mut self, port: u16) -> io::Result<SocketAddr> {
+		let inspector = Inspector::bind(port)?;
+		let address = inspector.get_local_addr();
+		self.inspector = Some(inspector);
+		Ok(address)
+	}
+
+	#[cfg(feature = "inspector")]
+	pub fn stop_inspector(&mut self) {
+		self.inspector = None;
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn save<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		WorldSerializer::new(&self.entity_manager, &self.serialization_registry)
+			.serialize(serializer)
+	}
+
+	// Replaces all the entities with the saved ones. Entities get new ids,
+	// the returned map is saved id -> new id. The world may be partially
+	// loaded if an error is returned.
+	#[cfg(feature = "serde")]
+	pub fn load<'de, D: Deserializer<'de>>(&mut self, deserializer: D) -> Result<HashMap<usize, usize>, D::Error> {
+		for entity_id in self.entity_manager.get_entity_ids() {
+			self.entity_manager.remove_entity(entity_id);
+		}
+		// Before the removed ids are reused
+		self.apply_hook_commands();
+		let mut entity_map = HashMap::new();
+		let result = WorldDeserializer::new(&mut self.entity_manager, &self.serialization_registry, &mut entity_map)
+			.deserialize(deserializer);
+		if let Err(error) = result {
+			self.apply_hook_commands();
+			return Err(error);
+		}
+		self.serialization_registry.map_entities(&mut self.entity_manager, &entity_map);
+		self.apply_hook_commands();
+		Ok(entity_map)
+	}
+
+	// Makes the entity ids in the component rewritten on load(), as the
+	// entities get new ids. The component must be serializable too.
+	#[cfg(feature = "serde")]
+	pub fn register_map_entities_component<T>(&mut self) -> &mut Self
+	where T: 'static + Component + MapEntities {
+		self.serialization_registry.register_map_entities::<T>();
+		self
+	}
+
+	// Makes Parent and Children saved and loaded, with their entity ids
+	// rewritten like register_map_entities_component()
+	#[cfg(feature = "serde")]
+	pub fn register_serializable_hierarchy(&mut self) -> &mut Self {
+		self.entity_manager.register_cloneable::<Parent>();
+		self.entity_manager.register_cloneable::<Children>();
+		self
+			.register_serializable_component::<Parent>("Parent")
+			.register_serializable_component::<Children>("Children")
+			.register_map_entities_component::<Parent>()
+			.register_map_entities_component::<Children>()
+	}
+
+	// Saves the entities in the same format as save(), to be loaded with
+	// load_scene(). References to entities not in entity_ids are kept as
+	// they are, see MapEntities.
+	#[cfg(feature = "serde")]
+	pub fn save_scene<S: Serializer>(&self, entity_ids: &[usize], serializer: S) -> Result<S::Ok, S::Error> {
+		WorldSerializer::with_entity_ids(&self.entity_manager, &self.serialization_registry, entity_ids)
+			.serialize(serializer)
+	}
+
+	// Loads the entities of a scene or of a save as new entities next to the
+	// existing ones. The entities get SceneMember components with the
+	// returned instance id. Nothing is added if loading fails.
+	#[cfg(feature = "serde")]
+	pub fn load_scene<'de, D: Deserializer<'de>>(&mut self, deserializer: D) -> Result<SceneInstanceId, D::Error> {
+		let mut entity_map = HashMap::new();
+		let result = WorldDeserializer::new(&mut self.entity_manager, &self.serialization_registry, &mut entity_map)
+			.deserialize(deserializer);
+		if let Err(error) = result {
+			for entity_id in entity_map.values() {
+				self.entity_manager.remove_entity(*entity_id);
+			}
+			self.apply_hook_commands();
+			return Err(error);
+		}
+		self.serialization_registry.map_entities(&mut self.entity_manager, &entity_map);
+
+		let instance_id = self.scene_instances.create_instance_id();
+		self.entity_manager.register_cloneable::<SceneMember>();
+		self.entity_manager.borrow_type_registry_mut().register_debuggable::<SceneMember>();
+		for entity_id in entity_map.values() {
+			self.entity_manager.add_component_to_entity(*entity_id, SceneMember::new(instance_id));
+		}
+		self.scene_instances.insert(instance_id, entity_map);
+		self.apply_hook_commands();
+		Ok(instance_id)
+	}
+
+	// Removes the entities the scene instance created which still exist
+	#[cfg(feature = "serde")]
+	pub fn unload_scene(&mut self, instance_id: SceneInstanceId) {
+		let entity_map = match self.scene_instances.remove(instance_id) {
+			Some(entity_map) => entity_map,
+			None => {
+				// @TODO: Better error handling
+				println!("Unknown scene instance");
+				return;
+			}
+		};
+		for entity_id in entity_map.values() {
+			// The id may be reused by another entity after removal
+			let is_member = self.entity_manager.borrow_component::<SceneMember>(*entity_id)
+				.map(|member| member.get_instance_id() == instance_id)
+				.unwrap_or(false);
+			if is_member {
+				self.entity_manager.remove_entity(*entity_id);
+			}
+		}
+		self.apply_hook_commands();
+	}
+
+	// Saved id -> new id of the entities the scene instance created, e.g.
+	// to find a door of a room
+	#[cfg(feature = "serde")]
+	pub fn borrow_scene_entity_map(&self, instance_id: SceneInstanceId) -> Option<&HashMap<usize, usize>> {
+		self.scene_instances.borrow_entity_map(instance_id)
+	}
+
+	// Inputs are resources and events injected from outside of the systems.
+	// Inserting or sending them is recorded while recording.
+	#[cfg(feature = "serde")]
+	pub fn register_input_resource<T>(&mut self, name: &str) -> &mut Self
+	where T: 'static + Clone + Serialize + for<'de> Deserialize<'de> {
+		self.input_registry.register_resource::<T>(name);
+		self
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn register_input_event<T>(&mut self, name: &str) -> &mut Self
+	where T: 'static + Clone + Serialize + for<'de> Deserialize<'de> {
+		self.entity_manager.register_event::<T>();
+		self.input_registry.register_event::<T>(name);
+		self
+	}
+
+	// Records the inputs injected before each update() call. If checksum_fn
+	// is given, the world checksum after each update() is recorded too so
+	// that replay() can detect divergence.
+	#[cfg(feature = "serde")]
+	pub fn start_recording(&mut self, checksum_fn: Option<fn(&World) -> u64>) {
+		self.recorder = Some(Recorder::new(checksum_fn));
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn stop_recording(&mut self) -> Option<Replay> {
+		self.recorder.take().map(|recorder| recorder.finish())
+	}
+
+	#[cfg(feature = "serde")]
+	fn record_input<T: 'static>(&mut self, input: &T) {
+		if let Some(recorder) = self.recorder.as_mut() {
+			recorder.record(&self.input_registry, input);
+		}
+	}
+
+	// Feeds the recorded inputs and calls update() for each recorded frame.
+	// The world should be set up the same as the recorded one was when
+	// the recording started.
+	#[cfg(feature = "serde")]
+	pub fn replay(&mut self, replay: &Replay, checksum_fn: Option<fn(&World) -> u64>) -> Result<(), ReplayDivergence> {
+		for frame in 0..replay.get_frame_count() {
+			apply_inputs(&mut self.entity_manager, &self.input_registry, replay, frame);
+			self.update();
+			if let (Some(checksum_fn), Some(expected)) = (checksum_fn, replay.get_checksum(frame)) {
+				let actual = checksum_fn(self);
+				if actual != expected {
+					return Err(ReplayDivergence {
+						frame,
+						expected,
+						actual
+					});
+				}
+			}
+		}
+		Ok(())
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn save_replay<S: Serializer>(&self, replay: &Replay, serializer: S) -> Result<S::Ok, S::Error> {
+		ReplaySerializer::new(replay, &self.input_registry).serialize(serializer)
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn load_replay<'de, D: Deserializer<'de>>(&self, deserializer: D) -> Result<Replay, D::Error> {
+		ReplayDeserializer::new(&self.input_registry).deserialize(deserializer)
 	}
 }
@@ -5,6 +5,7 @@ use wasm_bindgen::JsCast;
 use ecs_rust::world::World;
 use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
 use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
 use ecs_rust::system::System;
 
 const CANVAS_ID: &str = "canvas";
@@ -47,9 +48,11 @@ struct Circle {
 }
 
 impl Component for Position {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Circle {
+	type Storage = DenseStorage<Self>;
 }
 
 struct RenderSystem {
@@ -7,6 +7,7 @@ use wasm_bindgen::JsCast;
 use ecs_rust::world::World;
 use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
 use ecs_rust::component::Component;
+use ecs_rust::storage::DenseStorage;
 use ecs_rust::system::System;
 
 const CANVAS_ID: &str = "canvas";
@@ -70,18 +71,23 @@ struct Collidable {
 }
 
 impl Component for CanvasSize {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Position {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Velocity {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Circle {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Collidable {
+	type Storage = DenseStorage<Self>;
 }
 
 struct MoveSystem {
@@ -129,37 +135,22 @@ impl System for ReflectBoundarySystem {
 }
 
 impl System for CollisionCheckSystem {
-	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
-		let ids = accessor.borrow_ids_for_triple::<Position, Circle, Collidable>(manager).unwrap();
-		for id in ids.iter() {
-			let collidable = manager.borrow_component_mut::<Collidable>(*id).unwrap();
+	fn update(&mut self, manager: &mut EntityManager, _accessor: &mut EntityIdAccessor) {
+		manager.for_each_mut::<Collidable, _>(|_id, collidable| {
 			collidable.collided = false;
-		}
-		for i in 0..ids.len() {
-			for j in (i+1)..ids.len() {
-				if self.check_collision(manager, ids[i], ids[j]) {
-					let collidable = manager.borrow_component_mut::<Collidable>(ids[i]).unwrap();
-					collidable.collided = true;
-					let collidable = manager.borrow_component_mut::<Collidable>(ids[j]).unwrap();
-					collidable.collided = true;
-				}
+		});
+		let mut combinations = manager.iter_combinations_mut::<(Position, Circle, Collidable), 2>();
+		while let Some([(_, (position1, circle1, collidable1)), (_, (position2, circle2, collidable2))]) = combinations.fetch_next() {
+			let dx = position1.x - position2.x;
+			let dy = position1.y - position2.y;
+			if (dx * dx + dy * dy).sqrt() < (circle1.radius + circle2.radius) {
+				collidable1.collided = true;
+				collidable2.collided = true;
 			}
 		}
 	}
 }
 
-impl CollisionCheckSystem {
-	fn check_collision(&self, manager: &EntityManager, entity_id1: usize, entity_id2: usize) -> bool {
-		let position1 = manager.borrow_component::<Position>(entity_id1).unwrap();
-		let circle1 = manager.borrow_component::<Circle>(entity_id1).unwrap();
-		let position2 = manager.borrow_component::<Position>(entity_id2).unwrap();
-		let circle2 = manager.borrow_component::<Circle>(entity_id2).unwrap();
-		let dx = position1.x - position2.x;
-		let dy = position1.y - position2.y;
-		(dx * dx + dy * dy).sqrt() < (circle1.radius + circle2.radius)
-	}
-}
-
 impl System for RenderSystem {
 	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
 		let (canvas_width, canvas_height) = {
@@ -7,6 +7,8 @@ use wasm_bindgen::JsCast;
 use ecs_rust::world::World;
 use ecs_rust::entity_manager::{EntityIdAccessor, EntityManager};
 use ecs_rust::component::Component;
+use ecs_rust::storage::{DenseStorage, MarkerStorage};
+use ecs_rust::input::{Input, InputEvent};
 use ecs_rust::system::System;
 
 const CANVAS_ID: &str = "canvas";
@@ -50,31 +52,6 @@ fn get_context() -> web_sys::CanvasRenderingContext2d {
 		.unwrap()
 }
 
-struct UserInputBuffer {
-	x: f64,
-	y: f64
-}
-
-static mut USER_INPUT_BUFFER: UserInputBuffer = UserInputBuffer{x: -1.0, y: -1.0};
-
-// @TODO: Any way to avoid unsafe block?
-
-fn fetch_user_input_buffer() -> (f64, f64) {
-	unsafe {
-		let (x, y) = (USER_INPUT_BUFFER.x, USER_INPUT_BUFFER.y);
-		USER_INPUT_BUFFER.x = -1.0;
-		USER_INPUT_BUFFER.y = -1.0;
-		(x, y)
-	}
-}
-
-fn update_user_input_buffer(x: f64, y: f64) {
-	unsafe {
-		USER_INPUT_BUFFER.x = x;
-		USER_INPUT_BUFFER.y = y;
-	}
-}
-
 // Components and Systems define
 
 struct CanvasSize {
@@ -82,6 +59,7 @@ struct CanvasSize {
 	height: f64
 }
 
+#[derive(Default)]
 struct UserObject {
 }
 
@@ -120,33 +98,41 @@ struct RenderSystem {
 // Components and Systems implementation
 
 impl Component for CanvasSize {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for UserObject {
+	type Storage = MarkerStorage<Self>;
 }
 
 impl Component for Position {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Velocity {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Circle {
+	type Storage = DenseStorage<Self>;
 }
 
 impl Component for Collidable {
+	type Storage = DenseStorage<Self>;
 }
 
 impl System for UserInputReflectSystem {
 	fn update(&mut self, manager: &mut EntityManager, accessor: &mut EntityIdAccessor) {
+		let input = manager.borrow_resource::<Input>().unwrap();
+		if !input.is_pointer_moved() {
+			return;
+		}
+		let [x, y] = input.get_pointer_position().unwrap();
 		let ids = accessor.borrow_ids_for_pair::<Position, UserObject>(manager).unwrap();
 		for id in ids.iter() {
-			let (x, y) = fetch_user_input_buffer();
-			if x != -1.0 || y != -1.0 {
-				let position = manager.borrow_component_mut::<Position>(*id).unwrap();
-				position.x = x;
-				position.y = y;
-			}
+			let position = manager.borrow_component_mut::<Position>(*id).unwrap();
+			position.x = x as f64;
+			position.y = y as f64;
 		}
 	}
 }
@@ -346,7 +332,11 @@ pub fn start() {
 			.add_component_to_entity(entity_id, Collidable {});
 	}
 
+	let input = Input::new();
+	let input_queue = input.get_queue();
+
 	world
+		.add_input(input)
 		.add_system(UserInputReflectSystem {})
 		.add_system(MoveSystem {})
 		.add_system(ReflectBoundarySystem {})
@@ -355,8 +345,12 @@ pub fn start() {
 
 	{
 		let canvas = get_canvas();
+		let input_queue = input_queue.clone();
 		let closure = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
-			update_user_input_buffer(event.offset_x() as f64, event.offset_y() as f64);
+			input_queue.push(InputEvent::PointerMoved {
+				x: event.offset_x() as f32,
+				y: event.offset_y() as f32
+			});
 		}) as Box<dyn FnMut(_)>);
 		canvas.add_event_listener_with_callback("mousemove", closure.as_ref().unchecked_ref()).unwrap();
 		closure.forget();
@@ -364,15 +358,16 @@ pub fn start() {
 
 	{
 		let canvas = get_canvas();
+		let input_queue = input_queue.clone();
 		let closure = Closure::wrap(Box::new(move |event: web_sys::TouchEvent| {
 			let page_x = event.touches().get(0).unwrap().page_x();
 			let page_y = event.touches().get(0).unwrap().page_y();
 			let offset_left = event.touches().get(0).unwrap().target().unwrap().dyn_ref::<web_sys::HtmlElement>().unwrap().offset_left();
 			let offset_top = event.touches().get(0).unwrap().target().unwrap().dyn_ref::<web_sys::HtmlElement>().unwrap().offset_top();
-			update_user_input_buffer(
-				(page_x - offset_left) as f64,
-				(page_y - offset_top) as f64
-			);
+			input_queue.push(InputEvent::PointerMoved {
+				x: (page_x - offset_left) as f32,
+				y: (page_y - offset_top) as f32
+			});
 		}) as Box<dyn FnMut(_)>);
 		canvas.add_event_listener_with_callback("touchstart", closure.as_ref().unchecked_ref()).unwrap();
 		canvas.add_event_listener_with_callback("touchmove", closure.as_ref().unchecked_ref()).unwrap();
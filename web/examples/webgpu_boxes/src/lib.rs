@@ -10,6 +10,7 @@ use ecs_rust::{
 		EntityManager,
 	},
 	component::Component,
+	storage::DenseStorage,
 	system::System,
 };
 use wgpu_rust_renderer::{
@@ -82,12 +83,24 @@ struct Renderer {
 	renderer: WGPUWebRenderer,
 }
 
-impl Component for RotationSpeed {}
-impl Component for SceneComponent {}
-impl Component for CameraComponent {}
-impl Component for BoxComponent {}
-impl Component for Pools {}
-impl Component for Renderer {}
+impl Component for RotationSpeed {
+	type Storage = DenseStorage<Self>;
+}
+impl Component for SceneComponent {
+	type Storage = DenseStorage<Self>;
+}
+impl Component for CameraComponent {
+	type Storage = DenseStorage<Self>;
+}
+impl Component for BoxComponent {
+	type Storage = DenseStorage<Self>;
+}
+impl Component for Pools {
+	type Storage = DenseStorage<Self>;
+}
+impl Component for Renderer {
+	type Storage = DenseStorage<Self>;
+}
 
 struct AnimationSystem {}
 struct SceneUpdateSystem {}